@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+pub use hpos_hc_connect::holo_config::CleanupPolicy;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+
+/// What happens to an enabled app during clean-up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupAction {
+    Keep,
+    Disable,
+    Uninstall,
+}
+
+/// Clean-up decision for one enabled app with the rule it was made by
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CleanupDecision {
+    pub app_id: String,
+    pub action: CleanupAction,
+    pub reason: String,
+}
+
+impl fmt::Display for CleanupDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            CleanupAction::Keep => "keeping",
+            CleanupAction::Disable => "disabling",
+            CleanupAction::Uninstall => "uninstalling",
+        };
+        write!(f, "{} {}: {}", action, self.app_id, self.reason)
+    }
+}
+
+/// `CleanupPolicy` with its patterns compiled
+#[derive(Debug)]
+pub struct CleanupRules {
+    policy: CleanupPolicy,
+    keep: Vec<(String, Regex)>,
+    keep_regex: Vec<Regex>,
+    protected: Vec<(String, Regex)>,
+    never_touch: Vec<(String, Regex)>,
+}
+
+impl Default for CleanupRules {
+    fn default() -> Self {
+        CleanupRules::new(&CleanupPolicy::default()).expect("default policy has no patterns")
+    }
+}
+
+impl CleanupRules {
+    pub fn new(policy: &CleanupPolicy) -> Result<Self> {
+        let globs = |patterns: &[String]| -> Result<Vec<(String, Regex)>> {
+            patterns
+                .iter()
+                .map(|glob| Ok((glob.clone(), glob_to_regex(glob)?)))
+                .collect()
+        };
+        Ok(CleanupRules {
+            keep: globs(&policy.keep)?,
+            keep_regex: policy
+                .keep_regex
+                .iter()
+                .map(|re| Regex::new(re).context(format!("invalid keep_regex {}", re)))
+                .collect::<Result<_>>()?,
+            protected: globs(&policy.protected)?,
+            never_touch: globs(&policy.never_touch)?,
+            policy: policy.clone(),
+        })
+    }
+
+    /// Glob of `never_touch` matching the app, if any
+    pub fn never_touch(&self, app_id: &str) -> Option<&str> {
+        matching_glob(&self.never_touch, app_id)
+    }
+
+    /// Decides about an enabled app, `happs_to_keep` are the app ids of the happs file
+    pub fn decide(&self, app_id: &str, happs_to_keep: &[String]) -> CleanupDecision {
+        let decision = |action, reason: String| CleanupDecision {
+            app_id: app_id.to_string(),
+            action,
+            reason,
+        };
+
+        if happs_to_keep.iter().any(|id| id == app_id) {
+            return decision(CleanupAction::Keep, "listed in happs file".to_string());
+        }
+        if let Some(glob) = self.never_touch(app_id) {
+            return decision(CleanupAction::Keep, format!("matches never_touch {}", glob));
+        }
+        if self.policy.keep_hosted && app_id.contains("uhCkk") {
+            return decision(CleanupAction::Keep, "hosted happ".to_string());
+        }
+        if let Some(glob) = matching_glob(&self.keep, app_id) {
+            return decision(CleanupAction::Keep, format!("matches keep {}", glob));
+        }
+        if let Some(re) = self.keep_regex.iter().find(|re| re.is_match(app_id)) {
+            return decision(CleanupAction::Keep, format!("matches keep_regex {}", re));
+        }
+        let (action, reason) = self.removal(app_id);
+        decision(action, format!("not in happs file, {}", reason))
+    }
+
+    /// How an app that is going away gets removed: disabled when protected
+    /// or `disable_instead_of_uninstall` is set, uninstalled otherwise
+    pub fn removal(&self, app_id: &str) -> (CleanupAction, String) {
+        if let Some(glob) = matching_glob(&self.protected, app_id) {
+            (CleanupAction::Disable, format!("protected by {}", glob))
+        } else if self.policy.disable_instead_of_uninstall {
+            (
+                CleanupAction::Disable,
+                "disable_instead_of_uninstall is set".to_string(),
+            )
+        } else {
+            (
+                CleanupAction::Uninstall,
+                "not kept by cleanup policy".to_string(),
+            )
+        }
+    }
+}
+
+fn matching_glob<'a>(globs: &'a [(String, Regex)], app_id: &str) -> Option<&'a str> {
+    globs
+        .iter()
+        .find(|(_, re)| re.is_match(app_id))
+        .map(|(glob, _)| glob.as_str())
+}
+
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).context(format!("invalid glob {}", glob))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_default_policy() {
+        let rules = CleanupRules::default();
+        let happs_to_keep = vec!["elemental-chat:2".to_string(), "hha:1".to_string()];
+        let app_1 = "elemental-chat:1";
+        let app_2 = "elemental-chat:2";
+        let app_3 = "uhCkkcF0X1dpwHFeIPI6-7rzM6ma9IgyiqD-othxgENSkL1So1Slt::servicelogger";
+        let app_4 = "other-app";
+
+        let action = |app| rules.decide(app, &happs_to_keep).action;
+        assert_eq!(action(app_1), CleanupAction::Uninstall);
+        assert_eq!(action(app_2), CleanupAction::Keep); // because it is in config
+        assert_eq!(action(app_3), CleanupAction::Keep); // because it is hosted
+        assert_eq!(action(app_4), CleanupAction::Uninstall);
+    }
+
+    #[test]
+    fn verify_configured_policy() {
+        let rules = CleanupRules::new(&CleanupPolicy {
+            keep: vec!["debug-*".to_string()],
+            keep_regex: vec!["^test-[0-9]+$".to_string()],
+            protected: vec!["holofuel:?".to_string()],
+            never_touch: vec!["manual*".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let decide = |app| rules.decide(app, &[]);
+
+        assert_eq!(decide("debug-chat").action, CleanupAction::Keep);
+        assert_eq!(decide("test-12").action, CleanupAction::Keep);
+        assert_eq!(decide("test-12a").action, CleanupAction::Uninstall);
+        assert_eq!(decide("holofuel:1").action, CleanupAction::Disable);
+        assert_eq!(decide("holofuel:12").action, CleanupAction::Uninstall);
+        assert_eq!(
+            decide("manual-app"),
+            CleanupDecision {
+                app_id: "manual-app".to_string(),
+                action: CleanupAction::Keep,
+                reason: "matches never_touch manual*".to_string(),
+            }
+        );
+
+        let rules = CleanupRules::new(&CleanupPolicy {
+            keep_hosted: false,
+            disable_instead_of_uninstall: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            rules.decide("uhCkkabc::servicelogger", &[]).action,
+            CleanupAction::Disable
+        );
+    }
+}
@@ -1,28 +1,32 @@
 use anyhow::{Context, Result};
 pub use hpos_hc_connect::AdminWebsocket;
+pub use hpos_hc_connect::HposEnvironment;
+use hpos_hc_connect::{
+    hbs::HbsClient, hha_agent::CoreAppAgent, hpos_agent::Agent, hpos_membrane_proof,
+};
 pub use hpos_hc_connect::{
     holo_config::{Config, Happ, HappsFile, MembraneProofFile, ProofPayload},
-    utils::{download_file, extract_zip},
+    utils::{download_file, extract_zip, verify_sha256},
 };
-use hpos_hc_connect::{hpos_agent::Agent, hpos_membrane_proof};
 use std::collections::HashMap;
-use std::sync::Arc;
 use tracing::{debug, info, instrument, warn};
 
-mod utils;
-
+pub mod cleanup;
 pub mod jurisdictions;
-use jurisdictions::HbsClient;
+pub mod plan;
+pub mod upgrade;
+use cleanup::{CleanupAction, CleanupRules};
+use plan::{AppAction, Plan};
 
-#[instrument(err, skip(config))]
-pub async fn run(config: Config) -> Result<()> {
+#[instrument(err, skip(config, env))]
+pub async fn run(config: Config, env: &HposEnvironment) -> Result<()> {
     debug!("Starting configure holochain...");
 
     let happ_file = HappsFile::load_happ_file(&config.happs_file_path)
         .context("failed to load hApps YAML config")?;
-    install_happs(&happ_file, &config).await?;
+    install_happs(&happ_file, &config, env).await?;
 
-    if let Err(e) = update_host_jurisdiction_if_changed(&config).await {
+    if let Err(e) = update_host_jurisdiction_if_changed(&config, env).await {
         warn!(
             "Note: This is only needed for holoports. Failed to update jurisdiction.  Error: {}",
             e
@@ -34,7 +38,11 @@ pub async fn run(config: Config) -> Result<()> {
 
 /// based on the config file provided this installs the core apps on the holoport
 /// It manages getting the mem-proofs and properties that are expected to be used on the holoport
-pub async fn install_happs(happ_file: &HappsFile, config: &Config) -> Result<()> {
+pub async fn install_happs(
+    happ_file: &HappsFile,
+    config: &Config,
+    env: &HposEnvironment,
+) -> Result<()> {
     let mut admin_websocket = AdminWebsocket::connect(config.admin_port)
         .await
         .context("failed to connect to holochain's admin interface")?;
@@ -46,61 +54,123 @@ pub async fn install_happs(happ_file: &HappsFile, config: &Config) -> Result<()>
         warn!(port = ?config.happ_port, ?error, "failed to start app interface for hosted happs, maybe it's already up?");
     }
 
-    let agent = Agent::init(admin_websocket.clone()).await?;
+    let agent = Agent::init(admin_websocket.clone(), env).await?;
 
     debug!("Agent key for all core happs {:?}", agent.admin.key);
 
-    debug!("Getting a list of active happ");
-    let active_happs = Arc::new(
-        admin_websocket
-            .list_enabled_apps()
-            .await
-            .context("failed to get installed hApps")?,
-    );
-
     let happs_to_install: Vec<&Happ> = happ_file
         .core_happs
         .iter()
         .chain(happ_file.self_hosted_happs.iter())
         .collect();
 
+    let rules = CleanupRules::new(&happ_file.cleanup).context("invalid cleanup policy")?;
+
+    debug!("Getting a list of installed happs");
+    let plan = Plan::fetch(
+        &mut admin_websocket,
+        &happs_to_install,
+        config.ui_store_folder.as_deref(),
+        &rules,
+        env,
+    )
+    .await?;
+
     for happ in &happs_to_install {
-        if active_happs.contains(&happ.id()) {
-            info!("App {} already installed, just downloading UI", &happ.id());
-        } else {
-            info!("Installing app {}", &happ.id());
-            let mem_proof_vec =
-                hpos_membrane_proof::create_vec_for_happ(happ, agent.membrane_proof.clone())
-                    .await?;
+        match plan.action_for(&happ.id(env)) {
+            AppAction::Skip => {
+                info!(
+                    "App {} already installed, just downloading UI",
+                    &happ.id(env)
+                );
+            }
+            AppAction::Enable => {
+                info!(
+                    "app {} was previously installed, re-activating",
+                    &happ.id(env)
+                );
+                admin_websocket.activate_app(happ, env).await?;
+            }
+            AppAction::Upgrade(previous) => {
+                let previous = admin_websocket
+                    .list_apps(None)
+                    .await?
+                    .into_iter()
+                    .find(|app| app.installed_app_id == previous)
+                    .context(format!("previous version {} disappeared", previous))?;
+                let mem_proof_vec = hpos_membrane_proof::create_vec_for_happ(
+                    happ,
+                    agent.membrane_proof.clone(),
+                    env,
+                )
+                .await?;
+                upgrade::upgrade(
+                    &mut admin_websocket,
+                    happ,
+                    &previous,
+                    mem_proof_vec,
+                    agent.clone(),
+                    &rules,
+                    env,
+                )
+                .await?;
+            }
+            AppAction::Install => {
+                info!("Installing app {}", &happ.id(env));
+                let mem_proof_vec = hpos_membrane_proof::create_vec_for_happ(
+                    happ,
+                    agent.membrane_proof.clone(),
+                    env,
+                )
+                .await?;
 
-            if let Err(err) = admin_websocket
-                .install_and_activate_app(happ, Some(mem_proof_vec), agent.clone(), HashMap::new())
-                .await
-            {
-                if err.to_string().contains("AppAlreadyInstalled") {
-                    info!("app {} was previously installed, re-activating", &happ.id());
-                    admin_websocket.activate_app(happ).await?;
-                } else {
-                    return Err(err);
+                let bundle = happ.bundle(env).await?;
+                if happ.expects_dna_hashes() {
+                    happ.verify_dna_hashes(&Happ::role_dnas(&bundle, env).await?)?;
+                }
+                if let Err(err) = admin_websocket
+                    .install_and_activate_app(
+                        happ,
+                        bundle,
+                        Some(mem_proof_vec),
+                        agent.clone(),
+                        HashMap::new(),
+                        env,
+                    )
+                    .await
+                {
+                    if err.to_string().contains("AppAlreadyInstalled") {
+                        info!(
+                            "app {} was previously installed, re-activating",
+                            &happ.id(env)
+                        );
+                        admin_websocket.activate_app(happ, env).await?;
+                    } else {
+                        return Err(err);
+                    }
                 }
             }
         }
-        install_ui(happ, config).await?
+        install_ui(happ, config, env).await?
     }
 
     // Clean-up part of the script
     // This clean up will remove any old app that were installed by the old config file
     // This will also include removing happs that were installed with the old UID
     // This will leave old servicelogger instances and old hosted happs. (That should be cleaned by the holo-auto-installer service)
-    let happs_to_keep: Vec<String> = happs_to_install.iter().map(|happ| happ.id()).collect();
-
-    for app in &*active_happs {
-        let installed_app_id = app.to_string();
-        if !utils::keep_app_active(&installed_app_id, happs_to_keep.clone()) {
-            info!("deactivating app {}", &installed_app_id);
-            admin_websocket
-                .uninstall_app(&installed_app_id, false)
-                .await?;
+    // What is kept, disabled or uninstalled is decided by the cleanup policy of the happs file
+    for decision in &plan.cleanup {
+        info!("{}", decision);
+        match decision.action {
+            CleanupAction::Keep => {}
+            CleanupAction::Disable => {
+                admin_websocket.disable_app(&decision.app_id).await?;
+            }
+            CleanupAction::Uninstall => {
+                admin_websocket
+                    .uninstall_app(&decision.app_id, false)
+                    .await?;
+            }
         }
     }
 
@@ -108,40 +178,70 @@ pub async fn install_happs(happ_file: &HappsFile, config: &Config) -> Result<()>
     Ok(())
 }
 
+/// Computes what `install_happs` would do with `happ_file` on the conductor, without changing it
+pub async fn plan_happs(
+    happ_file: &HappsFile,
+    config: &Config,
+    env: &HposEnvironment,
+) -> Result<Plan> {
+    let mut admin_websocket = AdminWebsocket::connect(config.admin_port)
+        .await
+        .context("failed to connect to holochain's admin interface")?;
+
+    let happs: Vec<&Happ> = happ_file
+        .core_happs
+        .iter()
+        .chain(happ_file.self_hosted_happs.iter())
+        .collect();
+
+    let rules = CleanupRules::new(&happ_file.cleanup).context("invalid cleanup policy")?;
+
+    Plan::fetch(
+        &mut admin_websocket,
+        &happs,
+        config.ui_store_folder.as_deref(),
+        &rules,
+        env,
+    )
+    .await
+}
+
 /// Install the UI based on the zip files that are provided in the config
-#[instrument(err, skip(happ, config))]
-async fn install_ui(happ: &Happ, config: &Config) -> Result<()> {
-    let source_path = match happ.ui_path.clone() {
+#[instrument(err, skip(happ, config, env))]
+async fn install_ui(happ: &Happ, config: &Config, env: &HposEnvironment) -> Result<()> {
+    let source_path = match happ
+        .download_ui(env)
+        .await
+        .context("failed to download UI archive")?
+    {
         Some(path) => path,
         None => {
-            if happ.ui_url.is_none() {
-                debug!("ui_url == None, skipping UI installation for {}", happ.id());
-                return Ok(());
-            }
-            download_file(happ.ui_url.as_ref().unwrap())
-                .await
-                .context("failed to download UI archive")?
+            debug!(
+                "ui_url == None, skipping UI installation for {}",
+                happ.id(env)
+            );
+            return Ok(());
         }
     };
     if let Some(ui_home) = config.ui_store_folder.clone() {
         let unpack_path = ui_home.join(happ.ui_name());
         extract_zip(&source_path, &unpack_path).context("failed to extract UI archive")?;
-        debug!("installed UI: {}", happ.id());
+        debug!("installed UI: {}", happ.id(env));
     }
     Ok(())
 }
 
-pub async fn update_host_jurisdiction_if_changed(config: &Config) -> Result<()> {
-    if let Ok(is_integration_test) = std::env::var("IS_INTEGRATION_TEST") {
-        if is_integration_test == "TRUE" {
-            // set in ../tests/integration.rs and ../../holo_happ_manager/tests/integration.ts
-            return Ok(());
-        }
+pub async fn update_host_jurisdiction_if_changed(
+    config: &Config,
+    env: &HposEnvironment,
+) -> Result<()> {
+    if env.skip_jurisdiction_update {
+        debug!("skipping jurisdiction update");
+        return Ok(());
     }
 
-    // get current jurisdiction in hbs
-    let hbs = HbsClient::connect().await?;
-    let hbs_jurisdiction = hbs.get_host_registration().await?.jurisdiction;
+    let hbs = HbsClient::connect(Some(config), env).await?;
+    let mut agent = CoreAppAgent::spawn(Some(config), env).await?;
 
-    jurisdictions::update_jurisdiction_if_changed(config, hbs_jurisdiction).await
+    jurisdictions::update_jurisdiction_if_changed(&hbs, &mut agent).await
 }
@@ -1,11 +1,41 @@
-use anyhow::Result;
-use configure_holochain::{self, Config};
+use anyhow::{Context, Result};
+use configure_holochain::{self, Config, HappsFile, HposEnvironment};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tracing::debug;
 use tracing_subscriber::EnvFilter;
 
+#[derive(Debug, StructOpt)]
+struct Cli {
+    /// Print the apps that would be installed, enabled or uninstalled and the UIs that would
+    /// be downloaded, without changing the conductor
+    #[structopt(long, alias = "dry-run")]
+    plan: bool,
+    /// TOML or YAML file with the HPOS environment, read from env vars if not given
+    #[structopt(long, env = "HPOS_ENVIRONMENT_FILE")]
+    environment_file: Option<PathBuf>,
+    #[structopt(flatten)]
+    config: Config,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let filter = EnvFilter::from_default_env().add_directive("again=trace".parse().unwrap());
     tracing_subscriber::fmt().with_env_filter(filter).init();
-    let config = Config::load();
-    configure_holochain::run(config).await
+    let cli = Cli::from_args();
+    debug!(config = ?cli.config, "loaded");
+    let env = match &cli.environment_file {
+        Some(path) => HposEnvironment::load(path)?,
+        None => HposEnvironment::from_env(),
+    };
+
+    if cli.plan {
+        let happ_file = HappsFile::load_happ_file(&cli.config.happs_file_path)
+            .context("failed to load hApps YAML config")?;
+        let plan = configure_holochain::plan_happs(&happ_file, &cli.config, &env).await?;
+        print!("{}", plan);
+        return Ok(());
+    }
+
+    configure_holochain::run(cli.config, &env).await
 }
@@ -0,0 +1,394 @@
+use crate::cleanup::{CleanupAction, CleanupDecision, CleanupRules};
+use anyhow::{Context, Result};
+use hpos_hc_connect::holo_config::Happ;
+use hpos_hc_connect::{AdminWebsocket, HposEnvironment};
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What `install_happs` does to a single app of the happs file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppAction {
+    /// Not installed yet
+    Install,
+    /// Not installed yet, replaces the enabled previous version with given id
+    Upgrade(String),
+    /// Installed but disabled
+    Enable,
+    /// Already installed and enabled
+    Skip,
+}
+
+/// New version of an app replacing the enabled previous one, see `upgrade::upgrade`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedUpgrade {
+    pub from: String,
+    pub to: String,
+}
+
+/// UI archive that will be extracted into the UI store folder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UiDownload {
+    pub app_id: String,
+    /// `ui_url` or `ui_path` of the happ
+    pub source: String,
+    pub destination: PathBuf,
+}
+
+/// Diff between a happs file and the apps of a conductor, computed without changing anything
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    pub install: Vec<String>,
+    pub upgrade: Vec<PlannedUpgrade>,
+    pub enable: Vec<String>,
+    pub skip: Vec<String>,
+    /// Clean-up decisions for enabled apps that are not in the happs file,
+    /// except for previous versions which are removed by their upgrade
+    pub cleanup: Vec<CleanupDecision>,
+    pub ui_downloads: Vec<UiDownload>,
+}
+
+impl Plan {
+    /// `installed` are all apps of the conductor, `enabled` the subset that is enabled
+    pub fn new(
+        happs: &[&Happ],
+        installed: &[String],
+        enabled: &[String],
+        ui_store_folder: Option<&Path>,
+        rules: &CleanupRules,
+        env: &HposEnvironment,
+    ) -> Self {
+        let mut plan = Plan::default();
+        let happs_to_keep: Vec<String> = happs.iter().map(|happ| happ.id(env)).collect();
+
+        for happ in happs {
+            let id = happ.id(env);
+            match Self::action(happ, &id, installed, enabled, &happs_to_keep, rules) {
+                AppAction::Install => plan.install.push(id.clone()),
+                AppAction::Upgrade(from) => plan.upgrade.push(PlannedUpgrade {
+                    from,
+                    to: id.clone(),
+                }),
+                AppAction::Enable => plan.enable.push(id.clone()),
+                AppAction::Skip => plan.skip.push(id.clone()),
+            }
+
+            let source = match (&happ.ui_path, &happ.ui_url) {
+                (Some(path), _) => Some(path.display().to_string()),
+                (None, Some(url)) => Some(url.to_string()),
+                (None, None) => None,
+            };
+            if let (Some(source), Some(ui_home)) = (source, ui_store_folder) {
+                plan.ui_downloads.push(UiDownload {
+                    app_id: id,
+                    source,
+                    destination: ui_home.join(happ.ui_name()),
+                });
+            }
+        }
+
+        plan.cleanup = enabled
+            .iter()
+            .filter(|app| !happs_to_keep.contains(app))
+            .filter(|app| !plan.upgrade.iter().any(|upgrade| &&upgrade.from == app))
+            .map(|app| rules.decide(app, &happs_to_keep))
+            .collect();
+
+        plan
+    }
+
+    /// Action planned for an app of the happs file
+    pub fn action_for(&self, id: &str) -> AppAction {
+        if self.install.iter().any(|app| app == id) {
+            AppAction::Install
+        } else if let Some(upgrade) = self.upgrade.iter().find(|upgrade| upgrade.to == id) {
+            AppAction::Upgrade(upgrade.from.clone())
+        } else if self.enable.iter().any(|app| app == id) {
+            AppAction::Enable
+        } else {
+            AppAction::Skip
+        }
+    }
+
+    fn action(
+        happ: &Happ,
+        id: &str,
+        installed: &[String],
+        enabled: &[String],
+        happs_to_keep: &[String],
+        rules: &CleanupRules,
+    ) -> AppAction {
+        if enabled.iter().any(|app| app == id) {
+            AppAction::Skip
+        } else if installed.iter().any(|app| app == id) {
+            AppAction::Enable
+        } else if let Some(previous) = Self::previous_version(happ, enabled, happs_to_keep, rules) {
+            AppAction::Upgrade(previous)
+        } else {
+            AppAction::Install
+        }
+    }
+
+    /// Enabled app with the same name but another version, that the clean-up rules
+    /// would disable or uninstall
+    fn previous_version(
+        happ: &Happ,
+        enabled: &[String],
+        happs_to_keep: &[String],
+        rules: &CleanupRules,
+    ) -> Option<String> {
+        let name = happ.ui_name();
+        enabled
+            .iter()
+            .find(|app| {
+                app.split(':').next() == Some(name.as_str())
+                    && rules.decide(app, happs_to_keep).action != CleanupAction::Keep
+            })
+            .cloned()
+    }
+
+    /// Reads installed and enabled apps from the conductor and diffs them against `happs`
+    pub async fn fetch(
+        admin_websocket: &mut AdminWebsocket,
+        happs: &[&Happ],
+        ui_store_folder: Option<&Path>,
+        rules: &CleanupRules,
+        env: &HposEnvironment,
+    ) -> Result<Self> {
+        let installed = admin_websocket
+            .list_app(None)
+            .await
+            .context("failed to get installed hApps")?;
+        let enabled = admin_websocket
+            .list_enabled_apps()
+            .await
+            .context("failed to get enabled hApps")?;
+        Ok(Self::new(
+            happs,
+            &installed,
+            &enabled,
+            ui_store_folder,
+            rules,
+            env,
+        ))
+    }
+
+    /// Apps the clean-up uninstalls
+    pub fn uninstall(&self) -> Vec<&str> {
+        self.cleanup_with(CleanupAction::Uninstall)
+    }
+
+    /// Apps the clean-up disables
+    pub fn disable(&self) -> Vec<&str> {
+        self.cleanup_with(CleanupAction::Disable)
+    }
+
+    fn cleanup_with(&self, action: CleanupAction) -> Vec<&str> {
+        self.cleanup
+            .iter()
+            .filter(|decision| decision.action == action)
+            .map(|decision| decision.app_id.as_str())
+            .collect()
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.install.is_empty()
+            && self.upgrade.is_empty()
+            && self.enable.is_empty()
+            && self
+                .cleanup
+                .iter()
+                .all(|decision| decision.action == CleanupAction::Keep)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for app in &self.install {
+            writeln!(f, "+ install   {}", app)?;
+        }
+        for upgrade in &self.upgrade {
+            writeln!(f, "^ upgrade   {} from {}", upgrade.to, upgrade.from)?;
+        }
+        for app in &self.enable {
+            writeln!(f, "~ enable    {}", app)?;
+        }
+        for decision in &self.cleanup {
+            let prefix = match decision.action {
+                CleanupAction::Uninstall => "- uninstall",
+                CleanupAction::Disable => "! disable  ",
+                CleanupAction::Keep => "= keep     ",
+            };
+            writeln!(f, "{} {} ({})", prefix, decision.app_id, decision.reason)?;
+        }
+        for app in &self.skip {
+            writeln!(f, "= skip      {}", app)?;
+        }
+        for ui in &self.ui_downloads {
+            writeln!(
+                f,
+                "  ui        {} from {} to {}",
+                ui.app_id,
+                ui.source,
+                ui.destination.display()
+            )?;
+        }
+        if self.is_noop() {
+            writeln!(f, "No changes to installed apps")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn happ(bundle: &str, ui: Option<&str>) -> Happ {
+        Happ {
+            ui_url: ui.map(|url| url.parse().unwrap()),
+            ui_path: None,
+            ui_sha256: None,
+            bundle_url: Some(format!("https://example.com/{}", bundle).parse().unwrap()),
+            bundle_path: None,
+            bundle_sha256: None,
+            dnas: None,
+            agent_bundle_override: None,
+        }
+    }
+
+    #[test]
+    fn diffs_happs_file_against_conductor() {
+        let core = happ("core-app.0_6_2.happ", None);
+        let chat = happ("chat.2.happ", Some("https://example.com/chat.zip"));
+        let new = happ("new.1.happ", None);
+        let installed = vec![
+            "core-app:0_6_2".to_string(),
+            "chat:2".to_string(),
+            "chat:1".to_string(),
+            "uhCkkabc::servicelogger".to_string(),
+        ];
+        let enabled = vec![
+            "core-app:0_6_2".to_string(),
+            "chat:1".to_string(),
+            "uhCkkabc::servicelogger".to_string(),
+        ];
+
+        let plan = Plan::new(
+            &[&core, &chat, &new],
+            &installed,
+            &enabled,
+            Some(Path::new("/var/ui")),
+            &CleanupRules::default(),
+            &HposEnvironment::default(),
+        );
+
+        assert_eq!(plan.install, vec!["new:1"]);
+        assert_eq!(plan.enable, vec!["chat:2"]);
+        assert_eq!(plan.skip, vec!["core-app:0_6_2"]);
+        assert_eq!(plan.uninstall(), vec!["chat:1"]);
+        assert!(plan.disable().is_empty());
+        assert_eq!(plan.cleanup[1].action, CleanupAction::Keep);
+        assert_eq!(
+            plan.ui_downloads,
+            vec![UiDownload {
+                app_id: "chat:2".to_string(),
+                source: "https://example.com/chat.zip".to_string(),
+                destination: PathBuf::from("/var/ui/chat"),
+            }]
+        );
+        assert!(!plan.is_noop());
+    }
+
+    #[test]
+    fn plans_upgrade_from_previous_version() {
+        let core = happ("core-app.0_6_3.happ", None);
+        let enabled = vec!["core-app:0_6_2".to_string(), "other:1".to_string()];
+
+        let plan = Plan::new(
+            &[&core],
+            &enabled,
+            &enabled,
+            None,
+            &CleanupRules::default(),
+            &HposEnvironment::default(),
+        );
+
+        assert!(plan.install.is_empty());
+        assert_eq!(
+            plan.upgrade,
+            vec![PlannedUpgrade {
+                from: "core-app:0_6_2".to_string(),
+                to: "core-app:0_6_3".to_string(),
+            }]
+        );
+        assert_eq!(
+            plan.action_for("core-app:0_6_3"),
+            AppAction::Upgrade("core-app:0_6_2".to_string())
+        );
+        assert_eq!(plan.uninstall(), vec!["other:1"]);
+    }
+
+    #[test]
+    fn kept_apps_are_not_upgraded_from() {
+        let core = happ("core-app.0_6_3.happ", None);
+        let policies = [
+            crate::cleanup::CleanupPolicy {
+                never_touch: vec!["core-app:0_6_2".to_string()],
+                ..Default::default()
+            },
+            crate::cleanup::CleanupPolicy {
+                keep: vec!["core-app:*".to_string()],
+                ..Default::default()
+            },
+            crate::cleanup::CleanupPolicy {
+                keep_regex: vec!["^core-app:0_6_[0-9]$".to_string()],
+                ..Default::default()
+            },
+        ];
+
+        for policy in policies {
+            let enabled = vec!["core-app:0_6_2".to_string()];
+            let rules = CleanupRules::new(&policy).unwrap();
+
+            let plan = Plan::new(
+                &[&core],
+                &enabled,
+                &enabled,
+                None,
+                &rules,
+                &HposEnvironment::default(),
+            );
+
+            assert_eq!(plan.install, vec!["core-app:0_6_3"], "{:?}", policy);
+            assert!(plan.upgrade.is_empty(), "{:?}", policy);
+            assert_eq!(plan.cleanup[0].action, CleanupAction::Keep);
+        }
+    }
+
+    #[test]
+    fn protected_apps_are_upgraded_from() {
+        let core = happ("core-app.0_6_3.happ", None);
+        let enabled = vec!["core-app:0_6_2".to_string()];
+        let rules = CleanupRules::new(&crate::cleanup::CleanupPolicy {
+            protected: vec!["core-app:*".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let plan = Plan::new(
+            &[&core],
+            &enabled,
+            &enabled,
+            None,
+            &rules,
+            &HposEnvironment::default(),
+        );
+
+        assert_eq!(
+            plan.action_for("core-app:0_6_3"),
+            AppAction::Upgrade("core-app:0_6_2".to_string())
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::cleanup::{CleanupAction, CleanupRules};
+use anyhow::{anyhow, Context, Result};
+use holochain_conductor_api::{AppInfo, CellInfo};
+use holochain_types::prelude::{AgentPubKey, CellId, DnaHash, SerializedBytes};
+use hpos_hc_connect::holo_config::Happ;
+use hpos_hc_connect::hpos_agent::Agent;
+use hpos_hc_connect::{AdminWebsocket, HposEnvironment};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+/// How the cells of a previous version of a happ are carried over to the new one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeStrategy {
+    /// Every DNA changed, the new version starts with fresh source chains
+    Reinstall,
+    /// Roles whose DNA did not change keep their cells, other roles get new ones
+    ReuseCells(HashMap<String, CellId>),
+    /// No DNA changed, all cells are kept and only get the new coordinator zomes
+    UpdateCoordinators(HashMap<String, CellId>),
+}
+
+impl UpgradeStrategy {
+    /// A cell is reused when its role still has the same DNA hash, which covers integrity
+    /// zomes and modifiers but not coordinators, and it belongs to the agent installing
+    pub fn choose(
+        new_dnas: &HashMap<String, DnaHash>,
+        previous_cells: &HashMap<String, CellId>,
+        agent_key: &AgentPubKey,
+    ) -> Self {
+        let reused: HashMap<String, CellId> = previous_cells
+            .iter()
+            .filter(|(role, cell_id)| {
+                new_dnas.get(*role) == Some(cell_id.dna_hash())
+                    && cell_id.agent_pubkey() == agent_key
+            })
+            .map(|(role, cell_id)| (role.clone(), cell_id.clone()))
+            .collect();
+
+        if reused.is_empty() {
+            Self::Reinstall
+        } else if reused.len() == new_dnas.len() {
+            Self::UpdateCoordinators(reused)
+        } else {
+            Self::ReuseCells(reused)
+        }
+    }
+
+    pub fn existing_cells(&self) -> HashMap<String, CellId> {
+        match self {
+            Self::Reinstall => HashMap::new(),
+            Self::ReuseCells(cells) | Self::UpdateCoordinators(cells) => cells.clone(),
+        }
+    }
+}
+
+/// Provisioned cells of an installed app by role name
+pub fn provisioned_cells(app: &AppInfo) -> HashMap<String, CellId> {
+    app.cell_info
+        .iter()
+        .filter_map(|(role, cells)| {
+            cells.iter().find_map(|cell| match cell {
+                CellInfo::Provisioned(cell) => Some((role.clone(), cell.cell_id.clone())),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+/// Installs `happ` as the new version of `previous`, moving over what the strategy allows.
+/// The previous version is removed the way `rules` remove apps, and only after the new one
+/// is enabled. Cells taken over by the new version survive uninstalling the previous one.
+pub async fn upgrade(
+    admin_websocket: &mut AdminWebsocket,
+    happ: &Happ,
+    previous: &AppInfo,
+    membrane_proofs: HashMap<String, Arc<SerializedBytes>>,
+    agent: Agent,
+    rules: &CleanupRules,
+    env: &HposEnvironment,
+) -> Result<()> {
+    let bundle = happ.bundle(env).await?;
+    let role_dnas = Happ::role_dnas(&bundle, env)
+        .await
+        .context("failed to read DNAs of the new version")?;
+    happ.verify_dna_hashes(&role_dnas)?;
+    let new_dnas: HashMap<String, DnaHash> = role_dnas
+        .iter()
+        .map(|(role, dna)| (role.clone(), dna.hash.clone()))
+        .collect();
+    let agent_key = match happ.agent_override_details(env).await? {
+        Some(admin) => admin.key,
+        None => agent.admin.key.clone(),
+    };
+
+    let strategy = UpgradeStrategy::choose(&new_dnas, &provisioned_cells(previous), &agent_key);
+    info!(
+        "upgrading {} to {} with strategy {:?}",
+        previous.installed_app_id,
+        happ.id(env),
+        strategy
+    );
+
+    let existing_cells = strategy.existing_cells();
+    admin_websocket
+        .install_and_activate_app(
+            happ,
+            bundle,
+            Some(membrane_proofs),
+            agent,
+            existing_cells.clone(),
+            env,
+        )
+        .await?;
+
+    // Reused cells still run the coordinators of the previous version
+    for (role, cell_id) in existing_cells {
+        let coordinators = role_dnas[&role].coordinators.clone();
+        admin_websocket
+            .update_coordinators(cell_id, coordinators)
+            .await
+            .context(format!("failed to update coordinators of role {}", role))?;
+    }
+
+    if !admin_websocket
+        .list_enabled_apps()
+        .await?
+        .contains(&happ.id(env))
+    {
+        return Err(anyhow!(
+            "{} is not enabled after upgrade, keeping {}",
+            happ.id(env),
+            previous.installed_app_id
+        ));
+    }
+
+    let (action, reason) = rules.removal(&previous.installed_app_id);
+    if action == CleanupAction::Uninstall {
+        info!(
+            "uninstalling previous version {}: {}",
+            previous.installed_app_id, reason
+        );
+        admin_websocket
+            .uninstall_app(&previous.installed_app_id, false)
+            .await?;
+    } else {
+        info!(
+            "disabling previous version {}: {}",
+            previous.installed_app_id, reason
+        );
+        admin_websocket
+            .disable_app(&previous.installed_app_id)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chooses_strategy_by_unchanged_dnas() {
+        let agent = AgentPubKey::from_raw_32(vec![1; 32]);
+        let other_agent = AgentPubKey::from_raw_32(vec![2; 32]);
+        let dna = |byte| DnaHash::from_raw_32(vec![byte; 32]);
+        let previous_cells: HashMap<String, CellId> = [
+            ("holofuel".to_string(), CellId::new(dna(10), agent.clone())),
+            ("hha".to_string(), CellId::new(dna(20), agent.clone())),
+        ]
+        .into();
+
+        let all_changed: HashMap<String, DnaHash> = [
+            ("holofuel".to_string(), dna(11)),
+            ("hha".to_string(), dna(21)),
+        ]
+        .into();
+        assert_eq!(
+            UpgradeStrategy::choose(&all_changed, &previous_cells, &agent),
+            UpgradeStrategy::Reinstall
+        );
+
+        let one_changed: HashMap<String, DnaHash> = [
+            ("holofuel".to_string(), dna(10)),
+            ("hha".to_string(), dna(21)),
+        ]
+        .into();
+        assert_eq!(
+            UpgradeStrategy::choose(&one_changed, &previous_cells, &agent),
+            UpgradeStrategy::ReuseCells(
+                [("holofuel".to_string(), CellId::new(dna(10), agent.clone()))].into()
+            )
+        );
+
+        let unchanged: HashMap<String, DnaHash> = [
+            ("holofuel".to_string(), dna(10)),
+            ("hha".to_string(), dna(20)),
+        ]
+        .into();
+        assert_eq!(
+            UpgradeStrategy::choose(&unchanged, &previous_cells, &agent),
+            UpgradeStrategy::UpdateCoordinators(previous_cells.clone())
+        );
+
+        // cells of another agent can not be taken over
+        assert_eq!(
+            UpgradeStrategy::choose(&unchanged, &previous_cells, &other_agent),
+            UpgradeStrategy::Reinstall
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! `install_happs` against an in-process mock conductor, with happ bundles built on the fly,
+//! so it runs without holochain, lair-keystore and network access.
+
+use anyhow::Result;
+use configure_holochain::{Config, Happ, HappsFile, HposEnvironment};
+use holochain_types::app::AppManifest;
+use holochain_types::prelude::{AgentPubKey, CellId, DnaHash, DnaManifest};
+use hpos_hc_connect::holo_config::{CleanupPolicy, Dna};
+use mock_conductor::{MockApp, MockConductor};
+use mock_services::membrane_proof::{MemProofServer, SignedMemProof};
+use mr_bundle::Bundle;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Role of a test happ. Wasms are hashed but never run, so any bytes do.
+struct TestRole {
+    name: &'static str,
+    integrity: &'static [u8],
+    coordinator: &'static [u8],
+}
+
+fn dna_bundle(role: &TestRole) -> Result<Vec<u8>> {
+    let manifest: DnaManifest = serde_yaml::from_str(&format!(
+        r#"
+manifest_version: "1"
+name: {}
+integrity:
+  origin_time: 1700000000000000
+  zomes:
+    - name: integrity
+      bundled: integrity.wasm
+coordinator:
+  zomes:
+    - name: main
+      bundled: coordinator.wasm
+      dependencies:
+        - name: integrity
+"#,
+        role.name
+    ))?;
+    let bundle = Bundle::new(
+        manifest,
+        vec![
+            ("integrity.wasm".into(), role.integrity.to_vec().into()),
+            ("coordinator.wasm".into(), role.coordinator.to_vec().into()),
+        ],
+        PathBuf::new(),
+    )?;
+    Ok(bundle.encode()?)
+}
+
+/// Writes a happ bundle named `file_name` into `dir`
+fn write_happ(dir: &Path, file_name: &str, roles: &[TestRole]) -> Result<PathBuf> {
+    let mut manifest = "manifest_version: \"1\"\nname: test\ndescription: ~\nroles:".to_string();
+    let mut resources = vec![];
+    for role in roles {
+        manifest.push_str(&format!(
+            r#"
+  - name: {0}
+    provisioning:
+      strategy: create
+      deferred: false
+    dna:
+      bundled: {0}.dna
+"#,
+            role.name
+        ));
+        resources.push((
+            format!("{}.dna", role.name).into(),
+            dna_bundle(role)?.into(),
+        ));
+    }
+    let manifest: AppManifest = serde_yaml::from_str(&manifest)?;
+    let bundle = Bundle::new(manifest, resources, PathBuf::new())?;
+
+    let path = dir.join(file_name);
+    std::fs::write(&path, bundle.encode()?)?;
+    Ok(path)
+}
+
+fn happ(bundle_path: PathBuf) -> Happ {
+    Happ {
+        ui_url: None,
+        ui_path: None,
+        ui_sha256: None,
+        bundle_url: None,
+        bundle_path: Some(bundle_path),
+        bundle_sha256: None,
+        dnas: None,
+        agent_bundle_override: None,
+    }
+}
+
+fn happs_file(happs: Vec<Happ>) -> HappsFile {
+    HappsFile {
+        self_hosted_happs: happs,
+        core_happs: vec![],
+        cleanup: Default::default(),
+    }
+}
+
+fn config(conductor: &MockConductor) -> Config {
+    Config {
+        admin_port: conductor.admin_port(),
+        happ_port: conductor.app_port(),
+        ui_store_folder: None,
+        happs_file_path: PathBuf::new(),
+        lair_url: None,
+    }
+}
+
+/// Host with a random agent key, saved to `tmp`, and a read-only membrane proof
+fn environment(tmp: &Path) -> HposEnvironment {
+    HposEnvironment {
+        hpos_config_path: Some("../holochain_env_setup/config/hp-primary-bzywj.json".into()),
+        host_pubkey_path: Some(tmp.join("agent.key")),
+        force_random_agent_key: true,
+        mem_proof_path: Some(tmp.join("mem-proof")),
+        read_only_mem_proof: true,
+        ..Default::default()
+    }
+}
+
+const CHAT: TestRole = TestRole {
+    name: "chat",
+    integrity: b"chat integrity",
+    coordinator: b"chat coordinator",
+};
+
+const PROFILES: TestRole = TestRole {
+    name: "profiles",
+    integrity: b"profiles integrity",
+    coordinator: b"profiles coordinator",
+};
+
+const HOLOFUEL: TestRole = TestRole {
+    name: "holofuel",
+    integrity: b"holofuel integrity",
+    coordinator: b"holofuel coordinator",
+};
+
+#[tokio::test]
+async fn installs_and_enables_happs() -> Result<()> {
+    let conductor = MockConductor::builder().spawn().await?;
+    let tmp = tempfile::tempdir()?;
+    let bundle_path = write_happ(tmp.path(), "chat.1.happ", &[CHAT, PROFILES])?;
+    let happs = happs_file(vec![happ(bundle_path)]);
+    let env = environment(tmp.path());
+
+    configure_holochain::install_happs(&happs, &config(&conductor), &env).await?;
+
+    let apps = conductor.installed_apps();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0].installed_app_id, "chat:1");
+    assert!(apps[0].enabled);
+    let mut role_names: Vec<&str> = apps[0].roles.iter().map(|r| r.name.as_str()).collect();
+    role_names.sort();
+    assert_eq!(role_names, vec!["chat", "profiles"]);
+
+    // Second run finds the app enabled and leaves it alone
+    configure_holochain::install_happs(&happs, &config(&conductor), &env).await?;
+    let apps_after = conductor.installed_apps();
+    assert_eq!(apps_after.len(), 1);
+    assert_eq!(
+        apps_after[0].cell_id("chat"),
+        apps[0].cell_id("chat"),
+        "app was reinstalled"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn verifies_expected_dna_hashes() -> Result<()> {
+    let conductor = MockConductor::builder().spawn().await?;
+    let tmp = tempfile::tempdir()?;
+    let env = environment(tmp.path());
+    let mut chat = happ(write_happ(tmp.path(), "chat.1.happ", &[CHAT, PROFILES])?);
+    let expected = |dna_hash: String| {
+        Some(vec![Dna {
+            role_name: "chat".to_string(),
+            properties: None,
+            dna_hash: Some(dna_hash),
+        }])
+    };
+
+    // hash of another DNA
+    let other = happ(write_happ(tmp.path(), "other.1.happ", &[PROFILES])?);
+    chat.dnas = expected(
+        Happ::role_dnas(&other.bundle(&env).await?, &env).await?["profiles"]
+            .hash
+            .to_string(),
+    );
+    let err = configure_holochain::install_happs(
+        &happs_file(vec![chat.clone()]),
+        &config(&conductor),
+        &env,
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("DNA hash mismatch for role chat"),
+        "{:#}",
+        err
+    );
+    assert!(conductor.installed_apps().is_empty());
+
+    chat.dnas = expected(
+        Happ::role_dnas(&chat.bundle(&env).await?, &env).await?["chat"]
+            .hash
+            .to_string(),
+    );
+    configure_holochain::install_happs(&happs_file(vec![chat]), &config(&conductor), &env).await?;
+    assert_eq!(conductor.enabled_apps(), vec!["chat:1"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn installs_with_mem_proof_from_server() -> Result<()> {
+    let conductor = MockConductor::builder().spawn().await?;
+    let server = MemProofServer::builder()
+        .spawn("127.0.0.1:0".parse()?)
+        .await?;
+    let tmp = tempfile::tempdir()?;
+    let env = HposEnvironment {
+        read_only_mem_proof: false,
+        mem_proof_server_url: Some(server.url()),
+        holofuel_instance_role: Some("host".to_string()),
+        ..environment(tmp.path())
+    };
+    let holofuel = happ(write_happ(tmp.path(), "holofuel.1.happ", &[HOLOFUEL])?);
+
+    configure_holochain::install_happs(&happs_file(vec![holofuel]), &config(&conductor), &env)
+        .await?;
+
+    let registrations = server.registrations();
+    assert_eq!(registrations.len(), 1);
+    assert_eq!(registrations[0].payload.role, "host");
+    let saved = std::fs::read_to_string(tmp.path().join("mem-proof"))?;
+    SignedMemProof::decode(&saved)?.verify(&server.verifying_key())?;
+
+    let app = installed(&conductor, "holofuel:1").expect("holofuel is installed");
+    assert!(app.enabled);
+    let mem_proof = app
+        .membrane_proofs
+        .get("holofuel")
+        .expect("installed with a mem proof for holofuel");
+    assert_eq!(mem_proof.bytes().to_vec(), base64::decode(&saved)?);
+    Ok(())
+}
+
+/// Saves the agent key the host installs with, so a test can install apps of the same agent
+fn host_agent_key(tmp: &Path) -> Result<AgentPubKey> {
+    let agent_key = AgentPubKey::from_raw_32(vec![7; 32]);
+    std::fs::write(tmp.join("agent.key"), agent_key.get_raw_39())?;
+    Ok(agent_key)
+}
+
+/// Installs `chat:2` over an enabled `chat:1` of the host agent. `previous_cell` gets the
+/// cell of `chat:1` for a role of `chat:2`, from the DNA hash `chat:2` would install.
+async fn upgrade_chat(
+    previous_cell: impl Fn(&str, DnaHash, AgentPubKey) -> CellId,
+    cleanup: CleanupPolicy,
+) -> Result<(MockConductor, MockApp)> {
+    let conductor = MockConductor::builder().spawn().await?;
+    let tmp = tempfile::tempdir()?;
+    let env = environment(tmp.path());
+    let agent_key = host_agent_key(tmp.path())?;
+    let new_version = happ(write_happ(tmp.path(), "chat.2.happ", &[CHAT, PROFILES])?);
+
+    let mut previous = MockApp::new("chat:1", agent_key.clone());
+    for (role, dna) in Happ::role_dnas(&new_version.bundle(&env).await?, &env).await? {
+        let cell_id = previous_cell(&role, dna.hash, agent_key.clone());
+        previous = previous.role_with_cell(role, cell_id);
+    }
+    conductor.install_app(previous.clone());
+
+    let happs = HappsFile {
+        cleanup,
+        ..happs_file(vec![new_version])
+    };
+    configure_holochain::install_happs(&happs, &config(&conductor), &env).await?;
+    Ok((conductor, previous))
+}
+
+fn installed(conductor: &MockConductor, app_id: &str) -> Option<MockApp> {
+    conductor
+        .installed_apps()
+        .into_iter()
+        .find(|app| app.installed_app_id == app_id)
+}
+
+#[tokio::test]
+async fn upgrade_keeps_cells_of_unchanged_dnas() -> Result<()> {
+    // profiles DNA changed
+    let (conductor, previous) = upgrade_chat(
+        |role, hash, agent_key| match role {
+            "chat" => CellId::new(hash, agent_key),
+            _ => CellId::new(DnaHash::from_raw_32(vec![1; 32]), agent_key),
+        },
+        Default::default(),
+    )
+    .await?;
+
+    let chat_cell = previous.cell_id("chat").unwrap();
+    assert_eq!(conductor.coordinator_updates(), vec![chat_cell.clone()]);
+    let upgraded = installed(&conductor, "chat:2").expect("new version is installed");
+    assert!(upgraded.enabled);
+    assert_eq!(upgraded.cell_id("chat"), Some(chat_cell));
+    assert_ne!(upgraded.cell_id("profiles"), previous.cell_id("profiles"));
+    // the chat cell now belongs to the new version, so the previous one can go
+    assert!(installed(&conductor, "chat:1").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrade_updates_coordinators_when_no_dna_changed() -> Result<()> {
+    let (conductor, previous) = upgrade_chat(
+        |_, hash, agent_key| CellId::new(hash, agent_key),
+        Default::default(),
+    )
+    .await?;
+
+    let updated: HashSet<CellId> = conductor.coordinator_updates().into_iter().collect();
+    let cells: HashSet<CellId> = [
+        previous.cell_id("chat").unwrap(),
+        previous.cell_id("profiles").unwrap(),
+    ]
+    .into();
+    assert_eq!(updated, cells);
+    let upgraded = installed(&conductor, "chat:2").expect("new version is installed");
+    assert!(upgraded.enabled);
+    assert_eq!(upgraded.cell_id("profiles"), previous.cell_id("profiles"));
+    assert!(installed(&conductor, "chat:1").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrade_uninstalls_previous_version_when_all_dnas_changed() -> Result<()> {
+    let (conductor, _) = upgrade_chat(
+        |role, _, agent_key| {
+            let byte = if role == "chat" { 1 } else { 2 };
+            CellId::new(DnaHash::from_raw_32(vec![byte; 32]), agent_key)
+        },
+        Default::default(),
+    )
+    .await?;
+
+    assert!(conductor.coordinator_updates().is_empty());
+    assert!(installed(&conductor, "chat:2").unwrap().enabled);
+    assert!(installed(&conductor, "chat:1").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrade_disables_protected_previous_version() -> Result<()> {
+    let cleanup = CleanupPolicy {
+        protected: vec!["chat:*".to_string()],
+        ..Default::default()
+    };
+    let (conductor, previous) =
+        upgrade_chat(|_, hash, agent_key| CellId::new(hash, agent_key), cleanup).await?;
+
+    let upgraded = installed(&conductor, "chat:2").expect("new version is installed");
+    assert!(upgraded.enabled);
+    assert_eq!(upgraded.cell_id("chat"), previous.cell_id("chat"));
+    let previous = installed(&conductor, "chat:1").expect("protected version is kept");
+    assert!(!previous.enabled);
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use anyhow::Context;
 use configure_holochain;
-use hpos_config_core::Config;
-use hpos_hc_connect::hpos_agent::get_hpos_config;
+use hpos_hc_connect::hpos_config::HposConfig;
 use hpos_hc_connect::hpos_membrane_proof::delete_mem_proof_file;
+use hpos_hc_connect::HposEnvironment;
 use serial_test::serial;
 use std::env::set_var;
 use std::path::PathBuf;
@@ -94,15 +94,20 @@ async fn run_configure_holochain(f_r_a_k: &str, r_o_m_p: &str) {
     set_var("DEVICE_SEED_DEFAULT_PASSWORD", "pass");
     set_var("HOLOCHAIN_DEFAULT_PASSWORD", "pass");
 
-    let device_bundle = match get_hpos_config().unwrap() {
-        Config::V3 { device_bundle, .. } | Config::V2 { device_bundle, .. } => device_bundle,
-        _ => panic!("Unsupported Config version"),
+    // lair is still spawned with the passwords from env vars, there is no HBS to update
+    // the jurisdiction from
+    let env = HposEnvironment {
+        skip_jurisdiction_update: true,
+        ..HposEnvironment::from_env()
     };
 
+    let hpos_config = HposConfig::from_env(&env).unwrap();
+    let device_bundle = hpos_config.device_bundle().unwrap();
+
     // spin up lair
     println!("\nStarting lair-keystore");
     let (_lair, lair_config, _) =
-        holochain_env_setup::lair::spawn(&tmp_dir, &log_dir, Some(&device_bundle), None)
+        holochain_env_setup::lair::spawn(&tmp_dir, &log_dir, Some(device_bundle), None)
             .await
             .unwrap();
 
@@ -122,24 +127,22 @@ async fn run_configure_holochain(f_r_a_k: &str, r_o_m_p: &str) {
     };
     println!("Test running with config: {:?}", &config);
 
-    set_var("IS_INTEGRATION_TEST", "TRUE");
-
     println!("Run configure holochain script");
-    configure_holochain::run(config.clone())
+    configure_holochain::run(config.clone(), &env)
         .await
         .expect("Failed when running configure holochain script the first time");
 
     // Second run should not error out
-    configure_holochain::run(config.clone())
+    configure_holochain::run(config.clone(), &env)
         .await
         .expect("Failed when running configure holochain script the second time");
 
     // Delete memproof which is an equivalent of changing DEV_UID_OVERRIDE for holoport
     // which was creating a bug https://github.com/Holo-Host/hpos-configure-holochain/issues/136
-    delete_mem_proof_file().unwrap();
+    delete_mem_proof_file(&env).unwrap();
 
     // Third run should not error out
-    configure_holochain::run(config)
+    configure_holochain::run(config, &env)
         .await
         .expect("Failed when running configure holochain script the third time");
 
@@ -159,10 +162,18 @@ async fn run_configure_holochain(f_r_a_k: &str, r_o_m_p: &str) {
 
     // checking if all the happs are installed
     happ_file.self_hosted_happs.iter().for_each(|h| {
-        assert!(happs.contains(&h.id()), "{} is not installed", h.id());
+        assert!(
+            happs.contains(&h.id(&env)),
+            "{} is not installed",
+            h.id(&env)
+        );
     });
     happ_file.core_happs.iter().for_each(|h| {
-        assert!(happs.contains(&h.id()), "{} is not installed", h.id());
+        assert!(
+            happs.contains(&h.id(&env)),
+            "{} is not installed",
+            h.id(&env)
+        );
     });
     println!("Successfully tested! {:?}", happs);
 }
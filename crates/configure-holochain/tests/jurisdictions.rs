@@ -0,0 +1,100 @@
+//! `update_jurisdiction_if_changed` against the mock HBS and an in-process mock conductor,
+//! so it runs without holochain, lair-keystore and Holo backend services.
+
+use anyhow::{anyhow, Result};
+use configure_holochain::jurisdictions::update_jurisdiction_if_changed;
+use ed25519_dalek::SigningKey;
+use holochain_types::prelude::AgentPubKey;
+use hpos_hc_connect::{
+    hbs::HbsClient,
+    hha_agent::CoreAppAgent,
+    hha_types::SetHostJurisdictionInput,
+    host_keys::{HostKeys, InMemorySigner},
+    AdminWebsocket, AppConnection,
+};
+use mock_conductor::{MockApp, MockConductor};
+use mock_services::hbs::{registration_record, HbsServer};
+use std::sync::Arc;
+
+const CORE_APP_ID: &str = "core-app:0_6_2";
+
+/// Core app of a host whose jurisdiction in HHA is `hha_jurisdiction`
+async fn core_app_agent(
+    hha_jurisdiction: &'static str,
+) -> Result<(MockConductor, CoreAppAgent, AgentPubKey)> {
+    let keystore = holochain_keystore::test_keystore();
+    let agent_key = keystore.new_sign_keypair_random().await?;
+
+    let conductor = MockConductor::builder()
+        .app(MockApp::new(CORE_APP_ID, agent_key.clone()).role("core-app"))
+        .zome_fn("hha", "get_host_jurisdiction", move |_: AgentPubKey| {
+            Ok(Some(hha_jurisdiction.to_string()))
+        })
+        .zome_fn(
+            "hha",
+            "set_host_jurisdiction",
+            |_: SetHostJurisdictionInput| Ok(()),
+        )
+        .spawn()
+        .await?;
+
+    let mut admin_ws = AdminWebsocket::connect(conductor.admin_port()).await?;
+    let app = AppConnection::connect(&mut admin_ws, keystore, CORE_APP_ID.to_string()).await?;
+    Ok((conductor, CoreAppAgent { app }, agent_key))
+}
+
+fn host_keys() -> HostKeys {
+    let signer = InMemorySigner::new(SigningKey::from_bytes(&[1; 32]));
+    HostKeys::from_signer("host@holo.host".to_string(), Arc::new(signer))
+}
+
+/// Inputs of all `set_host_jurisdiction` calls the conductor received
+fn set_jurisdiction_calls(conductor: &MockConductor) -> Result<Vec<SetHostJurisdictionInput>> {
+    conductor
+        .zome_calls()
+        .into_iter()
+        .filter(|call| call.fn_name == "set_host_jurisdiction".into())
+        .map(|call| {
+            call.payload
+                .decode()
+                .map_err(|e| anyhow!("failed to decode payload: {:?}", e))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn jurisdiction_changed_in_hbs_is_set_in_hha() -> Result<()> {
+    let (conductor, mut agent, agent_key) = core_app_agent("Gibraltar").await?;
+    let keys = host_keys();
+    let server = HbsServer::builder()
+        .registration(registration_record(&keys.pubkey_base36, "Germany"))
+        .spawn("127.0.0.1:0".parse()?)
+        .await?;
+    let hbs = HbsClient::new(server.url(), keys.clone());
+
+    update_jurisdiction_if_changed(&hbs, &mut agent).await?;
+
+    let calls = set_jurisdiction_calls(&conductor)?;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].jurisdiction, "Germany");
+    assert_eq!(calls[0].pubkey, agent_key);
+
+    // same jurisdiction in HBS and HHA
+    server.set_jurisdiction(&keys.pubkey_base36, "Gibraltar");
+    update_jurisdiction_if_changed(&hbs, &mut agent).await?;
+    assert_eq!(set_jurisdiction_calls(&conductor)?.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unregistered_host_keeps_hha_jurisdiction() -> Result<()> {
+    let (conductor, mut agent, _) = core_app_agent("Gibraltar").await?;
+    let server = HbsServer::builder().spawn("127.0.0.1:0".parse()?).await?;
+    let hbs = HbsClient::new(server.url(), host_keys());
+
+    assert!(update_jurisdiction_if_changed(&hbs, &mut agent)
+        .await
+        .is_err());
+    assert!(conductor.zome_calls().is_empty());
+    Ok(())
+}
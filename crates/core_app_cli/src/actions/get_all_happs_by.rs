@@ -1,27 +1,21 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent, hha_types::PresentedHappBundle,
-};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hha_agent::CoreAppAgent, hha_types::PresentedHappBundle, HposEnvironment};
 
-pub async fn get(publisher_pubkey: String) -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get(publisher_pubkey: String, output: OutputFormat) -> Result<()> {
+    let mut agent = CoreAppAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let happs: Vec<PresentedHappBundle> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            ZomeName::from("hha"),
-            FunctionName::from("get_happs"),
-            (),
-        )
-        .await?;
+    let happs = agent.get_happs().await?;
 
     let publisher_happs: Vec<&PresentedHappBundle> = happs
         .iter()
         .filter(|h| h.provider_pubkey.to_string() == publisher_pubkey)
         .collect();
 
+    if output.print(&publisher_happs)? {
+        return Ok(());
+    }
+
     println!("===================");
     println!("All Published Happs by {} are: ", publisher_pubkey);
     println!("{:?}", publisher_happs);
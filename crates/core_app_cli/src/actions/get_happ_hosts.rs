@@ -1,22 +1,19 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent, hha_types::HoloportDetails,
-};
+use holochain_types::prelude::ActionHashB64;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hha_agent::CoreAppAgent, HposEnvironment};
 
-pub async fn get(happ_id: String) -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get(happ_id: String, output: OutputFormat) -> Result<()> {
+    let mut agent = CoreAppAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let hosts: Vec<HoloportDetails> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            ZomeName::from("hha"),
-            FunctionName::from("get_hosts"),
-            happ_id.clone(),
-        )
+    let hosts = agent
+        .get_hosts(ActionHashB64::from_b64_str(&happ_id)?)
         .await?;
 
+    if output.print(&hosts)? {
+        return Ok(());
+    }
+
     println!("===================");
     println!("All Hosts for Happ ID {} are: ", happ_id);
     println!("{:#?}", hosts);
@@ -1,33 +1,32 @@
 // NB: This endpoint is used by the nightly tests.  Any change to it's input or output should also be updated there.
+// Scripts should prefer `--output json`, which prints just the preference hash (or null).
 
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent, hha_types::HoloportDetails,
-};
+use holochain_types::prelude::ActionHashB64;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hha_agent::CoreAppAgent, HposEnvironment};
 
-pub async fn get(happ_id: String, host_id: String) -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get(happ_id: String, host_id: String, output: OutputFormat) -> Result<()> {
+    let mut agent = CoreAppAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let hosts: Vec<HoloportDetails> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            ZomeName::from("hha"),
-            FunctionName::from("get_hosts"),
-            happ_id.clone(),
-        )
+    let hosts = agent
+        .get_hosts(ActionHashB64::from_b64_str(&happ_id)?)
         .await?;
 
-    let found = hosts.into_iter().find(|h| h.holoport_id.0 == host_id);
+    let preferences_hash = hosts
+        .into_iter()
+        .find(|h| h.holoport_id.0 == host_id)
+        .and_then(|h| h.preferences_hash);
 
-    if let Some(d) = found {
-        if let Some(p) = d.preferences_hash {
-            // Please do not change this print and if you do see that the nightly tests that depend on this print are updated as well
-            println!("===================");
-            println!("Happ Preference Hash: {:#?}", p);
-            println!("===================");
-        }
+    if output.print(&preferences_hash)? {
+        return Ok(());
+    }
+
+    if let Some(p) = preferences_hash {
+        // Please do not change this print and if you do see that the nightly tests that depend on this print are updated as well
+        println!("===================");
+        println!("Happ Preference Hash: {:#?}", p);
+        println!("===================");
     }
 
     Ok(())
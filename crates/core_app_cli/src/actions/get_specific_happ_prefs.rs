@@ -1,24 +1,19 @@
 use anyhow::Result;
-use holochain_types::prelude::{ActionHash, ActionHashB64, FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent, hha_types::HappPreferences,
-};
+use holochain_types::prelude::{ActionHash, ActionHashB64};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hha_agent::CoreAppAgent, HposEnvironment};
 
-pub async fn get(pref_hash: String) -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get(pref_hash: String, output: OutputFormat) -> Result<()> {
+    let mut agent = CoreAppAgent::spawn(None, &HposEnvironment::from_env()).await?;
     let pref_holo_hash = ActionHashB64::from_b64_str(&pref_hash)
         .expect("Failed to serialize string into ActionHashB4");
     let hash = ActionHash::from(pref_holo_hash);
 
-    let prefs: HappPreferences = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            ZomeName::from("hha"),
-            FunctionName::from("get_specific_happ_preferences"),
-            hash,
-        )
-        .await?;
+    let prefs = agent.get_specific_happ_preferences(hash).await?;
+
+    if output.print(&prefs)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!(
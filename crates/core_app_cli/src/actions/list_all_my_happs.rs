@@ -1,21 +1,15 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hha_agent::CoreAppAgent, hha_types::PresentedHappBundle,
-};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hha_agent::CoreAppAgent, HposEnvironment};
 
-pub async fn get() -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = CoreAppAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let happs: Vec<PresentedHappBundle> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            ZomeName::from("hha"),
-            FunctionName::from("get_my_happs"),
-            (),
-        )
-        .await?;
+    let happs = agent.get_my_happs().await?;
+
+    if output.print(&happs)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("Your Published Happs are: ");
@@ -1,23 +1,33 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
+use hpos_hc_connect::output::OutputFormat;
 use hpos_hc_connect::{
-    app_connection::CoreAppRoleName,
-    hha_agent::CoreAppAgent,
+    hf_agent::HfAgent,
     holofuel_types::{Actionable, Pending, Transaction},
+    HposEnvironment,
 };
+use serde::Serialize;
 
-pub async fn get() -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+/// Structured output of all three transaction lists
+#[derive(Serialize)]
+struct AllTransactions {
+    pending: Pending,
+    actionable: Actionable,
+    completed: Vec<Transaction>,
+}
+
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+
+    if output.is_structured() {
+        output.print(&AllTransactions {
+            pending: agent.pending().await?,
+            actionable: agent.actionable().await?,
+            completed: agent.completed().await?,
+        })?;
+        return Ok(());
+    }
 
-    let txs: Pending = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_pending_transactions"),
-            (),
-        )
-        .await?;
+    let txs: Pending = agent.pending().await?;
 
     println!("===================");
     println!("Your Pending List is: ");
@@ -39,15 +49,7 @@ pub async fn get() -> Result<()> {
         txs.accepted.len()
     );
     println!("===================");
-    let txs: Actionable = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_actionable_transactions"),
-            (),
-        )
-        .await?;
+    let txs: Actionable = agent.actionable().await?;
 
     println!("===================");
     println!("Your Actionable List is: ");
@@ -58,15 +60,7 @@ pub async fn get() -> Result<()> {
         txs.invoice_actionable.len() + txs.promise_actionable.len()
     );
     println!("===================");
-    let txs: Vec<Transaction> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_completed_transactions"),
-            (),
-        )
-        .await?;
+    let txs: Vec<Transaction> = agent.completed().await?;
 
     println!("===================");
     println!("Your Completed List is: ");
@@ -10,3 +10,4 @@ pub mod pay_invoices;
 pub mod profile;
 pub mod set_happ_prefs;
 pub mod summary;
+pub mod transaction;
@@ -1,69 +1,11 @@
 use anyhow::Result;
-use holochain_types::prelude::Timestamp;
-use holochain_types::prelude::{
-    holochain_serial, EntryHashB64, FunctionName, SerializedBytes, ZomeName,
-};
-use hpos_hc_connect::app_connection::CoreAppRoleName;
-use hpos_hc_connect::hha_agent::CoreAppAgent;
-use hpos_hc_connect::holofuel_types::{CounterSigningResponse, Pending};
-use serde::{Deserialize, Serialize};
-
-pub async fn get() -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
-
-    let txs: Pending = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_pending_transactions"),
-            (),
-        )
-        .await?;
-
-    #[derive(Serialize, Deserialize, Debug, SerializedBytes)]
-    struct AcceptTx {
-        address: EntryHashB64,
-        expiration_date: Option<Timestamp>,
-    }
-    if !txs.invoice_pending.is_empty() {
-        println!("===================");
-        println!("Going to accept first transaction");
-        println!("Invoices Pending: {:?}", txs.invoice_pending[0]);
-        println!("===================");
-
-        let hash: EntryHashB64 = agent
-            .app
-            .zome_call_typed(
-                CoreAppRoleName::Holofuel.into(),
-                ZomeName::from("transactor"),
-                FunctionName::from("accept_transaction"),
-                AcceptTx {
-                    address: txs.invoice_pending[0].id.clone(),
-                    expiration_date: None,
-                },
-            )
-            .await?;
-
-        println!("Accepted tx: {:?}", hash);
-        println!("Trying to complete, if this fails it will be completed by your schedular");
-
-        let countersigning_response: CounterSigningResponse = agent
-            .app
-            .zome_call_typed(
-                CoreAppRoleName::Holofuel.into(),
-                ZomeName::from("transactor"),
-                FunctionName::from("complete_transactions"),
-                hash,
-            )
-            .await?;
-
-        println!("CounterSigningResponse {:?}", countersigning_response);
-    } else {
-        println!("===================");
-        println!("No pending invoices");
-        println!("===================");
-    }
-
-    Ok(())
+use hpos_hc_connect::hf_agent::HfAgent;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::pay::{pay_invoices, PayOpt};
+use hpos_hc_connect::HposEnvironment;
+
+/// Accepts and completes all pending invoices matching given filters
+pub async fn get(opt: PayOpt, output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+    pay_invoices(&mut agent, opt, output).await
 }
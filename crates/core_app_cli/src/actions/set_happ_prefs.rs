@@ -1,10 +1,9 @@
 use anyhow::Result;
-use holochain_types::prelude::{ActionHashB64, FunctionName, ZomeName};
+use holochain_types::prelude::ActionHashB64;
 use holofuel_types::fuel::Fuel;
+use hpos_hc_connect::output::OutputFormat;
 use hpos_hc_connect::{
-    app_connection::CoreAppRoleName,
-    hha_agent::CoreAppAgent,
-    hha_types::{HappPreferences, SetHappPreferencesInput},
+    hha_agent::CoreAppAgent, hha_types::SetHappPreferencesInput, HposEnvironment,
 };
 use std::{str::FromStr, time::Duration};
 
@@ -16,8 +15,9 @@ pub async fn get(
     max_fuel_before_invoice: String,
     max_time_before_invoice_sec: String,
     max_time_before_invoice_ms: String,
+    output: OutputFormat,
 ) -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+    let mut agent = CoreAppAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
     let max_time_sec = max_time_before_invoice_sec
         .parse::<u64>()
@@ -36,15 +36,11 @@ pub async fn get(
         max_time_before_invoice: Duration::new(max_time_sec, max_time_ms),
     };
 
-    let happ_prefs: HappPreferences = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::HHA.into(),
-            ZomeName::from("hha"),
-            FunctionName::from("set_happ_preferences"),
-            host_pricing_prefs,
-        )
-        .await?;
+    let happ_prefs = agent.set_happ_preferences(host_pricing_prefs).await?;
+
+    if output.print(&happ_prefs)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("Your Published Happ Preferences are: ");
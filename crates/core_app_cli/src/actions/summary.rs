@@ -1,40 +1,29 @@
 use anyhow::Result;
 use holochain_types::dna::AgentPubKey;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::app_connection::CoreAppRoleName;
-use hpos_hc_connect::hha_agent::CoreAppAgent;
+use hpos_hc_connect::hf_agent::HfAgent;
 use hpos_hc_connect::holofuel_types::MigrationCloseStateV1Handler;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::HposEnvironment;
 
-pub async fn get_my_summary() -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get_my_summary(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let summary: MigrationCloseStateV1Handler = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_my_summary"),
-            (),
-        )
-        .await?;
+    let summary: MigrationCloseStateV1Handler = agent.my_summary().await?;
 
-    display(summary);
+    if !output.print(&summary)? {
+        display(summary);
+    }
     Ok(())
 }
 
-pub async fn get_agent_summary(pub_key: AgentPubKey) -> Result<()> {
-    let mut agent = CoreAppAgent::spawn(None).await?;
+pub async fn get_agent_summary(pub_key: AgentPubKey, output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let summary: MigrationCloseStateV1Handler = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_agent_summary"),
-            pub_key,
-        )
-        .await?;
-    display(summary);
+    let summary: MigrationCloseStateV1Handler = agent.agent_summary(pub_key).await?;
+
+    if !output.print(&summary)? {
+        display(summary);
+    }
     Ok(())
 }
 
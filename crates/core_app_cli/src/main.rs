@@ -1,9 +1,20 @@
 use anyhow::Result;
 use holochain_types::dna::AgentPubKeyB64;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::pay::PayOpt;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "core-app-cli", about = "An example of StructOpt usage.")]
+struct Cli {
+    /// Output format: table, json or yaml
+    #[structopt(long, short, global = true, default_value = "table")]
+    output: OutputFormat,
+    #[structopt(subcommand)]
+    cmd: Opt,
+}
+
+#[derive(Debug, StructOpt)]
 pub enum Opt {
     /// Gets profile details
     #[structopt(name = "pr")]
@@ -14,9 +25,21 @@ pub enum Opt {
     /// Gets the list of all your transactions
     #[structopt(name = "tx")]
     Transactions,
-    /// Pay your first pending invoice
+    /// Pays all pending invoices matching given filters
     #[structopt(name = "pay")]
-    PayInvoice,
+    PayInvoice(PayOpt),
+    /// Accepts a pending transaction and tries to complete it
+    #[structopt(name = "accept")]
+    Accept { tx_id: String },
+    /// Declines a pending transaction
+    #[structopt(name = "decline")]
+    Decline { tx_id: String },
+    /// Cancels a transaction created by you
+    #[structopt(name = "cancel")]
+    Cancel { tx_id: String },
+    /// Retries completing an accepted transaction
+    #[structopt(name = "complete")]
+    Complete { tx_id: String },
     /// List all happs published by me
     #[structopt(name = "my-happs")]
     Happs,
@@ -61,25 +84,29 @@ pub enum Opt {
 }
 impl Opt {
     /// Run this command
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(self, output: OutputFormat) -> Result<()> {
         match self {
-            Opt::Profile => core_app_cli::profile::get().await?,
-            Opt::Ledger => core_app_cli::ledger::get().await?,
-            Opt::Transactions => core_app_cli::list_all_tx::get().await?,
-            Opt::PayInvoice => core_app_cli::pay_invoices::get().await?,
-            Opt::Happs => core_app_cli::list_all_my_happs::get().await?,
-            Opt::Hosts { happ_id } => core_app_cli::get_happ_hosts::get(happ_id).await?,
+            Opt::Profile => core_app_cli::profile::get(output).await?,
+            Opt::Ledger => core_app_cli::ledger::get(output).await?,
+            Opt::Transactions => core_app_cli::list_all_tx::get(output).await?,
+            Opt::PayInvoice(opt) => core_app_cli::pay_invoices::get(opt, output).await?,
+            Opt::Accept { tx_id } => core_app_cli::transaction::accept(tx_id, output).await?,
+            Opt::Decline { tx_id } => core_app_cli::transaction::decline(tx_id, output).await?,
+            Opt::Cancel { tx_id } => core_app_cli::transaction::cancel(tx_id, output).await?,
+            Opt::Complete { tx_id } => core_app_cli::transaction::complete(tx_id, output).await?,
+            Opt::Happs => core_app_cli::list_all_my_happs::get(output).await?,
+            Opt::Hosts { happ_id } => core_app_cli::get_happ_hosts::get(happ_id, output).await?,
             Opt::GetPreferenceByHash { pref_hash } => {
-                core_app_cli::get_specific_happ_prefs::get(pref_hash).await?
+                core_app_cli::get_specific_happ_prefs::get(pref_hash, output).await?
             }
             Opt::GetHappsForPublisher { publisher_pubkey } => {
-                core_app_cli::get_all_happs_by::get(publisher_pubkey).await?
+                core_app_cli::get_all_happs_by::get(publisher_pubkey, output).await?
             }
             Opt::EnableHappForHost { happ_id, host_id } => {
-                core_app_cli::enable_happ_for_host::get(happ_id, host_id).await?
+                core_app_cli::enable_happ_for_host::get(happ_id, host_id, output).await?
             }
             Opt::GetHappPrefHashForHost { happ_id, host_id } => {
-                core_app_cli::get_happ_pref_for_host::get(happ_id, host_id).await?
+                core_app_cli::get_happ_pref_for_host::get(happ_id, host_id, output).await?
             }
             Opt::SetHappPreferences {
                 happ_id,
@@ -98,14 +125,15 @@ impl Opt {
                     max_fuel_before_invoice,
                     max_time_before_invoice_sec,
                     max_time_before_invoice_ms,
+                    output,
                 )
                 .await?
             }
-            Opt::GetMySummary => core_app_cli::summary::get_my_summary().await?,
+            Opt::GetMySummary => core_app_cli::summary::get_my_summary(output).await?,
             Opt::GetAgentSummary { pub_key } => {
                 let pub_key = AgentPubKeyB64::from_b64_str(&pub_key)
                     .expect("Failed to serialize string into AgentPubKey");
-                core_app_cli::summary::get_agent_summary(pub_key.into()).await?
+                core_app_cli::summary::get_agent_summary(pub_key.into(), output).await?
             }
         }
         Ok(())
@@ -114,6 +142,6 @@ impl Opt {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let opt = Opt::from_args();
-    opt.run().await
+    let cli = Cli::from_args();
+    cli.cmd.run(cli.output).await
 }
@@ -5,13 +5,14 @@ use hpos_hc_connect::hha_agent::CoreAppAgent;
 pub use hpos_hc_connect::{
     hha_types::HappInput,
     holo_config::{Config, Happ, HappsFile},
+    HposEnvironment,
 };
 use tracing::{debug, info};
 
-pub async fn run(config: &Config) -> Result<()> {
+pub async fn run(config: &Config, env: &HposEnvironment) -> Result<()> {
     info!("Running happ manager");
 
-    let mut hha = CoreAppAgent::spawn(Some(config)).await?;
+    let mut hha = CoreAppAgent::spawn(Some(config), env).await?;
 
     let apps = happ_to_be_published()?;
 
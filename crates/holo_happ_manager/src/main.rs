@@ -1,5 +1,5 @@
 use anyhow::Result;
-use holo_happ_manager::{self, Config};
+use holo_happ_manager::{self, Config, HposEnvironment};
 use tracing::instrument;
 use tracing_subscriber::EnvFilter;
 
@@ -13,5 +13,5 @@ async fn main() -> Result<()> {
 #[instrument(err)]
 async fn spawn() -> Result<()> {
     let config = Config::load();
-    holo_happ_manager::run(&config).await
+    holo_happ_manager::run(&config, &HposEnvironment::from_env()).await
 }
@@ -4,8 +4,9 @@ use hpos_hc_connect::hha_agent::CoreAppAgent;
 #[tokio::test]
 async fn run_happ_manager() {
     use configure_holochain;
-    use hpos_config_core::Config;
-    use hpos_hc_connect::hpos_agent::get_hpos_config;
+    use holochain_env_setup::environment::Environment;
+    use hpos_hc_connect::hpos_config::HposConfig;
+    use hpos_hc_connect::HposEnvironment;
     use std::env::set_var;
     use std::path::PathBuf;
 
@@ -50,40 +51,42 @@ async fn run_happ_manager() {
 
     set_var("IS_INTEGRATION_TEST", "TRUE");
 
-    let device_bundle = match get_hpos_config().unwrap() {
-        Config::V3 { device_bundle, .. } | Config::V2 { device_bundle, .. } => device_bundle,
-        _ => panic!("Unsupported Config version"),
-    };
+    // lair is still spawned with the passwords from env vars
+    let env = HposEnvironment::from_env();
 
-    // spin up lair
-    println!("Starting lair-keystore");
-    let (_lair, lair_config, _) =
-        holochain_env_setup::lair::spawn(&tmp_dir, &log_dir, Some(&device_bundle), None)
-            .await
-            .unwrap();
+    let hpos_config = HposConfig::from_env(&env).unwrap();
+    let device_bundle = hpos_config.device_bundle().unwrap();
 
-    println!("Spinning up holochain");
-    let _holochain =
-        holochain_env_setup::holochain::spawn_holochain(&tmp_dir, &log_dir, lair_config.clone());
+    // spin up lair and holochain on a free admin port
+    println!("Starting lair-keystore and holochain");
+    let environment = Environment::builder()
+        .tmp_dir(&tmp_dir)
+        .log_dir(&log_dir)
+        .device_bundle(device_bundle)
+        .build()
+        .await
+        .unwrap();
 
     let happs_file_path: PathBuf = "./tests/config.yaml".into();
     let config = hpos_hc_connect::holo_config::Config {
-        admin_port: 4444,
+        admin_port: environment.admin_port,
         happ_port: 42233,
         ui_store_folder: None,
         happs_file_path: happs_file_path.clone(),
-        lair_url: Some(lair_config.connection_url.to_string()),
+        lair_url: Some(environment.lair_config.connection_url.to_string()),
     };
     println!("Test running with config: {:?}", &config);
     println!("Run configure holochain script to install HHA");
-    configure_holochain::run(config.clone()).await.unwrap();
+    configure_holochain::run(config.clone(), &env)
+        .await
+        .unwrap();
 
     set_var("HOLO_PUBLISHED_HAPPS", "./tests/holo-published-happs.json");
 
     println!("Run holo happ manager script");
-    holo_happ_manager::run(&config).await.unwrap();
+    holo_happ_manager::run(&config, &env).await.unwrap();
 
-    let mut hha = CoreAppAgent::spawn(Some(&config)).await.unwrap();
+    let mut hha = CoreAppAgent::spawn(Some(&config), &env).await.unwrap();
 
     let published_happ = hha.get_my_happs().await.unwrap();
 
@@ -1,7 +1,11 @@
-use crate::holochain::spawn_holochain;
+use crate::holochain::{
+    create_log_dir, create_tmp_dir, spawn_holochain_with_options, ConductorOptions, DpkiConfig,
+    NetworkConfig, DEFAULT_ADMIN_PORT,
+};
 
 use super::lair;
 use holochain_keystore::MetaLairClient;
+use hpos_hc_connect::AdminWebsocket;
 use lair_keystore_api::prelude::LairServerConfigInner as LairConfig;
 use log::trace;
 use snafu::Snafu;
@@ -14,20 +18,17 @@ pub async fn setup_environment(
     device_bundle: Option<&str>,
     lair_fallback: Option<(PathBuf, u16)>,
 ) -> Result<Environment, SetupEnvironmentError> {
-    trace!("Starting lair-keystore");
-    let (lair, lair_config, keystore) = lair::spawn(tmp_dir, log_dir, device_bundle, lair_fallback)
-        .await
-        .unwrap();
-
-    trace!("Spinning up holochain");
-    let holochain = spawn_holochain(tmp_dir, log_dir, lair_config.clone());
-
-    Ok(Environment {
-        _holochain: holochain,
-        _lair: lair,
-        lair_config,
-        keystore,
-    })
+    let mut builder = Environment::builder()
+        .tmp_dir(tmp_dir)
+        .log_dir(log_dir)
+        .admin_port(DEFAULT_ADMIN_PORT);
+    if let Some(bundle) = device_bundle {
+        builder = builder.device_bundle(bundle);
+    }
+    if let Some((executable_path, signing_port)) = lair_fallback {
+        builder = builder.lair_fallback(executable_path, signing_port);
+    }
+    builder.build().await
 }
 
 #[derive(Debug, Snafu)]
@@ -52,4 +53,137 @@ pub struct Environment {
     _lair: KillChildOnDrop,
     pub lair_config: LairConfig,
     pub keystore: MetaLairClient,
+    /// Port of holochain's admin interface, pass it as `admin_port` of `holo_config::Config`
+    pub admin_port: u16,
+    /// Ports of app interfaces attached by `EnvironmentBuilder::app_interface`, in order
+    pub app_ports: Vec<u16>,
+    pub tmp_dir: PathBuf,
+    pub log_dir: PathBuf,
+}
+
+impl Environment {
+    pub fn builder() -> EnvironmentBuilder {
+        EnvironmentBuilder::default()
+    }
+}
+
+/// Sets up lair and holochain for tests. Unless configured otherwise each environment
+/// gets its own tmp and log dirs and an admin port picked by holochain, so several can run at once.
+#[derive(Default)]
+pub struct EnvironmentBuilder {
+    tmp_dir: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    device_bundle: Option<String>,
+    lair_fallback: Option<(PathBuf, u16)>,
+    admin_port: Option<u16>,
+    conductor: ConductorOptions,
+    app_interfaces: Vec<Option<u16>>,
+}
+
+impl EnvironmentBuilder {
+    pub fn tmp_dir(mut self, tmp_dir: impl Into<PathBuf>) -> Self {
+        self.tmp_dir = Some(tmp_dir.into());
+        self
+    }
+
+    pub fn log_dir(mut self, log_dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = Some(log_dir.into());
+        self
+    }
+
+    /// Imports the seed of `device_bundle` into lair under the `host` tag
+    pub fn device_bundle(mut self, device_bundle: impl Into<String>) -> Self {
+        self.device_bundle = Some(device_bundle.into());
+        self
+    }
+
+    pub fn lair_fallback(mut self, executable_path: PathBuf, signing_port: u16) -> Self {
+        self.lair_fallback = Some((executable_path, signing_port));
+        self
+    }
+
+    /// Fixed admin port instead of one picked by holochain
+    pub fn admin_port(mut self, admin_port: u16) -> Self {
+        self.admin_port = Some(admin_port);
+        self
+    }
+
+    /// Origins allowed to connect to the admin interface, `*` by default
+    pub fn allowed_origins(mut self, allowed_origins: impl Into<String>) -> Self {
+        self.conductor.allowed_origins = allowed_origins.into();
+        self
+    }
+
+    pub fn network(mut self, network: NetworkConfig) -> Self {
+        self.conductor.network = Some(network);
+        self
+    }
+
+    /// DPKI is disabled by default
+    pub fn dpki(mut self, dpki: DpkiConfig) -> Self {
+        self.conductor.dpki = dpki;
+        self
+    }
+
+    /// Directory of holochain databases, `databases` in tmp dir by default
+    pub fn data_root(mut self, data_root_path: impl Into<PathBuf>) -> Self {
+        self.conductor.data_root_path = data_root_path.into();
+        self
+    }
+
+    /// Attaches an app interface once holochain is running, on a port picked by holochain if None
+    pub fn app_interface(mut self, port: Option<u16>) -> Self {
+        self.app_interfaces.push(port);
+        self
+    }
+
+    pub async fn build(self) -> Result<Environment, SetupEnvironmentError> {
+        let tmp_dir = self.tmp_dir.unwrap_or_else(create_tmp_dir);
+        let log_dir = self.log_dir.unwrap_or_else(create_log_dir);
+
+        trace!("Starting lair-keystore");
+        let (lair, lair_config, keystore) = lair::spawn(
+            &tmp_dir,
+            &log_dir,
+            self.device_bundle.as_deref(),
+            self.lair_fallback,
+        )
+        .await
+        .unwrap();
+
+        let conductor = ConductorOptions {
+            // holochain binds port 0 itself, so concurrent environments can not pick the same one
+            admin_port: self.admin_port.unwrap_or(0),
+            ..self.conductor
+        };
+        trace!("Spinning up holochain");
+        let (holochain, admin_port) =
+            spawn_holochain_with_options(&tmp_dir, &log_dir, lair_config.clone(), &conductor);
+        trace!("holochain admin port is {}", admin_port);
+
+        let mut app_ports = vec![];
+        if !self.app_interfaces.is_empty() {
+            let mut admin_ws = AdminWebsocket::connect(admin_port)
+                .await
+                .map_err(|source| SetupEnvironmentError::AdminWs { source })?;
+            for port in self.app_interfaces {
+                let port = admin_ws
+                    .attach_app_interface(port, None)
+                    .await
+                    .map_err(|source| SetupEnvironmentError::AppWs { source })?;
+                app_ports.push(port);
+            }
+        }
+
+        Ok(Environment {
+            _holochain: holochain,
+            _lair: lair,
+            lair_config,
+            keystore,
+            admin_port,
+            app_ports,
+            tmp_dir,
+            log_dir,
+        })
+    }
 }
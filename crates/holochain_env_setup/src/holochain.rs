@@ -15,20 +15,34 @@ pub fn default_password() -> String {
     std::env::var("HOLOCHAIN_DEFAULT_PASSWORD").unwrap()
 }
 
+/// Admin port of holochain spawned by `spawn_holochain`
+pub const DEFAULT_ADMIN_PORT: u16 = 4444;
+
 pub fn spawn_holochain(
     tmp_dir: &Path,
     logs_dir: &Path,
     lair_config: LairConfig,
 ) -> KillChildOnDrop {
-    let lair_connection_url = lair_config.connection_url.to_string();
+    let (holochain, _) =
+        spawn_holochain_with_options(tmp_dir, logs_dir, lair_config, &ConductorOptions::default());
+    holochain
+}
 
-    let admin_port = 4444;
+/// Spawns holochain in `tmp_dir` configured by `options` and returns it with the port
+/// of its admin interface, which holochain reports on startup
+pub fn spawn_holochain_with_options(
+    tmp_dir: &Path,
+    logs_dir: &Path,
+    lair_config: LairConfig,
+    options: &ConductorOptions,
+) -> (KillChildOnDrop, u16) {
+    let lair_connection_url = lair_config.connection_url.to_string();
 
     let holochain_config_name = "holochain-config.yaml";
     write_holochain_config(
         &tmp_dir.join(holochain_config_name),
         lair_connection_url,
-        admin_port,
+        options,
     )
     .unwrap();
 
@@ -52,16 +66,29 @@ pub fn spawn_holochain(
         holochain_input.write_all(passphrase.as_bytes()).unwrap();
     }
 
+    let mut admin_port = None;
     for line in std::io::BufReader::new(holochain.stdout.as_mut().unwrap()).lines() {
         let line = line.unwrap();
         trace!("{:?}", line);
+        if let Some(port) = parse_admin_port(&line) {
+            admin_port = Some(port);
+        }
         if line == "Conductor ready." {
             eprintln!("Encountered magic string");
             break;
         }
     }
 
-    holochain
+    let admin_port = admin_port.expect("holochain did not report its admin port");
+    (holochain, admin_port)
+}
+
+/// Port of `###ADMIN_PORT:<port>###` line holochain prints for its admin interface when piped
+fn parse_admin_port(line: &str) -> Option<u16> {
+    line.strip_prefix("###ADMIN_PORT:")?
+        .strip_suffix("###")?
+        .parse()
+        .ok()
 }
 
 pub fn create_tmp_dir() -> PathBuf {
@@ -77,10 +104,101 @@ pub enum WriteHolochainConfigError {
     CreateHolochainConfig { path: PathBuf },
 }
 
+/// Settings of holochain-config.yaml written by `spawn_holochain_with_options`
+#[derive(Debug, Clone)]
+pub struct ConductorOptions {
+    /// 0 lets holochain pick a free port, see `spawn_holochain_with_options`
+    pub admin_port: u16,
+    /// Origins allowed to connect to the admin interface
+    pub allowed_origins: String,
+    /// Relative paths are resolved against the tmp dir holochain runs in
+    pub data_root_path: PathBuf,
+    pub dpki: DpkiConfig,
+    /// Holochain's default network config is used if not set
+    pub network: Option<NetworkConfig>,
+}
+
+impl Default for ConductorOptions {
+    fn default() -> Self {
+        Self {
+            admin_port: DEFAULT_ADMIN_PORT,
+            allowed_origins: "*".to_string(),
+            data_root_path: "./databases".into(),
+            dpki: DpkiConfig::default(),
+            network: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DpkiConfig {
+    pub dna_path: Option<PathBuf>,
+    pub device_seed_lair_tag: String,
+    pub no_dpki: bool,
+}
+
+impl Default for DpkiConfig {
+    // Holo does not use DPKI, when we start using it this should be updated
+    fn default() -> Self {
+        Self {
+            dna_path: None,
+            device_seed_lair_tag: "dont-use-dpki".to_string(),
+            no_dpki: true,
+        }
+    }
+}
+
+impl DpkiConfig {
+    /// DPKI with device seed stored in lair under `device_seed_lair_tag`,
+    /// the DPKI dna bundled with holochain is used if `dna_path` is None
+    pub fn enabled(device_seed_lair_tag: impl Into<String>, dna_path: Option<PathBuf>) -> Self {
+        Self {
+            dna_path,
+            device_seed_lair_tag: device_seed_lair_tag.into(),
+            no_dpki: false,
+        }
+    }
+}
+
+/// Network section of holochain-config.yaml
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkConfig {
+    pub network_type: NetworkType,
+    pub bootstrap_service: Option<String>,
+    pub transport_pool: Vec<TransportConfig>,
+}
+
+impl NetworkConfig {
+    /// Peers are discovered via `bootstrap_service` and connected over webrtc signalled by `signal_url`
+    pub fn bootstrap(bootstrap_service: impl Into<String>, signal_url: impl Into<String>) -> Self {
+        Self {
+            network_type: NetworkType::QuicBootstrap,
+            bootstrap_service: Some(bootstrap_service.into()),
+            transport_pool: vec![TransportConfig::Webrtc {
+                signal_url: signal_url.into(),
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkType {
+    QuicBootstrap,
+    QuicMdns,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransportConfig {
+    Mem,
+    Webrtc { signal_url: String },
+}
+
 fn write_holochain_config(
     path: &Path,
     lair_connection_url: String,
-    admin_port: u16,
+    options: &ConductorOptions,
 ) -> Result<(), WriteHolochainConfigError> {
     let mut holochain_config_file = std::fs::OpenOptions::new()
         .create_new(true)
@@ -89,17 +207,13 @@ fn write_holochain_config(
         .unwrap();
 
     #[derive(Serialize)]
-    struct HolochainConfig {
-        data_root_path: PathBuf,
+    struct HolochainConfig<'a> {
+        data_root_path: &'a Path,
         keystore: KeystoreConfig,
-        dpki: DpkiConfig,
+        dpki: &'a DpkiConfig,
         admin_interfaces: Option<Vec<AdminInterfaceConfig>>,
-    }
-    #[derive(Serialize)]
-    pub struct DpkiConfig {
-        pub dna_path: Option<PathBuf>,
-        pub device_seed_lair_tag: String,
-        pub no_dpki: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        network: Option<&'a NetworkConfig>,
     }
     #[derive(Serialize)]
     #[serde(tag = "type", rename_all = "snake_case")]
@@ -119,24 +233,33 @@ fn write_holochain_config(
     }
 
     let config = HolochainConfig {
-        data_root_path: "./databases".into(),
+        data_root_path: &options.data_root_path,
         keystore: KeystoreConfig::LairServer {
             connection_url: lair_connection_url,
         },
-        // Holo does not use DPKI, when we start using it this should be updated
-        dpki: DpkiConfig {
-            dna_path: None,
-            device_seed_lair_tag: "dont-use-dpki".to_string(),
-            no_dpki: true,
-        },
+        dpki: &options.dpki,
         admin_interfaces: Some(vec![AdminInterfaceConfig {
             driver: AdminInterfaceDriver::Websocket {
-                port: admin_port,
-                allowed_origins: "*".to_string(),
+                port: options.admin_port,
+                allowed_origins: options.allowed_origins.clone(),
             },
         }]),
+        network: options.network.as_ref(),
     };
     serde_yaml::to_writer(&mut holochain_config_file, &config).unwrap();
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_port_is_parsed_from_holochain_output() {
+        assert_eq!(parse_admin_port("###ADMIN_PORT:40123###"), Some(40123));
+        assert_eq!(parse_admin_port("###HOLOCHAIN_SETUP###"), None);
+        assert_eq!(parse_admin_port("###ADMIN_PORT:none###"), None);
+        assert_eq!(parse_admin_port("Conductor ready."), None);
+    }
+}
@@ -0,0 +1,21 @@
+//! Needs `holochain` and `lair-keystore` on PATH.
+
+use anyhow::Result;
+use holochain_env_setup::environment::Environment;
+use hpos_hc_connect::AdminWebsocket;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environments_build_concurrently() -> Result<()> {
+    std::env::set_var("HOLOCHAIN_DEFAULT_PASSWORD", "pass");
+
+    let first = tokio::spawn(Environment::builder().build());
+    let second = tokio::spawn(Environment::builder().build());
+    let (first, second) = (first.await??, second.await??);
+
+    assert_ne!(first.admin_port, second.admin_port);
+    for environment in [&first, &second] {
+        let mut admin_ws = AdminWebsocket::connect(environment.admin_port).await?;
+        assert!(admin_ws.list_enabled_apps().await?.is_empty());
+    }
+    Ok(())
+}
@@ -1,21 +1,15 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hf_agent::HfAgent, holofuel_types::Transaction,
-};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hf_agent::HfAgent, holofuel_types::Transaction, HposEnvironment};
 
-pub async fn get() -> Result<()> {
-    let mut agent = HfAgent::spawn(None).await?;
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let txs: Vec<Transaction> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_completed_transactions"),
-            (),
-        )
-        .await?;
+    let txs: Vec<Transaction> = agent.completed().await?;
+
+    if output.print(&txs)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("Your Completed List is: ");
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Context, Result};
+use holochain_types::prelude::{AgentPubKeyB64, CapSecret, EntryHashB64, Timestamp};
+use holofuel_types::fuel::Fuel;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{
+    hf_agent::HfAgent,
+    holofuel_types::{
+        CounterSigningResponse, InvoiceInput, PayloadInput, PromiseInput, Transaction, POS,
+    },
+    pay::describe_countersigning,
+    HposEnvironment,
+};
+use serde::Serialize;
+use std::str::FromStr;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Promises have to expire, this is used when no expiration is given
+const DEFAULT_PROMISE_EXPIRY_DAYS: u64 = 7;
+
+/// Arguments shared by invoice and promise creation
+#[derive(Debug, StructOpt)]
+pub struct TxOpt {
+    /// Counterparty's agent public key (uhCAk...)
+    pub counterparty: String,
+    /// Amount of fuel, eg. 10.5
+    pub amount: String,
+    /// Note attached to the transaction
+    #[structopt(long)]
+    pub note: Option<String>,
+    /// Number of days after which the transaction expires
+    #[structopt(long)]
+    pub expires_in_days: Option<u64>,
+    /// Proof of service for hosting: base64 encoded cap secret
+    #[structopt(long, conflicts_with = "redemption")]
+    pub hosting: Option<String>,
+    /// Proof of service for redemption: wallet address
+    #[structopt(long)]
+    pub redemption: Option<String>,
+}
+
+/// Structured output of `promise`. The transaction is printed even when completing it
+/// fails, with the failure in `completion_error`, and the command succeeds.
+#[derive(Serialize)]
+struct CreatedPromise {
+    transaction: Transaction,
+    completion: Option<CounterSigningResponse>,
+    completion_error: Option<String>,
+}
+
+struct ParsedTx {
+    counterparty: AgentPubKeyB64,
+    amount: Fuel,
+    payload: PayloadInput,
+    expiration_date: Option<Timestamp>,
+}
+
+impl TxOpt {
+    fn parse(self) -> Result<ParsedTx> {
+        let counterparty = AgentPubKeyB64::from_b64_str(&self.counterparty)
+            .map_err(|e| anyhow!("invalid counterparty {}: {:?}", self.counterparty, e))?;
+        let amount = Fuel::from_str(&self.amount)
+            .map_err(|e| anyhow!("invalid amount {}: {:?}", self.amount, e))?;
+
+        let proof_of_service = match (self.hosting, self.redemption) {
+            (Some(secret), _) => Some(POS::Hosting(parse_cap_secret(&secret)?)),
+            (None, Some(wallet)) => Some(POS::Redemption(wallet)),
+            (None, None) => None,
+        };
+
+        let expiration_date = self
+            .expires_in_days
+            .map(|days| Timestamp::now() + Duration::from_secs(days * 24 * 60 * 60))
+            .transpose()
+            .map_err(|e| anyhow!("invalid expiration: {:?}", e))?;
+
+        Ok(ParsedTx {
+            counterparty,
+            amount,
+            payload: PayloadInput {
+                note: self.note,
+                proof_of_service,
+                url: None,
+            },
+            expiration_date,
+        })
+    }
+}
+
+fn parse_cap_secret(secret: &str) -> Result<CapSecret> {
+    let bytes: [u8; 64] = base64::decode(secret)
+        .context("hosting cap secret is not valid base64")?
+        .try_into()
+        .map_err(|_| anyhow!("hosting cap secret has to be 64 bytes long"))?;
+    Ok(CapSecret::from(bytes))
+}
+
+pub async fn invoice(opt: TxOpt, output: OutputFormat) -> Result<()> {
+    let tx = opt.parse()?;
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+
+    let transaction = agent
+        .create_invoice(InvoiceInput {
+            receiver: tx.counterparty,
+            amount: tx.amount.to_string(),
+            payload: tx.payload,
+            expiration_date: tx.expiration_date,
+        })
+        .await?;
+
+    if output.print(&transaction)? {
+        return Ok(());
+    }
+
+    println!("===================");
+    println!("Invoice created: ");
+    display(&transaction);
+    println!("===================");
+
+    Ok(())
+}
+
+/// Creates a promise. If it pays an existing invoice tries to complete it right away,
+/// otherwise the counterparty has to accept it first.
+pub async fn promise(opt: TxOpt, invoice: Option<String>, output: OutputFormat) -> Result<()> {
+    let tx = opt.parse()?;
+    let invoice_hash = invoice
+        .map(|hash| {
+            EntryHashB64::from_b64_str(&hash)
+                .map_err(|e| anyhow!("invalid invoice hash {}: {:?}", hash, e))
+        })
+        .transpose()?;
+    let expiration_date = match tx.expiration_date {
+        Some(date) => date,
+        None => (Timestamp::now()
+            + Duration::from_secs(DEFAULT_PROMISE_EXPIRY_DAYS * 24 * 60 * 60))
+        .map_err(|e| anyhow!("invalid expiration: {:?}", e))?,
+    };
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+
+    let transaction = agent
+        .create_promise(PromiseInput {
+            receiver: tx.counterparty,
+            amount: tx.amount.to_string(),
+            timestamp: Timestamp::now(),
+            expiration_date,
+            payload: tx.payload,
+            invoice_hash: invoice_hash.clone(),
+        })
+        .await?;
+
+    // a promise that can not be completed right away is still created, completion is
+    // retried by the scheduler, so only the completion error is reported
+    if output.is_structured() {
+        let completion = match invoice_hash {
+            Some(_) => Some(agent.complete_transactions(transaction.id.clone()).await),
+            None => None,
+        }
+        .transpose();
+        let (completion, completion_error) = match completion {
+            Ok(completion) => (completion, None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+        output.print(&CreatedPromise {
+            transaction,
+            completion,
+            completion_error,
+        })?;
+        return Ok(());
+    }
+
+    println!("===================");
+    println!("Promise created: ");
+    display(&transaction);
+
+    if invoice_hash.is_some() {
+        println!("Trying to complete, if this fails it will be completed by your scheduler");
+        match agent.complete_transactions(transaction.id.clone()).await {
+            Ok(response) => println!("{}", describe_countersigning(&response)),
+            Err(e) => println!("Completion failed: {:#}", e),
+        }
+    }
+    println!("===================");
+
+    Ok(())
+}
+
+fn display(tx: &Transaction) {
+    println!("Id: {:?}", tx.id);
+    println!("Amount: {:?}", tx.amount);
+    println!("Fee: {:?}", tx.fee);
+    println!("Counterparty: {:?}", tx.counterparty);
+    println!("Status: {:?}", tx.status);
+    println!("Note: {:?}", tx.note);
+    println!("Proof of service: {:?}", tx.proof_of_service);
+    println!("Expiration: {:?}", tx.expiration_date);
+}
@@ -0,0 +1,245 @@
+use anyhow::{anyhow, Result};
+use chrono::DateTime;
+use holochain_types::prelude::Timestamp;
+use hpos_hc_connect::hf_agent::HfAgent;
+use hpos_hc_connect::holofuel_types::{Actionable, Pending, Transaction, POS};
+use hpos_hc_connect::pay::parse_date;
+use hpos_hc_connect::HposEnvironment;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(anyhow!(
+                "unknown export format {}, expected csv or jsonl",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportOpt {
+    /// Export format: csv or jsonl
+    #[structopt(long, short, default_value = "csv")]
+    pub format: ExportFormat,
+    /// Only transactions on or after this date (YYYY-MM-DD or RFC 3339)
+    #[structopt(long)]
+    pub from: Option<String>,
+    /// Only transactions before this date (YYYY-MM-DD or RFC 3339)
+    #[structopt(long)]
+    pub to: Option<String>,
+    /// Write to this file instead of stdout
+    #[structopt(long, parse(from_os_str))]
+    pub file: Option<PathBuf>,
+}
+
+/// One exported transaction. Dates are RFC 3339 in UTC.
+#[derive(Debug, Serialize)]
+pub struct ExportRow {
+    pub id: String,
+    /// completed, pending or actionable - the list the transaction came from
+    pub list: &'static str,
+    pub status: String,
+    pub transaction_type: String,
+    pub direction: String,
+    pub amount: String,
+    pub fee: String,
+    pub counterparty: String,
+    pub created_date: String,
+    pub completed_date: Option<String>,
+    pub expiration_date: Option<String>,
+    pub note: Option<String>,
+    /// hosting or redemption
+    pub pos_type: Option<&'static str>,
+}
+
+const CSV_HEADER: [&str; 13] = [
+    "id",
+    "list",
+    "status",
+    "transaction_type",
+    "direction",
+    "amount",
+    "fee",
+    "counterparty",
+    "created_date",
+    "completed_date",
+    "expiration_date",
+    "note",
+    "pos_type",
+];
+
+impl ExportRow {
+    fn new(list: &'static str, tx: &Transaction) -> Self {
+        ExportRow {
+            id: tx.id.to_string(),
+            list,
+            status: format!("{:?}", tx.status),
+            transaction_type: format!("{:?}", tx.transaction_type),
+            direction: format!("{:?}", tx.direction),
+            amount: tx.amount.clone(),
+            fee: tx.fee.clone(),
+            counterparty: tx.counterparty.to_string(),
+            created_date: rfc3339(&tx.created_date),
+            completed_date: tx.completed_date.as_ref().map(rfc3339),
+            expiration_date: tx.expiration_date.as_ref().map(rfc3339),
+            note: tx.note.clone(),
+            pos_type: tx.proof_of_service.as_ref().map(|pos| match pos {
+                POS::Hosting(_) => "hosting",
+                POS::Redemption(_) => "redemption",
+            }),
+        }
+    }
+
+    fn csv_fields(&self) -> [&str; 13] {
+        [
+            &self.id,
+            self.list,
+            &self.status,
+            &self.transaction_type,
+            &self.direction,
+            &self.amount,
+            &self.fee,
+            &self.counterparty,
+            &self.created_date,
+            self.completed_date.as_deref().unwrap_or_default(),
+            self.expiration_date.as_deref().unwrap_or_default(),
+            self.note.as_deref().unwrap_or_default(),
+            self.pos_type.unwrap_or_default(),
+        ]
+    }
+}
+
+fn rfc3339(timestamp: &Timestamp) -> String {
+    DateTime::from_timestamp_micros(timestamp.as_micros())
+        .map(|date| date.to_rfc3339())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// Quotes a CSV field when it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv_line<'a>(
+    out: &mut impl Write,
+    fields: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let line: Vec<String> = fields.into_iter().map(csv_field).collect();
+    writeln!(out, "{}", line.join(","))?;
+    Ok(())
+}
+
+/// Date range filter, a transaction is dated by its completion or, until completed, its creation
+fn in_range(tx: &Transaction, from: Option<&Timestamp>, to: Option<&Timestamp>) -> bool {
+    let date = tx.completed_date.as_ref().unwrap_or(&tx.created_date);
+    from.map_or(true, |from| date >= from) && to.map_or(true, |to| date < to)
+}
+
+/// Exports completed, pending and actionable transactions for accounting
+pub async fn get(opt: ExportOpt) -> Result<()> {
+    let from = opt.from.as_deref().map(parse_date).transpose()?;
+    let to = opt.to.as_deref().map(parse_date).transpose()?;
+
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+
+    let completed: Vec<Transaction> = agent.completed().await?;
+    let pending: Pending = agent.pending().await?;
+    let actionable: Actionable = agent.actionable().await?;
+
+    let mut txs: Vec<(&'static str, Transaction)> =
+        completed.into_iter().map(|tx| ("completed", tx)).collect();
+    txs.extend(
+        [
+            pending.invoice_pending,
+            pending.promise_pending,
+            pending.invoice_declined,
+            pending.promise_declined,
+            pending.accepted,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|tx| ("pending", tx)),
+    );
+    txs.extend(
+        [actionable.invoice_actionable, actionable.promise_actionable]
+            .into_iter()
+            .flatten()
+            .map(|tx| ("actionable", tx)),
+    );
+    let rows: Vec<ExportRow> = txs
+        .iter()
+        .filter(|(_, tx)| in_range(tx, from.as_ref(), to.as_ref()))
+        .map(|(list, tx)| ExportRow::new(list, tx))
+        .collect();
+
+    let mut out: Box<dyn Write> = match &opt.file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    match opt.format {
+        ExportFormat::Csv => {
+            write_csv_line(&mut out, CSV_HEADER)?;
+            for row in &rows {
+                write_csv_line(&mut out, row.csv_fields())?;
+            }
+        }
+        ExportFormat::Jsonl => {
+            for row in &rows {
+                writeln!(out, "{}", serde_json::to_string(row)?)?;
+            }
+        }
+    }
+    out.flush()?;
+
+    if let Some(path) = &opt.file {
+        eprintln!("Exported {} transactions to {}", rows.len(), path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("10.5"), "10.5");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("rent, march"), "\"rent, march\"");
+        assert_eq!(csv_field("the \"big\" one"), "\"the \"\"big\"\" one\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn csv_lines_join_quoted_fields() {
+        let mut out = vec![];
+        write_csv_line(&mut out, ["id", "a,b", "say \"hi\""]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,\"a,b\",\"say \"\"hi\"\"\"\n"
+        );
+    }
+}
@@ -1,19 +1,15 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{app_connection::CoreAppRoleName, hf_agent::HfAgent, holofuel_types::Ledger};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hf_agent::HfAgent, holofuel_types::Ledger, HposEnvironment};
 
-pub async fn get() -> Result<()> {
-    let mut agent = HfAgent::spawn(None).await?;
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let ledger: Ledger = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_ledger"),
-            (),
-        )
-        .await?;
+    let ledger: Ledger = agent.ledger().await?;
+
+    if output.print(&ledger)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("Your Ledger is: ");
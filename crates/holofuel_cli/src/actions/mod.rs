@@ -1,7 +1,12 @@
 pub mod actionable;
 pub mod completed;
+pub mod create;
+pub mod export;
 pub mod ledger;
+pub mod pay;
 pub mod pending;
 pub mod profile;
+pub mod reconcile;
 pub mod reserve;
 pub mod summary;
+pub mod transaction;
@@ -0,0 +1,11 @@
+use anyhow::Result;
+use hpos_hc_connect::hf_agent::HfAgent;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::pay::{pay_invoices, PayOpt};
+use hpos_hc_connect::HposEnvironment;
+
+/// Accepts and completes all pending invoices matching given filters
+pub async fn get(opt: PayOpt, output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+    pay_invoices(&mut agent, opt, output).await
+}
@@ -1,29 +1,23 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hf_agent::HfAgent, holofuel_types::Pending,
-};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hf_agent::HfAgent, holofuel_types::Pending, HposEnvironment};
 
-pub async fn get() -> Result<()> {
-    let mut agent = HfAgent::spawn(None).await?;
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let txs: Pending = match agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("transactor"),
-            FunctionName::from("get_pending_transactions"),
-            (),
-        )
-        .await
-    {
+    let txs: Pending = match agent.pending().await {
         Ok(txs) => txs,
+        Err(e) if output.is_structured() => return Err(e),
         Err(e) => {
             println!("Error: {:?}", e);
             return Ok(());
         }
     };
 
+    if output.print(&txs)? {
+        return Ok(());
+    }
+
     println!("===================");
     println!("Your Pending List is: ");
     println!("Invoices Pending: {:?}", txs.invoice_pending);
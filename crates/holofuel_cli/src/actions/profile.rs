@@ -1,21 +1,15 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
-use hpos_hc_connect::{
-    app_connection::CoreAppRoleName, hf_agent::HfAgent, holofuel_types::Profile,
-};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::{hf_agent::HfAgent, holofuel_types::Profile, HposEnvironment};
 
-pub async fn get() -> Result<()> {
-    let mut agent = HfAgent::spawn(None).await?;
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let profile: Profile = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("profile"),
-            FunctionName::from("get_my_profile"),
-            (),
-        )
-        .await?;
+    let profile: Profile = agent.profile().await?;
+
+    if output.print(&profile)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("Your Profile details are: ");
@@ -0,0 +1,319 @@
+use anyhow::{anyhow, Result};
+use holochain_types::dna::AgentPubKey;
+use holofuel_types::fuel::Fuel;
+use hpos_hc_connect::hf_agent::HfAgent;
+use hpos_hc_connect::holofuel_types::{
+    parse_fuel, Ledger, MigrationCloseStateV1Handler, Transaction, TransactionDirection,
+};
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::HposEnvironment;
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Result of cross checking the ledger, completed transactions and migration summary.
+/// `mismatches` is empty when everything adds up.
+#[derive(Debug, Serialize)]
+pub struct Reconciliation {
+    pub ledger_balance: Fuel,
+    /// Incoming minus outgoing amounts of completed transactions
+    pub completed_net: Fuel,
+    pub completed_fees: Fuel,
+    pub opening_balance: Fuel,
+    pub closing_balance: Fuel,
+    /// Net change of the balance over the countersigned transactions of the summary
+    pub summary_net: Fuel,
+    pub mismatches: Vec<String>,
+}
+
+fn add(a: Fuel, b: Fuel) -> Result<Fuel> {
+    (a + b).map_err(|e| anyhow!("cannot add {} to {}: {:?}", b, a, e))
+}
+
+fn sub(a: Fuel, b: Fuel) -> Result<Fuel> {
+    (a - b).map_err(|e| anyhow!("cannot subtract {} from {}: {:?}", b, a, e))
+}
+
+/// Checks that completed transactions minus fees add up to the ledger balance, and that
+/// the summary's opening balance moved by its countersigned transactions ends at its
+/// closing balance, with each transaction's resulting balance on `me`'s side in line.
+pub fn reconcile(
+    me: &AgentPubKey,
+    ledger: &Ledger,
+    completed: &[Transaction],
+    summary: &MigrationCloseStateV1Handler,
+) -> Result<Reconciliation> {
+    let mut mismatches = vec![];
+
+    let mut completed_net = Fuel::new(0);
+    let mut completed_fees = Fuel::new(0);
+    for tx in completed {
+        let amount = parse_fuel(&tx.amount)?;
+        completed_net = match tx.direction {
+            TransactionDirection::Incoming => add(completed_net, amount)?,
+            TransactionDirection::Outgoing => sub(completed_net, amount)?,
+        };
+        completed_fees = add(completed_fees, parse_fuel(&tx.fee)?)?;
+    }
+    let ledger_balance = parse_fuel(&ledger.balance)?;
+    let completed_balance = sub(completed_net, completed_fees)?;
+    if completed_balance != ledger_balance {
+        mismatches.push(format!(
+            "completed transactions minus fees sum to {}, ledger balance is {}",
+            completed_balance, ledger_balance
+        ));
+    }
+
+    let opening_balance = summary.opening_balance;
+    let closing_balance = summary.closing_balance;
+    let mut cs_txs: Vec<_> = summary.cs_txs.iter().collect();
+    cs_txs.sort_by_key(|(_, _, timestamp)| *timestamp);
+    let mut running = opening_balance;
+    for (tx, hash, _) in cs_txs {
+        let amount = tx.tx_body.tx_amt;
+        let new_bal = if &tx.tx_body.spender_chain_info.agent_address == me {
+            running = sub(running, amount)?;
+            tx.spender_state.new_bal
+        } else if &tx.tx_body.receiver_chain_info.agent_address == me {
+            running = add(running, amount)?;
+            tx.receiver_state.new_bal
+        } else {
+            mismatches.push(format!(
+                "countersigned tx {} is not signed by this agent",
+                hash
+            ));
+            continue;
+        };
+        if new_bal != running {
+            mismatches.push(format!(
+                "countersigned tx {} leaves balance at {}, expected {}",
+                hash, new_bal, running
+            ));
+            running = new_bal;
+        }
+    }
+    if running != closing_balance {
+        mismatches.push(format!(
+            "opening balance {} with countersigned transactions gives {}, closing balance is {}",
+            opening_balance, running, closing_balance
+        ));
+    }
+
+    Ok(Reconciliation {
+        ledger_balance,
+        completed_net,
+        completed_fees,
+        opening_balance,
+        closing_balance,
+        summary_net: sub(running, opening_balance)?,
+        mismatches,
+    })
+}
+
+/// Reports mismatches between ledger, completed transactions and summary, fails if any are found
+pub async fn get(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+
+    let me = agent.pubkey().await?;
+    let ledger = agent.ledger().await?;
+    let completed = agent.completed().await?;
+    let summary = agent.my_summary().await?;
+
+    let report = reconcile(&me, &ledger, &completed, &summary)?;
+
+    if !output.print(&report)? {
+        println!("===================");
+        println!("Ledger balance: {}", report.ledger_balance);
+        println!("Completed tx net: {}", report.completed_net);
+        println!("Completed tx fees: {}", report.completed_fees);
+        println!("Opening balance: {}", report.opening_balance);
+        println!("Countersigned tx net: {}", report.summary_net);
+        println!("Closing balance: {}", report.closing_balance);
+        if report.mismatches.is_empty() {
+            println!("Everything reconciles");
+        }
+        for mismatch in &report.mismatches {
+            println!("Mismatch: {}", mismatch);
+        }
+        println!("===================");
+    }
+
+    if report.mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("found {} mismatches", report.mismatches.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_types::prelude::{ActionHash, EntryHash, Signature, Timestamp};
+    use hpos_hc_connect::holofuel_types::{
+        ChainInfo, CounterSignedTx, CounterSignedTxBody, FuelState, Payload, TransactionStatus,
+        TransactionType,
+    };
+
+    fn agent(byte: u8) -> AgentPubKey {
+        AgentPubKey::from_raw_36(vec![byte; 36])
+    }
+
+    fn fuel(amount: &str) -> Fuel {
+        Fuel::from_str(amount).unwrap()
+    }
+
+    fn ledger(balance: &str) -> Ledger {
+        Ledger {
+            balance: balance.into(),
+            promised: "0".into(),
+            fees: "0".into(),
+            available: balance.into(),
+        }
+    }
+
+    fn completed(direction: TransactionDirection, amount: &str, fee: &str) -> Transaction {
+        Transaction {
+            id: EntryHash::from_raw_36(vec![1; 36]).into(),
+            amount: amount.into(),
+            fee: fee.into(),
+            created_date: Timestamp::from_micros(0),
+            completed_date: Some(Timestamp::from_micros(1)),
+            transaction_type: TransactionType::Offer,
+            counterparty: agent(2).into(),
+            direction,
+            status: TransactionStatus::Completed,
+            note: None,
+            proof_of_service: None,
+            url: None,
+            expiration_date: None,
+        }
+    }
+
+    fn chain_info(agent_address: AgentPubKey) -> ChainInfo {
+        ChainInfo {
+            agent_address,
+            pre_auth: EntryHash::from_raw_36(vec![3; 36]),
+            prior_action: ActionHash::from_raw_36(vec![4; 36]),
+            tx_seq_num: 0,
+        }
+    }
+
+    fn fuel_state(new_bal: &str) -> FuelState {
+        FuelState {
+            new_bal: fuel(new_bal),
+            new_promise: Fuel::new(0),
+            tx_fees_owed: Fuel::new(0),
+            tx_body_signature: Signature([0; 64]),
+        }
+    }
+
+    /// Countersigned `amount` from `spender` to `receiver`, leaving given balances
+    fn cs_tx(
+        spender: (AgentPubKey, &str),
+        receiver: (AgentPubKey, &str),
+        amount: &str,
+        at: i64,
+    ) -> (CounterSignedTx, ActionHash, Timestamp) {
+        let tx = CounterSignedTx {
+            tx_body: CounterSignedTxBody {
+                tx_amt: fuel(amount),
+                tx_fee: Fuel::new(0),
+                spender_payload: Payload::default(),
+                receiver_payload: Payload::default(),
+                spender_chain_info: chain_info(spender.0),
+                receiver_chain_info: chain_info(receiver.0),
+            },
+            spender_state: fuel_state(spender.1),
+            receiver_state: fuel_state(receiver.1),
+        };
+        (
+            tx,
+            ActionHash::from_raw_36(vec![at as u8; 36]),
+            Timestamp::from_micros(at),
+        )
+    }
+
+    fn summary(
+        opening_balance: &str,
+        closing_balance: &str,
+        cs_txs: Vec<(CounterSignedTx, ActionHash, Timestamp)>,
+    ) -> MigrationCloseStateV1Handler {
+        MigrationCloseStateV1Handler {
+            opening_balance: fuel(opening_balance),
+            closing_balance: fuel(closing_balance),
+            number_of_declined: 0,
+            multi_sig_authorizer: None,
+            reserve_setting: None,
+            reserve_sale_price: None,
+            cs_txs,
+            tx_parked_links: vec![],
+            incomplete_invoice_txs: vec![],
+            incomplete_promise_txs: vec![],
+        }
+    }
+
+    #[test]
+    fn reconciles_matching_balances() {
+        let me = agent(10);
+        let txs = [
+            completed(TransactionDirection::Incoming, "10.5", "0"),
+            completed(TransactionDirection::Outgoing, "4", "0.25"),
+        ];
+        // listed out of order, sorted by timestamp before replaying
+        let summary = summary(
+            "1",
+            "7.5",
+            vec![
+                cs_tx((me.clone(), "7.5"), (agent(2), "4"), "4", 2),
+                cs_tx((agent(2), "0"), (me.clone(), "11.5"), "10.5", 1),
+            ],
+        );
+
+        let report = reconcile(&me, &ledger("6.25"), &txs, &summary).unwrap();
+
+        assert!(report.mismatches.is_empty(), "{:?}", report.mismatches);
+        assert_eq!(report.completed_net, fuel("6.5"));
+        assert_eq!(report.completed_fees, fuel("0.25"));
+        assert_eq!(report.summary_net, fuel("6.5"));
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let me = agent(10);
+        let txs = [completed(TransactionDirection::Incoming, "10", "1")];
+        let summary = summary(
+            "0",
+            "12",
+            vec![
+                // should leave 10
+                cs_tx((agent(2), "0"), (me.clone(), "11"), "10", 1),
+                cs_tx((agent(2), "0"), (agent(3), "1"), "1", 2),
+                cs_tx((me.clone(), "9"), (agent(2), "2"), "2", 3),
+            ],
+        );
+
+        let report = reconcile(&me, &ledger("10"), &txs, &summary).unwrap();
+
+        assert_eq!(report.mismatches.len(), 4, "{:?}", report.mismatches);
+        assert_eq!(
+            report.mismatches[0],
+            format!(
+                "completed transactions minus fees sum to {}, ledger balance is {}",
+                fuel("9"),
+                fuel("10")
+            )
+        );
+        assert!(report.mismatches[1].ends_with(&format!(
+            "leaves balance at {}, expected {}",
+            fuel("11"),
+            fuel("10")
+        )));
+        assert!(report.mismatches[2].ends_with("is not signed by this agent"));
+        // the summary's balance is followed after a mismatch
+        assert!(report.mismatches[3].ends_with(&format!(
+            "gives {}, closing balance is {}",
+            fuel("9"),
+            fuel("12")
+        )));
+        assert_eq!(report.summary_net, fuel("9"));
+    }
+}
@@ -1,23 +1,19 @@
 use anyhow::Result;
-use holochain_types::prelude::{FunctionName, ZomeName};
+use hpos_hc_connect::output::OutputFormat;
 use hpos_hc_connect::{
-    app_connection::CoreAppRoleName,
     hf_agent::HfAgent,
     holofuel_types::{Reserve, ReserveSalePrice},
+    HposEnvironment,
 };
 
-pub async fn get_setting() -> Result<()> {
-    let mut agent = HfAgent::spawn(None).await?;
+pub async fn get_setting(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
 
-    let reserve: Vec<Reserve> = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("reserves"),
-            FunctionName::from("get_all_reserve_accounts_details"),
-            (),
-        )
-        .await?;
+    let reserve: Vec<Reserve> = agent.reserve_accounts().await?;
+
+    if output.print(&reserve)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("All Reserve details: ");
@@ -27,18 +23,14 @@ pub async fn get_setting() -> Result<()> {
     Ok(())
 }
 
-pub async fn get_sale_price() -> Result<()> {
-    let mut agent = HfAgent::spawn(None).await?;
-
-    let reserve: ReserveSalePrice = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("reserves"),
-            FunctionName::from("get_my_sale_price"),
-            (),
-        )
-        .await?;
+pub async fn get_sale_price(output: OutputFormat) -> Result<()> {
+    let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await?;
+
+    let reserve: ReserveSalePrice = agent.sale_price().await?;
+
+    if output.print(&reserve)? {
+        return Ok(());
+    }
 
     println!("===================");
     println!("Reserve Sale Price: ");
@@ -0,0 +1,27 @@
+//! `accept`, `decline`, `cancel` and `complete`, see `hpos_hc_connect::pay`
+
+use anyhow::Result;
+use hpos_hc_connect::hf_agent::HfAgent;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::pay;
+use hpos_hc_connect::HposEnvironment;
+
+async fn agent() -> Result<HfAgent> {
+    HfAgent::spawn(None, &HposEnvironment::from_env()).await
+}
+
+pub async fn accept(id: String, output: OutputFormat) -> Result<()> {
+    pay::accept(&mut agent().await?, &id, output).await
+}
+
+pub async fn decline(id: String, output: OutputFormat) -> Result<()> {
+    pay::decline(&mut agent().await?, &id, output).await
+}
+
+pub async fn cancel(id: String, output: OutputFormat) -> Result<()> {
+    pay::cancel(&mut agent().await?, &id, output).await
+}
+
+pub async fn complete(id: String, output: OutputFormat) -> Result<()> {
+    pay::complete(&mut agent().await?, &id, output).await
+}
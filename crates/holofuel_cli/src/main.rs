@@ -1,7 +1,19 @@
 use anyhow::Result;
+use hf::actions::{create::TxOpt, export::ExportOpt};
 use holochain_types::dna::AgentPubKeyB64;
+use hpos_hc_connect::output::OutputFormat;
+use hpos_hc_connect::pay::PayOpt;
 use structopt::StructOpt;
 
+#[derive(Debug, StructOpt)]
+struct Cli {
+    /// Output format: table, json or yaml
+    #[structopt(long, short, global = true, default_value = "table")]
+    output: OutputFormat,
+    #[structopt(subcommand)]
+    cmd: Opt,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum Opt {
     /// Gets your balance, fees, promised and available Fuel
@@ -31,25 +43,69 @@ pub enum Opt {
     /// Get Summary by providing an agent public key
     #[structopt(name = "gas")]
     GetAgentSummary { pub_key: String },
+    /// Creates an invoice asking counterparty to pay given amount
+    #[structopt(name = "invoice")]
+    Invoice(TxOpt),
+    /// Creates a promise to pay counterparty given amount
+    #[structopt(name = "promise")]
+    Promise {
+        #[structopt(flatten)]
+        tx: TxOpt,
+        /// Hash of an invoice this promise pays, transaction is then completed right away
+        #[structopt(long)]
+        invoice: Option<String>,
+    },
+    /// Accepts a pending transaction and tries to complete it
+    #[structopt(name = "accept")]
+    Accept { tx_id: String },
+    /// Declines a pending transaction
+    #[structopt(name = "decline")]
+    Decline { tx_id: String },
+    /// Cancels a transaction created by you
+    #[structopt(name = "cancel")]
+    Cancel { tx_id: String },
+    /// Retries completing an accepted transaction
+    #[structopt(name = "complete")]
+    Complete { tx_id: String },
+    /// Pays all pending invoices matching given filters
+    #[structopt(name = "pay")]
+    Pay(PayOpt),
+    /// Exports completed, pending and actionable transactions as CSV or JSON Lines
+    #[structopt(name = "export")]
+    Export(ExportOpt),
+    /// Checks completed transactions and summary balances against the ledger
+    #[structopt(name = "reconcile")]
+    Reconcile,
 }
 
 impl Opt {
     /// Run this command
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(self, output: OutputFormat) -> Result<()> {
         match self {
-            Opt::Ledger => hf::actions::ledger::get().await?,
-            Opt::Pending => hf::actions::pending::get().await?,
-            Opt::Actionable => hf::actions::actionable::get().await?,
-            Opt::Completed => hf::actions::completed::get().await?,
-            Opt::Profile => hf::actions::profile::get().await?,
-            Opt::ReserveSetting => hf::actions::reserve::get_setting().await?,
-            Opt::ReserveSalePrice => hf::actions::reserve::get_sale_price().await?,
-            Opt::GetMySummary => hf::actions::summary::get_my_summary().await?,
+            Opt::Ledger => hf::actions::ledger::get(output).await?,
+            Opt::Pending => hf::actions::pending::get(output).await?,
+            Opt::Actionable => hf::actions::actionable::get(output).await?,
+            Opt::Completed => hf::actions::completed::get(output).await?,
+            Opt::Profile => hf::actions::profile::get(output).await?,
+            Opt::ReserveSetting => hf::actions::reserve::get_setting(output).await?,
+            Opt::ReserveSalePrice => hf::actions::reserve::get_sale_price(output).await?,
+            Opt::GetMySummary => hf::actions::summary::get_my_summary(output).await?,
             Opt::GetAgentSummary { pub_key } => {
                 let pub_key = AgentPubKeyB64::from_b64_str(&pub_key)
                     .expect("Failed to serialize string into AgentPubKey");
-                hf::actions::summary::get_agent_summary(pub_key.into()).await?
+                hf::actions::summary::get_agent_summary(pub_key.into(), output).await?
+            }
+            Opt::Invoice(tx) => hf::actions::create::invoice(tx, output).await?,
+            Opt::Promise { tx, invoice } => {
+                hf::actions::create::promise(tx, invoice, output).await?
             }
+            Opt::Accept { tx_id } => hf::actions::transaction::accept(tx_id, output).await?,
+            Opt::Decline { tx_id } => hf::actions::transaction::decline(tx_id, output).await?,
+            Opt::Cancel { tx_id } => hf::actions::transaction::cancel(tx_id, output).await?,
+            Opt::Complete { tx_id } => hf::actions::transaction::complete(tx_id, output).await?,
+            Opt::Pay(opt) => hf::actions::pay::get(opt, output).await?,
+            Opt::Export(opt) => hf::actions::export::get(opt).await?,
+            Opt::Reconcile => hf::actions::reconcile::get(output).await?,
         }
         Ok(())
     }
@@ -57,6 +113,6 @@ impl Opt {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let opt = Opt::from_args();
-    opt.run().await
+    let cli = Cli::from_args();
+    cli.cmd.run(cli.output).await
 }
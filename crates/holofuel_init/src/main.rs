@@ -1,16 +1,13 @@
 use anyhow::{anyhow, Context, Result};
 use holochain_types::{
     dna::EntryHashB64,
-    prelude::{
-        hash_type::Agent, holochain_serial, FunctionName, HoloHashB64, SerializedBytes, ZomeName,
-    },
+    prelude::{hash_type::Agent, HoloHashB64},
 };
 use hpos_hc_connect::{
-    app_connection::CoreAppRoleName,
     hf_agent::HfAgent,
-    holofuel_types::{Profile, ReserveSettingFile},
+    holofuel_types::{Profile, ProfileInput, ReserveSettingFile},
+    HposEnvironment,
 };
-use serde::{Deserialize, Serialize};
 use std::env;
 use tracing::{debug, info, Level};
 use tracing_subscriber::FmtSubscriber;
@@ -32,13 +29,8 @@ async fn main() -> Result<()> {
 
     info!("Start initializing the holofuel instance");
 
-    let mut agent = HfAgent::spawn(None).await?;
-
-    #[derive(Serialize, Deserialize, Debug, SerializedBytes)]
-    pub struct ProfileInput {
-        pub nickname: Option<String>,
-        pub avatar_url: Option<String>,
-    }
+    let hpos_env = HposEnvironment::from_env();
+    let mut agent = HfAgent::spawn(None, &hpos_env).await?;
 
     let apk = agent.pubkey().await?;
     if let Some(ek) = expect_pubkey() {
@@ -56,39 +48,25 @@ async fn main() -> Result<()> {
     if fpk == apk.clone().into() {
         nickname = Some("Holo Fee Collector".to_string());
     }
-    if ReserveSettingFile::load_happ_file().is_ok() {
+    if ReserveSettingFile::load_happ_file(&hpos_env).is_ok() {
         nickname = Some("HOT Reserve".to_string());
     }
-    let profile: Profile = agent
-        .app
-        .zome_call_typed(
-            CoreAppRoleName::Holofuel.into(),
-            ZomeName::from("profile"),
-            FunctionName::from("get_my_profile"),
-            (),
-        )
-        .await?;
+    let profile: Profile = agent.profile().await?;
     // is a profile name already set you are not allowed to update it
     if profile.nickname.is_none() {
         debug!("Setting nickname as {:?}", nickname);
         let _: EntryHashB64 = agent
-            .app
-            .zome_call_typed(
-                CoreAppRoleName::Holofuel.into(),
-                ZomeName::from("profile"),
-                FunctionName::from("update_my_profile"),
-                ProfileInput {
-                    nickname,
-                    avatar_url: None,
-                },
-            )
+            .update_profile(ProfileInput {
+                nickname,
+                avatar_url: None,
+            })
             .await?;
         info!("Profile name set successfully");
     } else {
         info!("Profile name already set as {:?}", profile.nickname);
     }
     // initialize reserve details
-    reserve_init::set_up_reserve(agent, apk).await?;
+    reserve_init::set_up_reserve(agent, apk, &hpos_env).await?;
     info!("Completed initializing the holofuel instance");
     Ok(())
 }
@@ -1,5 +1,6 @@
-use crate::utils::WsPollRecv;
+use crate::utils::{is_connection_error, reconnect_policy, WsPollRecv};
 
+use super::environment::HposEnvironment;
 use super::holo_config::Happ;
 use super::hpos_agent::Agent;
 use anyhow::{anyhow, Context, Result};
@@ -8,20 +9,22 @@ use holochain_conductor_api::{
     AppInterfaceInfo, AppStatusFilter, IssueAppAuthenticationTokenPayload,
 };
 use holochain_types::{
-    app::{DeleteCloneCellPayload, InstallAppPayload, InstalledAppId},
+    app::{AppManifest, DeleteCloneCellPayload, InstallAppPayload, InstalledAppId},
     dna::AgentPubKey,
+    dna::{CoordinatorManifest, CoordinatorSource, UpdateCoordinatorsPayload},
     prelude::{CellId, SerializedBytes},
     websocket::AllowedOrigins,
 };
 use holochain_websocket::{connect, ConnectRequest, WebsocketConfig, WebsocketSender};
-use std::{collections::HashMap, env, net::ToSocketAddrs, sync::Arc};
-use tracing::{debug, info, instrument, trace};
+use mr_bundle::Bundle;
+use std::{collections::HashMap, net::ToSocketAddrs, sync::Arc};
+use tracing::{debug, info, instrument, trace, warn};
 
-#[allow(dead_code)]
 #[derive(Clone)]
 pub struct AdminWebsocket {
     tx: WebsocketSender,
     rx: Arc<WsPollRecv>,
+    admin_port: u16,
 }
 
 impl AdminWebsocket {
@@ -42,7 +45,25 @@ impl AdminWebsocket {
 
         let rx = WsPollRecv::new::<AdminResponse>(rx).into();
 
-        Ok(Self { tx, rx })
+        Ok(Self { tx, rx, admin_port })
+    }
+
+    /// Port of holochain's admin interface this websocket is connected to
+    pub fn port(&self) -> u16 {
+        self.admin_port
+    }
+
+    /// Re-establishes connection to the admin interface, eg. after conductor restart.
+    /// Retries with exponential backoff until conductor is up again.
+    #[instrument(skip(self), fields(admin_port = self.admin_port), err)]
+    pub async fn reconnect(&mut self) -> Result<()> {
+        warn!("reconnecting to holochain's admin interface");
+        let admin_port = self.admin_port;
+        *self = reconnect_policy()
+            .retry(|| Self::connect(admin_port))
+            .await
+            .context("failed to reconnect to holochain's admin interface")?;
+        Ok(())
     }
 
     /// Attach an interface for app calls. If a port numer is None conductor will choose an available port
@@ -66,7 +87,10 @@ impl AdminWebsocket {
 
     pub async fn list_app_interfaces(&mut self) -> Result<Vec<AppInterfaceInfo>> {
         debug!("listing app interfaces");
-        match self.send(AdminRequest::ListAppInterfaces, None).await? {
+        match self
+            .send_replayable(|| AdminRequest::ListAppInterfaces, None)
+            .await?
+        {
             AdminResponse::AppInterfacesListed(app_interfaces) => Ok(app_interfaces),
             _ => Err(anyhow!("Failed to fetch list of attached app interfaces")),
         }
@@ -74,12 +98,14 @@ impl AdminWebsocket {
 
     pub async fn issue_app_auth_token(&mut self, app_id: String) -> Result<AppAuthenticationToken> {
         debug!("issuing app authentication token for app {:?}", app_id);
-        let msg = AdminRequest::IssueAppAuthenticationToken(IssueAppAuthenticationTokenPayload {
-            installed_app_id: app_id,
-            expiry_seconds: 30,
-            single_use: true,
-        });
-        let response = self.send(msg, None).await?;
+        let msg = || {
+            AdminRequest::IssueAppAuthenticationToken(IssueAppAuthenticationTokenPayload {
+                installed_app_id: app_id.clone(),
+                expiry_seconds: 30,
+                single_use: true,
+            })
+        };
+        let response = self.send_replayable(msg, None).await?;
 
         match response {
             AdminResponse::AppAuthenticationTokenIssued(AppAuthenticationTokenIssued {
@@ -94,9 +120,10 @@ impl AdminWebsocket {
         &mut self,
         status_filter: Option<AppStatusFilter>,
     ) -> Result<Vec<InstalledAppId>> {
-        let response = self
-            .send(AdminRequest::ListApps { status_filter }, None)
-            .await?;
+        let msg = || AdminRequest::ListApps {
+            status_filter: status_filter.clone(),
+        };
+        let response = self.send_replayable(msg, None).await?;
         match response {
             AdminResponse::AppsListed(info) => {
                 Ok(info.iter().map(|i| i.installed_app_id.to_owned()).collect())
@@ -110,30 +137,36 @@ impl AdminWebsocket {
         Ok(enabled)
     }
 
-    #[instrument(skip(self, app, membrane_proofs, agent))]
+    /// Installs `app` from `bundle`, as read by `Happ::bundle` and checked with
+    /// `Happ::verify_dna_hashes`, and enables it. Roles in `existing_cells` are
+    /// provisioned from those cells instead of new ones.
+    #[instrument(skip(self, app, bundle, membrane_proofs, agent, env))]
     pub async fn install_and_activate_app(
         &mut self,
         app: &Happ,
+        bundle: Bundle<AppManifest>,
         membrane_proofs: Option<HashMap<String, Arc<SerializedBytes>>>,
         agent: Agent,
         existing_cells: HashMap<String, CellId>,
+        env: &HposEnvironment,
     ) -> Result<()> {
-        let source = app.source().await?;
+        let roles: Vec<String> = existing_cells.keys().cloned().collect();
+        let source = Happ::source(bundle, &roles)?;
 
-        let agent_key = if let Some(admin) = &app.agent_override_details().await? {
+        let agent_key = if let Some(admin) = &app.agent_override_details(env).await? {
             admin.key.clone()
         } else {
             agent.admin.key.clone()
         };
 
-        let payload = if let Ok(id) = env::var("DEV_UID_OVERRIDE") {
+        let payload = if let Some(id) = &env.dev_uid_override {
             debug!("using network_seed to install: {}", id);
             InstallAppPayload {
                 agent_key: Some(agent_key),
-                installed_app_id: Some(app.id()),
+                installed_app_id: Some(app.id(env)),
                 source,
                 membrane_proofs,
-                network_seed: Some(id),
+                network_seed: Some(id.clone()),
                 ignore_genesis_failure: false,
                 existing_cells,
                 allow_throwaway_random_agent_key: false,
@@ -142,7 +175,7 @@ impl AdminWebsocket {
             debug!("using default network_seed to install");
             InstallAppPayload {
                 agent_key: Some(agent_key),
-                installed_app_id: Some(app.id()),
+                installed_app_id: Some(app.id(env)),
                 source,
                 membrane_proofs,
                 network_seed: None,
@@ -158,8 +191,8 @@ impl AdminWebsocket {
             }
         }
 
-        self.activate_app(app).await?;
-        debug!("installed & activated hApp: {}", app.id());
+        self.activate_app(app, env).await?;
+        debug!("installed & activated hApp: {}", app.id(env));
         Ok(())
     }
 
@@ -169,12 +202,17 @@ impl AdminWebsocket {
         self.send(msg, Some(300)).await // First install takes a while due to compile to WASM step
     }
 
-    #[instrument(skip(self), err)]
-    pub async fn activate_app(&mut self, happ: &Happ) -> Result<AdminResponse> {
-        let msg = AdminRequest::EnableApp {
-            installed_app_id: happ.id(),
+    #[instrument(skip(self, env), err)]
+    pub async fn activate_app(
+        &mut self,
+        happ: &Happ,
+        env: &HposEnvironment,
+    ) -> Result<AdminResponse> {
+        let installed_app_id = happ.id(env);
+        let msg = || AdminRequest::EnableApp {
+            installed_app_id: installed_app_id.clone(),
         };
-        self.send(msg, None).await
+        self.send_replayable(msg, None).await
     }
 
     #[instrument(skip(self), err)]
@@ -192,18 +230,18 @@ impl AdminWebsocket {
 
     #[instrument(skip(self), err)]
     pub async fn enable_app(&mut self, installed_app_id: &str) -> Result<AdminResponse> {
-        let msg = AdminRequest::EnableApp {
+        let msg = || AdminRequest::EnableApp {
             installed_app_id: installed_app_id.to_string(),
         };
-        self.send(msg, None).await
+        self.send_replayable(msg, None).await
     }
 
     #[instrument(skip(self), err)]
     pub async fn disable_app(&mut self, installed_app_id: &str) -> Result<AdminResponse> {
-        let msg = AdminRequest::DisableApp {
+        let msg = || AdminRequest::DisableApp {
             installed_app_id: installed_app_id.to_string(),
         };
-        self.send(msg, None).await
+        self.send_replayable(msg, None).await
     }
 
     #[instrument(skip(self), err)]
@@ -211,9 +249,10 @@ impl AdminWebsocket {
         &mut self,
         status_filter: Option<AppStatusFilter>,
     ) -> Result<Vec<AppInfo>> {
-        let response = self
-            .send(AdminRequest::ListApps { status_filter }, None)
-            .await?;
+        let msg = || AdminRequest::ListApps {
+            status_filter: status_filter.clone(),
+        };
+        let response = self.send_replayable(msg, None).await?;
         match response {
             AdminResponse::AppsListed(apps_infos) => Ok(apps_infos),
             _ => unreachable!("Unexpected response {:?}", response),
@@ -239,20 +278,79 @@ impl AdminWebsocket {
         }
     }
 
+    /// Replaces coordinator zomes of a cell, its integrity zomes and source chain are kept
+    #[instrument(skip(self, coordinators), err)]
+    pub async fn update_coordinators(
+        &mut self,
+        cell_id: CellId,
+        coordinators: Bundle<CoordinatorManifest>,
+    ) -> Result<()> {
+        let payload = UpdateCoordinatorsPayload {
+            cell_id,
+            source: CoordinatorSource::Bundle(Box::new(coordinators)),
+        };
+        let response = self
+            .send(
+                AdminRequest::UpdateCoordinators(Box::new(payload)),
+                Some(300),
+            )
+            .await?;
+        match response {
+            AdminResponse::CoordinatorsUpdated => Ok(()),
+            _ => Err(anyhow!("unexpected response: {:?}", response)),
+        }
+    }
+
+    /// Sends a request to the admin interface. If the conductor closed the connection since
+    /// the last request it is re-established first. A request that fails because the
+    /// connection is gone is not sent again, because it might have been executed already
+    /// and most admin requests, like installing or uninstalling an app, are not idempotent.
     #[instrument(skip(self))]
     pub async fn send(
         &mut self,
         msg: AdminRequest,
         duration: Option<u64>,
+    ) -> Result<AdminResponse> {
+        self.send_inner(msg, None, duration).await
+    }
+
+    /// Like `send`, but a request that fails because the connection is gone is built again
+    /// with `request` and sent once more on a new connection.
+    /// Only for requests which can safely be executed twice.
+    async fn send_replayable(
+        &mut self,
+        request: impl Fn() -> AdminRequest + Sync,
+        duration: Option<u64>,
+    ) -> Result<AdminResponse> {
+        self.send_inner(request(), Some(&request), duration).await
+    }
+
+    async fn send_inner(
+        &mut self,
+        msg: AdminRequest,
+        replay: Option<&(dyn Fn() -> AdminRequest + Sync)>,
+        duration: Option<u64>,
     ) -> Result<AdminResponse> {
         // default timeout is 60 seconds
         let timeout_duration = std::time::Duration::from_secs(duration.unwrap_or(60));
 
-        let response = self
-            .tx
-            .request_timeout(msg, timeout_duration)
-            .await
-            .context("failed to send message")?;
+        // Conductor closed the connection since last request
+        if self.rx.is_closed() {
+            self.reconnect().await?;
+        }
+
+        let response = match (self.tx.request_timeout(msg, timeout_duration).await, replay) {
+            (Ok(response), _) => response,
+            (Err(e), Some(replay)) if is_connection_error(&e) => {
+                warn!(error = ?e, "admin request failed, retrying once on a new connection");
+                self.reconnect().await?;
+                self.tx
+                    .request_timeout(replay(), timeout_duration)
+                    .await
+                    .context("failed to send message")?
+            }
+            (Err(e), _) => return Err(e).context("failed to send message"),
+        };
 
         match response {
             AdminResponse::Error(error) => Err(anyhow!("error: {:?}", error)),
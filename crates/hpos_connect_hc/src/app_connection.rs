@@ -1,6 +1,9 @@
 use crate::{
     admin_ws::AdminWebsocket,
-    utils::{fresh_nonce, WsPollRecv},
+    signals::{
+        signal_stream, ResolvedSignalFilter, SignalFilter, SignalStream, SIGNAL_CHANNEL_CAPACITY,
+    },
+    utils::{fresh_nonce, is_connection_error, reconnect_policy, WsPollRecv},
 };
 use anyhow::{anyhow, Context, Result};
 use core::fmt::Debug;
@@ -10,16 +13,20 @@ use holochain_conductor_api::{
 use holochain_keystore::MetaLairClient;
 use holochain_types::{
     app::{CreateCloneCellPayload, DisableCloneCellPayload, EnableCloneCellPayload},
-    prelude::{CellId, ClonedCell, ExternIO, FunctionName, RoleName, ZomeCallUnsigned, ZomeName},
+    prelude::{
+        CellId, ClonedCell, DnaModifiers, ExternIO, FunctionName, RoleName, ZomeCallUnsigned,
+        ZomeName,
+    },
+    signal::Signal,
 };
 use holochain_websocket::{connect, ConnectRequest, WebsocketConfig, WebsocketSender};
+use lair_keystore_api::dependencies::tokio::sync::broadcast;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, net::ToSocketAddrs, sync::Arc};
-use tracing::{instrument, trace};
+use tracing::{instrument, trace, warn};
 
 type CellInfoMap = HashMap<RoleName, Vec<CellInfo>>;
 
-#[allow(dead_code)]
 #[derive(Clone)]
 pub struct AppWebsocket {
     tx: WebsocketSender,
@@ -32,6 +39,8 @@ pub struct AppConnection {
     pub keystore: MetaLairClient,
     cell_info: Option<CellInfoMap>,
     app_id: String,
+    signals: broadcast::Sender<Signal>,
+    admin_port: u16,
 }
 
 impl AppConnection {
@@ -42,11 +51,31 @@ impl AppConnection {
         keystore: MetaLairClient,
         app_id: String,
     ) -> Result<Self> {
-        match Self::inner_connect(admin_ws, keystore.clone(), app_id.clone(), false).await {
+        // Signal channel outlives a single websocket so that subscribers keep receiving signals
+        // also after reconnecting
+        let (signals, _) = broadcast::channel(SIGNAL_CHANNEL_CAPACITY);
+        Self::connect_with_signals(admin_ws, keystore, app_id, signals).await
+    }
+
+    async fn connect_with_signals(
+        admin_ws: &mut AdminWebsocket,
+        keystore: MetaLairClient,
+        app_id: String,
+        signals: broadcast::Sender<Signal>,
+    ) -> Result<Self> {
+        match Self::inner_connect(
+            admin_ws,
+            keystore.clone(),
+            app_id.clone(),
+            signals.clone(),
+            false,
+        )
+        .await
+        {
             Ok(c) => Ok(c),
             Err(e) => {
                 log::warn!("Failed to connnect to existing app websocket for core happ id: {:?}... creating and connecting to a new one.  Error: {:#?}", app_id, e);
-                Self::inner_connect(admin_ws, keystore, app_id, true).await
+                Self::inner_connect(admin_ws, keystore, app_id, signals, true).await
             }
         }
     }
@@ -54,6 +83,7 @@ impl AppConnection {
         admin_ws: &mut AdminWebsocket,
         keystore: MetaLairClient,
         app_id: String,
+        signals: broadcast::Sender<Signal>,
         force_new_interface: bool,
     ) -> Result<Self> {
         let app_interface = if !force_new_interface {
@@ -91,7 +121,7 @@ impl AppConnection {
         })
         .await?;
 
-        let rx = WsPollRecv::new::<AppResponse>(rx).into();
+        let rx = WsPollRecv::new_with_signals::<AppResponse>(rx, signals.clone()).into();
 
         // Websocket connection needs authentication via token previously obtained from Admin Interface
         tx.authenticate(AppAuthenticationRequest { token })
@@ -103,15 +133,75 @@ impl AppConnection {
             keystore,
             cell_info: None, // cell info is populated lazily
             app_id,
+            signals,
+            admin_port: admin_ws.port(),
         })
     }
 
+    /// Re-establishes connection to the app interface after conductor restart.
+    /// A new auth token is issued via admin interface, app interface is re-attached if it is gone
+    /// and cached cell_info is dropped, because cells might have changed in the meantime.
+    /// Signal subscribers stay subscribed.
+    #[instrument(skip(self), fields(app_id = %self.app_id), err)]
+    pub async fn reconnect(&mut self) -> Result<()> {
+        warn!("reconnecting to holochain's app interface");
+        let admin_port = self.admin_port;
+        let keystore = self.keystore.clone();
+        let app_id = self.app_id.clone();
+        let signals = self.signals.clone();
+        *self = reconnect_policy()
+            .retry(|| {
+                let (keystore, app_id, signals) =
+                    (keystore.clone(), app_id.clone(), signals.clone());
+                async move {
+                    let mut admin_ws = AdminWebsocket::connect(admin_port).await?;
+                    Self::connect_with_signals(&mut admin_ws, keystore, app_id, signals).await
+                }
+            })
+            .await
+            .context("failed to reconnect to holochain's app interface")?;
+        Ok(())
+    }
+
+    /// Subscribes to signals emitted by the connected app.
+    /// Each call returns an independent stream, so any number of subscribers can listen at once.
+    /// Signals are filtered by `filter`, where `role_name` matches provisioned and cloned cells
+    /// of that role known at the time of subscribing.
+    pub async fn subscribe_signals(&mut self, filter: SignalFilter) -> Result<SignalStream> {
+        let mut cells = filter.cell_id.map(|c| vec![c]);
+        if let Some(role_name) = filter.role_name {
+            let info = self.cell_info().await?;
+            let role_cells: Vec<CellId> = info
+                .get(&role_name)
+                .ok_or(anyhow!("unable to find cells for RoleName {}", &role_name))?
+                .iter()
+                .filter_map(|cell_info| match cell_info {
+                    CellInfo::Provisioned(c) => Some(c.cell_id.clone()),
+                    CellInfo::Cloned(c) => Some(c.cell_id.clone()),
+                    _ => None,
+                })
+                .collect();
+            cells = Some(match cells {
+                // both role and cell given: only keep the cell if it belongs to the role
+                Some(c) => c.into_iter().filter(|c| role_cells.contains(c)).collect(),
+                None => role_cells,
+            });
+        }
+
+        Ok(signal_stream(
+            self.signals.subscribe(),
+            ResolvedSignalFilter {
+                cells,
+                zome_name: filter.zome_name,
+            },
+        ))
+    }
+
     /// Return app info for a connected app
     /// Returns an error if there is no app info
     #[instrument(skip(self))]
     pub async fn app_info(&mut self) -> Result<AppInfo> {
-        let msg = AppRequest::AppInfo;
-        let response = self.send(msg).await?;
+        let response = self.send_replayable(|| AppRequest::AppInfo).await?;
         trace!(
             "app_info response for app_id{}: {:?}",
             self.app_id,
@@ -134,6 +224,12 @@ impl AppConnection {
         Ok(self.cell_info.clone().unwrap())
     }
 
+    /// Drops cached cell_info, so that it is fetched again on next use,
+    /// eg. after clones were deleted over the admin interface
+    pub fn invalidate_cell_info(&mut self) {
+        self.cell_info = None;
+    }
+
     /// Returns a cell for a given RoleName in a connected app
     pub async fn cell(&mut self, role_name: RoleName) -> Result<CellId> {
         let info = &self.cell_info().await?;
@@ -146,6 +242,19 @@ impl AppConnection {
         }
     }
 
+    /// Returns dna modifiers of the provisioned cell for a given RoleName in a connected app
+    pub async fn dna_modifiers(&mut self, role_name: RoleName) -> Result<DnaModifiers> {
+        let info = &self.cell_info().await?;
+        info.get(&role_name)
+            .into_iter()
+            .flatten()
+            .find_map(|cell_info| match cell_info {
+                CellInfo::Provisioned(c) => Some(c.dna_modifiers.clone()),
+                _ => None,
+            })
+            .ok_or(anyhow!("unable to find cell for RoleName {}", &role_name))
+    }
+
     /// Returns all cloned cells for a given RoleName in a connected app
     pub async fn cloned_cells(&mut self, role_name: RoleName) -> Result<Vec<ClonedCell>> {
         let info = &self.cell_info().await?;
@@ -191,37 +300,48 @@ impl AppConnection {
         let app_request = AppRequest::CreateCloneCell(Box::new(payload.clone()));
         let response = self.send(app_request).await?;
         match response {
-            AppResponse::CloneCellCreated(cell) => Ok(cell),
+            AppResponse::CloneCellCreated(cell) => {
+                self.invalidate_cell_info();
+                Ok(cell)
+            }
             _ => Err(anyhow!("Error creating clone {:?}", payload)),
         }
     }
 
     /// Disables a clone cell
     pub async fn disable_clone(&mut self, payload: DisableCloneCellPayload) -> Result<()> {
-        let app_request = AppRequest::DisableCloneCell(Box::new(payload.clone()));
-        let response = self.send(app_request).await?;
+        let app_request = || AppRequest::DisableCloneCell(Box::new(payload.clone()));
+        let response = self.send_replayable(app_request).await?;
         match response {
-            AppResponse::CloneCellDisabled => Ok(()),
+            AppResponse::CloneCellDisabled => {
+                self.invalidate_cell_info();
+                Ok(())
+            }
             _ => Err(anyhow!("Error disabling clone {:?}", payload)),
         }
     }
 
     /// Enable a clone cell
     pub async fn enable_clone(&mut self, payload: EnableCloneCellPayload) -> Result<ClonedCell> {
-        let app_request = AppRequest::EnableCloneCell(Box::new(payload.clone()));
-        let response = self.send(app_request).await?;
+        let app_request = || AppRequest::EnableCloneCell(Box::new(payload.clone()));
+        let response = self.send_replayable(app_request).await?;
         match response {
-            AppResponse::CloneCellEnabled(cloned_cell) => Ok(cloned_cell),
+            AppResponse::CloneCellEnabled(cloned_cell) => {
+                self.invalidate_cell_info();
+                Ok(cloned_cell)
+            }
             _ => Err(anyhow!("Error enabling clone {:?}", payload)),
         }
     }
 
     /// Raw zome call function taking holochain_conductor_api::app_interface::ZomeCall as an argument
-    /// and returning AppResponse without checking an outcomeor deserializing
+    /// and returning AppResponse without checking an outcomeor deserializing.
+    /// A zome call lost with the connection is sent again unchanged, that is safe because
+    /// conductor rejects a signed call whose nonce it has already seen.
     #[instrument(skip(self))]
     pub async fn zome_call(&mut self, msg: ZomeCall) -> Result<AppResponse> {
-        let app_request = AppRequest::CallZome(Box::new(msg));
-        self.send(app_request).await
+        self.send_replayable(|| AppRequest::CallZome(Box::new(msg.clone())))
+            .await
     }
 
     /// Return app id
@@ -327,15 +447,47 @@ impl AppConnection {
     //     Ok(agent_pubkey.sign_raw(&self.keystore, data).await?)
     // }
 
-    /// Low level internal websocket function
+    /// Low level internal websocket function. If the conductor closed the connection since
+    /// the last request it is re-established first. A request that fails because the
+    /// connection is gone is not sent again, because it might have been executed already.
     #[instrument(skip(self))]
     async fn send(&mut self, msg: AppRequest) -> Result<AppResponse> {
-        let response = self
-            .ws
-            .tx
-            .request(msg)
-            .await
-            .context("failed to send message")?;
+        self.send_inner(msg, None).await
+    }
+
+    /// Like `send`, but a request that fails because the connection is gone is built again
+    /// with `request` and sent once more on a new connection.
+    /// Only for requests which can safely be executed twice.
+    async fn send_replayable(
+        &mut self,
+        request: impl Fn() -> AppRequest + Sync,
+    ) -> Result<AppResponse> {
+        self.send_inner(request(), Some(&request)).await
+    }
+
+    async fn send_inner(
+        &mut self,
+        msg: AppRequest,
+        replay: Option<&(dyn Fn() -> AppRequest + Sync)>,
+    ) -> Result<AppResponse> {
+        // Conductor closed the connection since last request
+        if self.ws.rx.is_closed() {
+            self.reconnect().await?;
+        }
+
+        let response = match (self.ws.tx.request(msg).await, replay) {
+            (Ok(response), _) => response,
+            (Err(e), Some(replay)) if is_connection_error(&e) => {
+                warn!(error = ?e, "app request failed, retrying once on a new connection");
+                self.reconnect().await?;
+                self.ws
+                    .tx
+                    .request(replay())
+                    .await
+                    .context("failed to send message")?
+            }
+            (Err(e), _) => return Err(e).context("failed to send message"),
+        };
         match response {
             AppResponse::Error(error) => Err(anyhow!("error: {:?}", error)),
             _ => {
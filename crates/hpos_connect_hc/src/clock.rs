@@ -0,0 +1,168 @@
+//! Source of the current time for service-logger scheduling.
+//!
+//! `sl_utils` and `SlCloneManager` read time only through a `Clock`, so schedulers can be tested
+//! at any moment with `FixedClock` or moved through time with `SimulatedClock` instead of
+//! waiting for bucket boundaries. Local wall-clock time is part of the clock, because deleting
+//! windows are defined around local midnight. Test clocks are generic over the `TimeZone` local
+//! time is in, so with a `chrono_tz::Tz` they follow DST changes like the holoport does.
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
+use std::sync::{Arc, Mutex};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wall-clock time of the holoport
+    fn now_local(&self) -> NaiveDateTime;
+}
+
+/// Reads the time of the system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn now_local(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// Always returns the same time, local time is that time in time zone `Tz`, UTC by default
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock<Tz: TimeZone = Utc> {
+    now: DateTime<Utc>,
+    time_zone: Tz,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now,
+            time_zone: Utc,
+        }
+    }
+}
+
+impl<Tz: TimeZone> FixedClock<Tz> {
+    /// Same clock with local time in `time_zone`, e.g. a `FixedOffset` or a `chrono_tz::Tz`
+    /// to follow DST changes
+    pub fn in_time_zone<Tz2: TimeZone>(self, time_zone: Tz2) -> FixedClock<Tz2> {
+        FixedClock {
+            now: self.now,
+            time_zone,
+        }
+    }
+}
+
+impl<Tz: TimeZone + Send + Sync> Clock for FixedClock<Tz> {
+    fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+
+    fn now_local(&self) -> NaiveDateTime {
+        self.now.with_timezone(&self.time_zone).naive_local()
+    }
+}
+
+/// Clock that only moves when told to, clones share the same time
+#[derive(Debug, Clone)]
+pub struct SimulatedClock<Tz: TimeZone = Utc> {
+    inner: Arc<Mutex<FixedClock<Tz>>>,
+}
+
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self::from(FixedClock::new(start))
+    }
+}
+
+impl<Tz: TimeZone> SimulatedClock<Tz> {
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.lock().now = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.lock().now += by;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FixedClock<Tz>> {
+        self.inner.lock().expect("simulated clock lock poisoned")
+    }
+}
+
+impl<Tz: TimeZone> From<FixedClock<Tz>> for SimulatedClock<Tz> {
+    fn from(clock: FixedClock<Tz>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(clock)),
+        }
+    }
+}
+
+impl<Tz: TimeZone + Send + Sync> Clock for SimulatedClock<Tz> {
+    fn now(&self) -> DateTime<Utc> {
+        self.lock().now()
+    }
+
+    fn now_local(&self) -> NaiveDateTime {
+        self.lock().now_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sl_utils::{
+        sl_get_current_time_bucket, sl_within_deleting_check_window,
+        sl_within_min_of_next_time_bucket,
+    };
+    use chrono::FixedOffset;
+    use chrono_tz::Europe::Berlin;
+
+    #[test]
+    fn sl_scheduling_follows_the_clock() {
+        // 2024 has 366 days, so bucket 26 of 14 days runs from 2024-12-30 to 2025-01-12
+        let clock = SimulatedClock::new(Utc.with_ymd_and_hms(2024, 12, 31, 23, 55, 0).unwrap());
+        assert_eq!(sl_get_current_time_bucket(&clock, 14), 26);
+        assert!(!sl_within_min_of_next_time_bucket(&clock, 14, 9));
+
+        clock.set(Utc.with_ymd_and_hms(2025, 1, 12, 23, 55, 0).unwrap());
+        assert!(sl_within_min_of_next_time_bucket(&clock, 14, 9));
+        clock.advance(Duration::minutes(10));
+        assert_eq!(sl_get_current_time_bucket(&clock, 14), 27);
+
+        // deleting window is around local midnight
+        let utc_midnight = Utc.with_ymd_and_hms(2025, 1, 13, 0, 5, 0).unwrap();
+        assert!(sl_within_deleting_check_window(
+            &FixedClock::new(utc_midnight),
+            10
+        ));
+        let new_york =
+            FixedClock::new(utc_midnight).in_time_zone(FixedOffset::west_opt(5 * 3600).unwrap());
+        assert!(!sl_within_deleting_check_window(&new_york, 10));
+    }
+
+    #[test]
+    fn deleting_window_follows_dst_changes() {
+        // Berlin is UTC+1 until 2024-03-31 02:00, UTC+2 after
+        let clock = SimulatedClock::from(
+            FixedClock::new(Utc.with_ymd_and_hms(2024, 3, 30, 23, 5, 0).unwrap())
+                .in_time_zone(Berlin),
+        );
+        assert!(sl_within_deleting_check_window(&clock, 10));
+
+        // a day later local midnight is an hour earlier in UTC
+        clock.advance(Duration::days(1));
+        assert!(!sl_within_deleting_check_window(&clock, 10));
+        clock.set(Utc.with_ymd_and_hms(2024, 3, 31, 22, 5, 0).unwrap());
+        assert!(sl_within_deleting_check_window(&clock, 10));
+
+        // and back on 2024-10-27
+        clock.set(Utc.with_ymd_and_hms(2024, 10, 27, 22, 5, 0).unwrap());
+        assert!(!sl_within_deleting_check_window(&clock, 10));
+        clock.advance(Duration::hours(1));
+        assert!(sl_within_deleting_check_window(&clock, 10));
+    }
+}
@@ -0,0 +1,391 @@
+//! Persistent cache of downloaded bundles and UI archives.
+//!
+//! Entries are keyed by URL and revalidated with `If-None-Match` against the ETag of the
+//! previous download, so unchanged artifacts are not transferred again. When the expected
+//! sha256 of a download is known, an entry with that content is used without any request,
+//! whatever URL it came from, and a download with other content is rejected. Each entry
+//! records the sha256 and size of its file when it is stored. The digest is trusted from
+//! then on, an entry whose file went missing or changed size is dropped. When the cache
+//! grows over its size limit least recently used entries are evicted.
+//!
+//! The cache is configured by `HposEnvironment::download_cache`.
+
+use crate::utils::sha256_file;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+use tracing::{debug, instrument, warn};
+use url::Url;
+
+pub const DEFAULT_MAX_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+const META_FILE: &str = "meta.json";
+
+/// Metadata stored next to a cached file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    file_name: String,
+    etag: Option<String>,
+    sha256: String,
+    size: u64,
+    /// Seconds since unix epoch
+    last_used: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl DownloadCache {
+    /// Opens the cache in `dir`, removing temp files left behind by interrupted downloads
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).context(format!("failed to create {}", dir.display()))?;
+        for file in fs::read_dir(&dir)?.flatten() {
+            if file.file_name().to_string_lossy().starts_with(".tmp") {
+                let _ = fs::remove_file(file.path());
+            }
+        }
+        Ok(DownloadCache { dir, max_bytes })
+    }
+
+    /// Returns the path of a cached copy of `url`, downloading it only if it changed.
+    /// With the hex `sha256` of the content a cached copy from any URL will do, and
+    /// fails if `url` has other content.
+    #[instrument(err, skip(self, url), fields(url = %url))]
+    pub fn fetch(&self, url: &Url, sha256: Option<&str>) -> Result<PathBuf> {
+        use isahc::config::{Configurable, RedirectPolicy};
+        use isahc::http::StatusCode;
+        use isahc::prelude::*;
+        use isahc::{HttpClient, Request};
+
+        if let Some(entry) = sha256.and_then(|sha256| self.lookup_content(sha256)) {
+            debug!("content found in cache, downloaded from {}", entry.url);
+            return self.touch(entry);
+        }
+
+        let cached = self.lookup(url.as_str());
+
+        let client = HttpClient::builder()
+            .redirect_policy(RedirectPolicy::Follow)
+            .build()
+            .context("failed to initiate download request")?;
+        let mut request = Request::get(url.as_str());
+        if let Some(etag) = cached.as_ref().and_then(|entry| entry.etag.as_ref()) {
+            request = request.header("If-None-Match", etag);
+        }
+        let mut response = client
+            .send(request.body(())?)
+            .context("failed to send GET request")?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(entry) = cached {
+                debug!("not modified, using cached copy");
+                check_sha256(url, sha256, &entry.sha256)?;
+                return self.touch(entry);
+            }
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "response status code {} indicated failure",
+                response.status().as_str()
+            ));
+        }
+
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let mut file = NamedTempFile::new_in(&self.dir).context("failed to create temp file")?;
+        response
+            .copy_to(file.as_file_mut())
+            .context("failed to write response to file")?;
+        debug!("download successful");
+        let actual = sha256_file(file.path())?;
+        check_sha256(url, sha256, &actual)?;
+
+        let file_name = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|name| !name.is_empty())
+            .context("failed to get basename from url")?;
+        self.store(url.as_str(), file_name, etag, file, actual)
+    }
+
+    /// Moves a downloaded file into the cache as the entry of `url` and evicts
+    /// least recently used entries beyond the size limit
+    fn store(
+        &self,
+        url: &str,
+        file_name: &str,
+        etag: Option<String>,
+        file: NamedTempFile,
+        sha256: String,
+    ) -> Result<PathBuf> {
+        let entry_dir = self.entry_dir(url);
+        let _ = fs::remove_dir_all(&entry_dir);
+        fs::create_dir_all(&entry_dir)?;
+
+        let path = entry_dir.join(file_name);
+        let entry = Entry {
+            url: url.to_string(),
+            file_name: file_name.to_string(),
+            etag,
+            sha256,
+            size: file.as_file().metadata()?.len(),
+            last_used: now(),
+        };
+        file.persist(&path)
+            .context("failed to move download into cache")?;
+        fs::write(entry_dir.join(META_FILE), serde_json::to_vec(&entry)?)?;
+
+        self.evict(url)?;
+        Ok(path)
+    }
+
+    /// Entry of `url` if its file is still there with the size it was stored with
+    fn lookup(&self, url: &str) -> Option<Entry> {
+        let entry_dir = self.entry_dir(url);
+        let entry: Entry =
+            serde_json::from_slice(&fs::read(entry_dir.join(META_FILE)).ok()?).ok()?;
+        match fs::metadata(entry_dir.join(&entry.file_name)) {
+            Ok(file) if file.len() == entry.size && entry.url == url => Some(entry),
+            _ => {
+                warn!("dropping corrupted cache entry for {}", url);
+                let _ = fs::remove_dir_all(entry_dir);
+                None
+            }
+        }
+    }
+
+    /// Intact entry whose file has given hex sha256
+    fn lookup_content(&self, sha256: &str) -> Option<Entry> {
+        fs::read_dir(&self.dir)
+            .ok()?
+            .flatten()
+            .filter_map(|dir| {
+                let meta = fs::read(dir.path().join(META_FILE)).ok()?;
+                serde_json::from_slice::<Entry>(&meta).ok()
+            })
+            .filter(|entry| entry.sha256.eq_ignore_ascii_case(sha256.trim()))
+            .find_map(|entry| self.lookup(&entry.url))
+    }
+
+    fn touch(&self, mut entry: Entry) -> Result<PathBuf> {
+        let entry_dir = self.entry_dir(&entry.url);
+        entry.last_used = now();
+        fs::write(entry_dir.join(META_FILE), serde_json::to_vec(&entry)?)?;
+        Ok(entry_dir.join(entry.file_name))
+    }
+
+    /// Removes least recently used entries until the cache fits its size limit,
+    /// the entry of `keep_url` is never removed
+    fn evict(&self, keep_url: &str) -> Result<()> {
+        let mut entries: Vec<(PathBuf, Entry)> = fs::read_dir(&self.dir)?
+            .flatten()
+            .filter_map(|dir| {
+                let meta = fs::read(dir.path().join(META_FILE)).ok()?;
+                Some((dir.path(), serde_json::from_slice(&meta).ok()?))
+            })
+            .collect();
+        let mut total: u64 = entries.iter().map(|(_, entry)| entry.size).sum();
+        entries.sort_by_key(|(_, entry)| entry.last_used);
+
+        for (dir, entry) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if entry.url == keep_url {
+                continue;
+            }
+            debug!("evicting {} from download cache", entry.url);
+            fs::remove_dir_all(&dir)?;
+            total -= entry.size;
+        }
+        Ok(())
+    }
+
+    fn entry_dir(&self, url: &str) -> PathBuf {
+        use sha2::{Digest, Sha256};
+        self.dir
+            .join(format!("{:x}", Sha256::digest(url.as_bytes())))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// Fails if `actual` hex sha256 of the content of `url` is not the `expected` one
+fn check_sha256(url: &Url, expected: Option<&str>, actual: &str) -> Result<()> {
+    match expected {
+        Some(expected) if !actual.eq_ignore_ascii_case(expected.trim()) => Err(anyhow!(
+            "sha256 mismatch for {}: expected {}, got {}",
+            url,
+            expected,
+            actual
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    fn download(cache: &DownloadCache, url: &str, bytes: &[u8]) -> PathBuf {
+        let mut file = NamedTempFile::new_in(cache.dir()).unwrap();
+        file.write_all(bytes).unwrap();
+        let name = url.rsplit('/').next().unwrap();
+        let sha256 = sha256_file(file.path()).unwrap();
+        cache
+            .store(url, name, Some("\"v1\"".into()), file, sha256)
+            .unwrap()
+    }
+
+    #[test]
+    fn reuses_and_evicts_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path(), 10).unwrap();
+
+        let a = download(&cache, "https://example.com/a.happ", b"aaaa");
+        let entry = cache.lookup("https://example.com/a.happ").unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(fs::read(&a).unwrap(), b"aaaa");
+
+        // make `a` the oldest entry
+        let mut old = entry;
+        old.last_used = 0;
+        fs::write(
+            cache.entry_dir(&old.url).join(META_FILE),
+            serde_json::to_vec(&old).unwrap(),
+        )
+        .unwrap();
+
+        download(&cache, "https://example.com/b.happ", b"bbbb");
+        assert!(cache.lookup("https://example.com/a.happ").is_some());
+        // 12 bytes are over the limit, so least recently used `a` goes
+        download(&cache, "https://example.com/c.happ", b"cccc");
+        assert!(cache.lookup("https://example.com/a.happ").is_none());
+        assert!(cache.lookup("https://example.com/b.happ").is_some());
+        assert!(cache.lookup("https://example.com/c.happ").is_some());
+    }
+
+    #[test]
+    fn drops_corrupted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path(), DEFAULT_MAX_BYTES).unwrap();
+
+        let truncated = download(&cache, "https://example.com/ui.zip", b"zip");
+        fs::write(&truncated, b"z").unwrap();
+        assert!(cache.lookup("https://example.com/ui.zip").is_none());
+        assert!(!truncated.exists());
+
+        let missing = download(&cache, "https://example.com/chat.happ", b"happ");
+        fs::remove_file(&missing).unwrap();
+        assert!(cache.lookup("https://example.com/chat.happ").is_none());
+        assert!(!cache.entry_dir("https://example.com/chat.happ").exists());
+    }
+
+    /// Serves `body` with ETag `"v1"` to `requests` requests, answering 304 to the ones
+    /// revalidating that ETag. Returns the `If-None-Match` header of every request.
+    fn serve(body: &'static [u8], requests: usize) -> (Url, JoinHandle<Vec<Option<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/a.happ", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || {
+            let mut received = vec![];
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut if_none_match = None;
+                for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("if-none-match") {
+                            if_none_match = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                let mut response = if if_none_match.as_deref() == Some("\"v1\"") {
+                    b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes()
+                };
+                if if_none_match.is_none() {
+                    response.extend_from_slice(body);
+                }
+                stream.write_all(&response).unwrap();
+                received.push(if_none_match);
+            }
+            received
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn revalidates_cached_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path(), DEFAULT_MAX_BYTES).unwrap();
+        let (url, server) = serve(b"bundle", 2);
+
+        let downloaded = cache.fetch(&url, None).unwrap();
+        let revalidated = cache.fetch(&url, None).unwrap();
+
+        assert_eq!(downloaded, revalidated);
+        assert_eq!(fs::read(&revalidated).unwrap(), b"bundle");
+        assert_eq!(
+            server.join().unwrap(),
+            vec![None, Some("\"v1\"".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_downloads_with_other_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path(), DEFAULT_MAX_BYTES).unwrap();
+        let (url, server) = serve(b"bundle", 1);
+
+        let err = cache.fetch(&url, Some(&"0".repeat(64))).unwrap_err();
+        assert!(err.to_string().contains("sha256 mismatch"), "{:#}", err);
+        server.join().unwrap();
+        assert!(cache.lookup(url.as_str()).is_none());
+    }
+
+    #[test]
+    fn finds_entries_by_content_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path(), DEFAULT_MAX_BYTES).unwrap();
+        let path = download(&cache, "https://example.com/v1/chat.happ", b"chat");
+        let sha256 = sha256_file(&path).unwrap().to_uppercase();
+
+        // nothing listens on port 9, so this fails if a request is made
+        let url = "http://127.0.0.1:9/v2/chat.happ".parse().unwrap();
+        assert_eq!(cache.fetch(&url, Some(&sha256)).unwrap(), path);
+        assert!(cache.fetch(&url, None).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Runtime configuration of the hpos agents, membrane proof handling and hApp installation.
+//!
+//! `HposEnvironment` is passed explicitly to everything that used to read env vars on its own,
+//! so differently configured agents can live in one process. It can be read from env vars with
+//! `from_env`, from a TOML or YAML file with `load`, or written out in code:
+//!
+//! ```ignore
+//! let env = HposEnvironment {
+//!     hpos_config_path: Some("./hp-primary.json".into()),
+//!     device_seed_default_password: Some("pass".to_string()),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! In a file the fields use their snake_case names, e.g. `mem_proof_server_url`.
+//!
+//! ### Environment vars
+//! ```
+//! // HPOS_CONFIG_PATH=<path to hpos-config json>
+//! // HOST_PUBKEY_PATH=<file the host's agent key is saved to>
+//! // FORCE_RANDOM_AGENT_KEY=<"1" to use a random agent key instead of the one of hpos-config>
+//! // MEM_PROOF_PATH=<file the membrane proof is cached in>
+//! // READ_ONLY_MEM_PROOF=<"true" to install core-app with a read-only membrane proof>
+//! // MEM_PROOF_SERVER_URL=<url of the membrane proof server>
+//! // HOLOFUEL_INSTANCE_ROLE=<role registered with the membrane proof server>
+//! // DEVICE_SEED_DEFAULT_PASSWORD=<password of the device seed bundle>
+//! // HOLOCHAIN_DEFAULT_PASSWORD=<password to unlock lair>
+//! // DEV_UID_OVERRIDE=<network seed appended to app ids and used to install hApps>
+//! // LAIR_CONNECTION_URL=<url of lair keystore>
+//! // LAIR_WORKING_DIR=<lair working dir, read when LAIR_CONNECTION_URL is not set>
+//! // CORE_HAPP_FILE=<happs file of configure-holochain>
+//! // TEST_HOLOFUEL_ID=<installed app id HfAgent connects to instead of holofuel's>
+//! // HBS_URL=<url of holo backend services>
+//! // REGISTER_RESERVE=<json file with reserve settings>
+//! // DOWNLOAD_CACHE_DIR=<directory of the download cache> *OPTIONAL, defaults to hpos-download-cache in the temp dir*
+//! // DOWNLOAD_CACHE_MAX_BYTES=<size limit of the download cache> *OPTIONAL, defaults to 2 GiB*
+//! // IS_INTEGRATION_TEST=<"TRUE" to skip updating the host's jurisdiction from HBS>
+//! ```
+
+use crate::download_cache::{DownloadCache, DEFAULT_MAX_BYTES};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HposEnvironment {
+    pub hpos_config_path: Option<PathBuf>,
+    pub host_pubkey_path: Option<PathBuf>,
+    pub force_random_agent_key: bool,
+    pub mem_proof_path: Option<PathBuf>,
+    pub read_only_mem_proof: bool,
+    pub mem_proof_server_url: Option<String>,
+    pub holofuel_instance_role: Option<String>,
+    pub device_seed_default_password: Option<String>,
+    pub holochain_default_password: Option<String>,
+    pub dev_uid_override: Option<String>,
+    pub lair_connection_url: Option<String>,
+    pub lair_working_dir: Option<PathBuf>,
+    pub core_happ_file: Option<PathBuf>,
+    pub test_holofuel_id: Option<String>,
+    pub hbs_url: Option<String>,
+    pub register_reserve: Option<PathBuf>,
+    pub download_cache_dir: Option<PathBuf>,
+    pub download_cache_max_bytes: Option<u64>,
+    /// Don't update the host's jurisdiction in HHA from HBS, for tests without HBS access
+    pub skip_jurisdiction_update: bool,
+}
+
+impl HposEnvironment {
+    /// Reads the environment from env vars, unset vars are left empty
+    pub fn from_env() -> Self {
+        let var = |name| env::var(name).ok();
+        HposEnvironment {
+            hpos_config_path: var("HPOS_CONFIG_PATH").map(PathBuf::from),
+            host_pubkey_path: var("HOST_PUBKEY_PATH").map(PathBuf::from),
+            force_random_agent_key: var("FORCE_RANDOM_AGENT_KEY").as_deref() == Some("1"),
+            mem_proof_path: var("MEM_PROOF_PATH").map(PathBuf::from),
+            read_only_mem_proof: var("READ_ONLY_MEM_PROOF").as_deref() == Some("true"),
+            mem_proof_server_url: var("MEM_PROOF_SERVER_URL"),
+            holofuel_instance_role: var("HOLOFUEL_INSTANCE_ROLE"),
+            device_seed_default_password: var("DEVICE_SEED_DEFAULT_PASSWORD"),
+            holochain_default_password: var("HOLOCHAIN_DEFAULT_PASSWORD"),
+            dev_uid_override: var("DEV_UID_OVERRIDE"),
+            lair_connection_url: var("LAIR_CONNECTION_URL"),
+            lair_working_dir: var("LAIR_WORKING_DIR").map(PathBuf::from),
+            core_happ_file: var("CORE_HAPP_FILE").map(PathBuf::from),
+            test_holofuel_id: var("TEST_HOLOFUEL_ID"),
+            hbs_url: var("HBS_URL"),
+            register_reserve: var("REGISTER_RESERVE").map(PathBuf::from),
+            download_cache_dir: var("DOWNLOAD_CACHE_DIR").map(PathBuf::from),
+            download_cache_max_bytes: var("DOWNLOAD_CACHE_MAX_BYTES")
+                .and_then(|max| max.parse().ok()),
+            skip_jurisdiction_update: var("IS_INTEGRATION_TEST").as_deref() == Some("TRUE"),
+        }
+    }
+
+    /// Reads the environment from a `.toml`, `.yaml` or `.yml` file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).context(format!(
+            "failed to read environment file {}",
+            path.display()
+        ))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content).context("failed to parse environment TOML"),
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&content).context("failed to parse environment YAML")
+            }
+            _ => Err(anyhow!(
+                "environment file {} is neither .toml nor .yaml",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn hpos_config_path(&self) -> Result<&Path> {
+        required(&self.hpos_config_path, "HPOS_CONFIG_PATH").map(PathBuf::as_path)
+    }
+
+    pub fn host_pubkey_path(&self) -> Result<&Path> {
+        required(&self.host_pubkey_path, "HOST_PUBKEY_PATH").map(PathBuf::as_path)
+    }
+
+    pub fn mem_proof_path(&self) -> Result<&Path> {
+        required(&self.mem_proof_path, "MEM_PROOF_PATH").map(PathBuf::as_path)
+    }
+
+    pub fn mem_proof_server_url(&self) -> Result<&str> {
+        required(&self.mem_proof_server_url, "MEM_PROOF_SERVER_URL").map(String::as_str)
+    }
+
+    pub fn holofuel_instance_role(&self) -> Result<&str> {
+        required(&self.holofuel_instance_role, "HOLOFUEL_INSTANCE_ROLE").map(String::as_str)
+    }
+
+    pub fn device_seed_default_password(&self) -> Result<&str> {
+        required(
+            &self.device_seed_default_password,
+            "DEVICE_SEED_DEFAULT_PASSWORD",
+        )
+        .map(String::as_str)
+    }
+
+    pub fn holochain_default_password(&self) -> Result<&str> {
+        required(
+            &self.holochain_default_password,
+            "HOLOCHAIN_DEFAULT_PASSWORD",
+        )
+        .map(String::as_str)
+    }
+
+    pub fn lair_working_dir(&self) -> Result<&Path> {
+        required(&self.lair_working_dir, "LAIR_WORKING_DIR").map(PathBuf::as_path)
+    }
+
+    pub fn hbs_url(&self) -> Result<&str> {
+        required(&self.hbs_url, "HBS_URL").map(String::as_str)
+    }
+
+    pub fn register_reserve(&self) -> Result<&Path> {
+        required(&self.register_reserve, "REGISTER_RESERVE").map(PathBuf::as_path)
+    }
+
+    /// Opens the download cache in `download_cache_dir`, limited to `download_cache_max_bytes`
+    pub fn download_cache(&self) -> Result<DownloadCache> {
+        let dir = self
+            .download_cache_dir
+            .clone()
+            .unwrap_or_else(|| env::temp_dir().join("hpos-download-cache"));
+        DownloadCache::new(
+            dir,
+            self.download_cache_max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        )
+    }
+}
+
+fn required<'a, T>(value: &'a Option<T>, var: &str) -> Result<&'a T> {
+    value.as_ref().ok_or_else(|| {
+        anyhow!(
+            "Failed to read {}. Is it set in env or the environment file?",
+            var
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_toml_and_yaml_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml_path = dir.path().join("hpos.toml");
+        std::fs::write(
+            &toml_path,
+            "hpos_config_path = \"/etc/hpos-config.json\"\nforce_random_agent_key = true\n",
+        )
+        .unwrap();
+        let yaml_path = dir.path().join("hpos.yaml");
+        std::fs::write(
+            &yaml_path,
+            "hpos_config_path: /etc/hpos-config.json\nforce_random_agent_key: true\n",
+        )
+        .unwrap();
+
+        let expected = HposEnvironment {
+            hpos_config_path: Some("/etc/hpos-config.json".into()),
+            force_random_agent_key: true,
+            ..Default::default()
+        };
+        assert_eq!(HposEnvironment::load(&toml_path).unwrap(), expected);
+        assert_eq!(HposEnvironment::load(&yaml_path).unwrap(), expected);
+        assert_eq!(
+            expected.hpos_config_path().unwrap(),
+            Path::new("/etc/hpos-config.json")
+        );
+
+        let err = expected.mem_proof_path().unwrap_err();
+        assert!(err.to_string().contains("MEM_PROOF_PATH"));
+
+        std::fs::write(&yaml_path, "mem_proof_pth: /tmp/proof\n").unwrap();
+        assert!(HposEnvironment::load(&yaml_path).is_err());
+    }
+}
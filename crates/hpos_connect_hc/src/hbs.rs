@@ -0,0 +1,224 @@
+//! Client of Holo backend services (HBS).
+//!
+//! Requests are signed with `HostKeys` and time out after `DEFAULT_TIMEOUT`. While HBS is unavailable
+//! (5xx responses, Cloudflare's `error code: 504` body, timeouts and connection failures) they are
+//! retried with exponential backoff, every other failure is returned right away as an `HbsError`.
+
+use crate::holo_config::Config;
+use crate::host_keys::HostKeys;
+use crate::HposEnvironment;
+use anyhow::Result;
+use holochain_types::prelude::{ExternIO, Timestamp};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use tracing::{debug, trace, warn};
+
+pub use reqwest::StatusCode;
+
+pub const HOLO_CLIENT_PATH: &str = "/auth/api/v1/holo-client";
+
+/// Time a single request to HBS may take
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Backoff used for requests to HBS unless configured with `HbsClient::with_retry_policy`
+pub fn default_retry_policy() -> again::RetryPolicy {
+    again::RetryPolicy::exponential(Duration::from_millis(500))
+        .with_max_delay(Duration::from_secs(10))
+        .with_max_retries(4)
+        .with_jitter(true)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HbsError {
+    #[error("HBS responded with {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("HBS request timed out")]
+    Timeout,
+    #[error("Failed to reach HBS: {0}")]
+    Request(#[source] reqwest::Error),
+    #[error("Failed to sign HBS request: {0:#}")]
+    Signature(anyhow::Error),
+    #[error("Failed to parse HBS response: {source}, body: {body}")]
+    Parse {
+        #[source]
+        source: serde_json::Error,
+        body: String,
+    },
+}
+
+impl HbsError {
+    /// True if HBS may answer the same request once it is available again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            HbsError::Status { status, .. } => status.is_server_error(),
+            HbsError::Timeout | HbsError::Request(_) => true,
+            HbsError::Signature(_) | HbsError::Parse { .. } => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for HbsError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            HbsError::Timeout
+        } else {
+            HbsError::Request(e)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationRecord {
+    pub id: String,
+    pub email: String,
+    pub access_token: String,
+    pub permissions: Vec<String>,
+    pub kyc: String,
+    pub jurisdiction: String,
+    pub public_key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HoloClientPayload {
+    pub email: String,
+    pub timestamp: u64,
+    pub pub_key: String,
+}
+
+#[derive(Clone)]
+pub struct HbsClient {
+    hbs_url: String,
+    keys: HostKeys,
+    client: Client,
+    timeout: Duration,
+    retry_policy: again::RetryPolicy,
+}
+
+impl HbsClient {
+    /// Connects to HBS at `hbs_url` of `env`, signing requests with the host's key in lair
+    pub async fn connect(config: Option<&Config>, env: &HposEnvironment) -> Result<Self> {
+        let keys = HostKeys::from_lair(config, env).await?;
+        Self::with_keys(env, keys)
+    }
+
+    /// Connects to HBS at `hbs_url` of `env`, signing requests with `keys`
+    pub fn with_keys(env: &HposEnvironment, keys: HostKeys) -> Result<Self> {
+        Ok(Self::new(env.hbs_url()?, keys))
+    }
+
+    pub fn new(hbs_url: impl Into<String>, keys: HostKeys) -> Self {
+        Self {
+            hbs_url: hbs_url.into().trim_end_matches('/').to_string(),
+            keys,
+            client: Client::new(),
+            timeout: DEFAULT_TIMEOUT,
+            retry_policy: default_retry_policy(),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: again::RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn keys(&self) -> &HostKeys {
+        &self.keys
+    }
+
+    /// Handles post request to HBS server under /auth/api/v1/holo-client path
+    /// Creates signature from agent's key that is verified by HBS
+    /// Returns the host's registration record
+    pub async fn get_host_registration(&self) -> Result<RegistrationRecord, HbsError> {
+        self.post_signed(HOLO_CLIENT_PATH, || HoloClientPayload {
+            email: self.keys.email.clone(),
+            timestamp: timestamp_millis(),
+            pub_key: self.keys.pubkey_base36.clone(),
+        })
+        .await
+    }
+
+    /// Posts the payload built by `payload` as JSON to `path` of HBS with the signature of its
+    /// msgpack encoding in `X-Signature` header and parses the JSON response. Each attempt
+    /// builds and signs the payload anew, so retries don't resend a stale timestamp.
+    pub async fn post_signed<P, R>(
+        &self,
+        path: &str,
+        payload: impl Fn() -> P,
+    ) -> Result<R, HbsError>
+    where
+        P: Serialize + fmt::Debug,
+        R: DeserializeOwned,
+    {
+        let url = format!("{}{}", self.hbs_url, path);
+        let body = self
+            .retry_policy
+            .retry_if(|| self.post(&url, payload()), HbsError::is_retryable)
+            .await?;
+        debug!("HBS response from {}: {}", path, body);
+
+        serde_json::from_str(&body).map_err(|source| HbsError::Parse { source, body })
+    }
+
+    async fn post<P: Serialize + fmt::Debug>(
+        &self,
+        url: &str,
+        payload: P,
+    ) -> Result<String, HbsError> {
+        trace!("HBS payload for {}: {:?}", url, payload);
+        let encoded_payload =
+            ExternIO::encode(&payload).map_err(|e| HbsError::Signature(e.into()))?;
+        let signature = self
+            .keys
+            .sign(encoded_payload)
+            .await
+            .map_err(HbsError::Signature)?;
+
+        let response = self
+            .client
+            .post(url)
+            .timeout(self.timeout)
+            .json(&payload)
+            .header("X-Signature", signature)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        // Cloudflare reports a gateway timeout of HBS with a successful status
+        let status = if status.is_success() && body.contains("error code: 504") {
+            StatusCode::GATEWAY_TIMEOUT
+        } else {
+            status
+        };
+        if !status.is_success() {
+            warn!("HBS request to {} failed with {}", url, status);
+            return Err(HbsError::Status { status, body });
+        }
+        Ok(body)
+    }
+}
+
+/// Current time in milliseconds since the epoch, the resolution HBS expects
+fn timestamp_millis() -> u64 {
+    let (secs, nanos) = Timestamp::now().as_seconds_and_nanos();
+    secs.max(0) as u64 * 1000 + u64::from(nanos / 1_000_000)
+}
+
+impl fmt::Debug for HbsClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HbsClient")
+            .field("hbs_url", &self.hbs_url)
+            .field("keys", &self.keys)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
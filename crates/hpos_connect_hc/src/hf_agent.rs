@@ -1,43 +1,49 @@
 use crate::app_connection::CoreAppRoleName;
-use crate::holo_config::{default_password, get_lair_url, Config, HappsFile, ADMIN_PORT};
-use crate::{AdminWebsocket, AppConnection};
+use crate::holo_config::{get_lair_url, Config, HappsFile, ADMIN_PORT};
+use crate::holofuel_types::{
+    AcceptTx, Actionable, CounterSigningResponse, InvoiceInput, Ledger,
+    MigrationCloseStateV1Handler, Pending, Profile, ProfileInput, PromiseInput, Reserve,
+    ReserveSalePrice, ReserveSetting, Transaction,
+};
+use crate::{AdminWebsocket, AppConnection, HposEnvironment};
 use anyhow::{anyhow, Context, Result};
 use holochain_keystore::AgentPubKeyExt;
-use holochain_types::dna::AgentPubKey;
-use holochain_types::prelude::Signature;
+use holochain_types::dna::{ActionHash, AgentPubKey, EntryHashB64};
+use holochain_types::prelude::{FunctionName, Signature, Timestamp, ZomeName};
 use std::sync::Arc;
 
 /// Struct giving access to local instance of HHA on HPOS
 /// `config` of type `holo_config::Config` represents CLI params and can be passed
-/// to describe local running environment
+/// to describe local running environment (admin port defaults to `ADMIN_PORT` without it), `env` supplies passwords, lair and happs file location
 pub struct HfAgent {
     pub app: AppConnection,
 }
 
 impl HfAgent {
-    pub async fn spawn(config: Option<&Config>) -> Result<Self> {
-        let mut admin_ws = AdminWebsocket::connect(ADMIN_PORT)
+    pub async fn spawn(config: Option<&Config>, env: &HposEnvironment) -> Result<Self> {
+        let admin_port = config.map_or(ADMIN_PORT, |c| c.admin_port);
+        let mut admin_ws = AdminWebsocket::connect(admin_port)
             .await
             .context("failed to connect to holochain's app interface")?;
 
-        let app_file = HappsFile::load_happ_file_from_env(config)?;
+        let app_file = HappsFile::load_happ_file_from_env(config, env)?;
         let holofuel = app_file
             .holofuel()
             .ok_or(anyhow!("There's no holofuel app defined in a happs file"))?;
 
         // connect to lair
-        let passphrase = sodoken::BufRead::from(default_password()?.as_bytes().to_vec());
+        let passphrase =
+            sodoken::BufRead::from(env.holochain_default_password()?.as_bytes().to_vec());
 
         let keystore = holochain_keystore::lair_keystore::spawn_lair_keystore(
-            url2::url2!("{}", get_lair_url(config)?),
+            url2::url2!("{}", get_lair_url(config, env)?),
             passphrase,
         )
         .await?;
 
-        let holofuel_id = if let Ok(id) = std::env::var("TEST_HOLOFUEL_ID") {
-            id
-        } else {
-            holofuel.id()
+        let holofuel_id = match &env.test_holofuel_id {
+            Some(id) => id.clone(),
+            None => holofuel.id(env),
         };
 
         let app = AppConnection::connect(&mut admin_ws, keystore, holofuel_id)
@@ -66,4 +72,222 @@ impl HfAgent {
         let pubkey = self.pubkey().await?;
         Ok(pubkey.sign_raw(&self.app.keystore, data).await?)
     }
+
+    // TRANSACTOR ZOME CALLS:
+    pub async fn ledger(&mut self) -> Result<Ledger> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("get_ledger"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn pending(&mut self) -> Result<Pending> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("get_pending_transactions"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn actionable(&mut self) -> Result<Actionable> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("get_actionable_transactions"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn completed(&mut self) -> Result<Vec<Transaction>> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("get_completed_transactions"),
+                (),
+            )
+            .await
+    }
+
+    /// Accepts a pending transaction. Returns hash of the transaction which then
+    /// needs to be completed via `complete_transactions` or by the scheduler
+    pub async fn accept(
+        &mut self,
+        id: EntryHashB64,
+        expiration_date: Option<Timestamp>,
+    ) -> Result<EntryHashB64> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("accept_transaction"),
+                AcceptTx {
+                    address: id,
+                    expiration_date,
+                },
+            )
+            .await
+    }
+
+    pub async fn decline(&mut self, id: EntryHashB64) -> Result<EntryHashB64> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("decline_pending_transaction"),
+                id,
+            )
+            .await
+    }
+
+    pub async fn cancel(&mut self, id: EntryHashB64) -> Result<EntryHashB64> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("cancel_transaction"),
+                id,
+            )
+            .await
+    }
+
+    pub async fn create_invoice(&mut self, invoice: InvoiceInput) -> Result<Transaction> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("create_invoice"),
+                invoice,
+            )
+            .await
+    }
+
+    pub async fn create_promise(&mut self, promise: PromiseInput) -> Result<Transaction> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("create_promise"),
+                promise,
+            )
+            .await
+    }
+
+    /// Tries to countersign an accepted transaction right away
+    /// instead of waiting for the scheduler to do it
+    pub async fn complete_transactions(
+        &mut self,
+        id: EntryHashB64,
+    ) -> Result<CounterSigningResponse> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("complete_transactions"),
+                id,
+            )
+            .await
+    }
+
+    pub async fn my_summary(&mut self) -> Result<MigrationCloseStateV1Handler> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("get_my_summary"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn agent_summary(
+        &mut self,
+        pub_key: AgentPubKey,
+    ) -> Result<MigrationCloseStateV1Handler> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("transactor"),
+                FunctionName::from("get_agent_summary"),
+                pub_key,
+            )
+            .await
+    }
+
+    // PROFILE ZOME CALLS:
+    pub async fn profile(&mut self) -> Result<Profile> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("profile"),
+                FunctionName::from("get_my_profile"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn update_profile(&mut self, profile: ProfileInput) -> Result<EntryHashB64> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("profile"),
+                FunctionName::from("update_my_profile"),
+                profile,
+            )
+            .await
+    }
+
+    // RESERVES ZOME CALLS:
+    pub async fn reserve_accounts(&mut self) -> Result<Vec<Reserve>> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("reserves"),
+                FunctionName::from("get_all_reserve_accounts_details"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn register_reserve_account(&mut self, setting: ReserveSetting) -> Result<Reserve> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("reserves"),
+                FunctionName::from("register_reserve_account"),
+                setting,
+            )
+            .await
+    }
+
+    pub async fn sale_price(&mut self) -> Result<ReserveSalePrice> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("reserves"),
+                FunctionName::from("get_my_sale_price"),
+                (),
+            )
+            .await
+    }
+
+    pub async fn set_sale_price(&mut self, price: ReserveSalePrice) -> Result<ActionHash> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::Holofuel.into(),
+                ZomeName::from("reserves"),
+                FunctionName::from("set_sale_price"),
+                price,
+            )
+            .await
+    }
 }
@@ -3,45 +3,48 @@ use std::sync::Arc;
 use crate::app_connection::CoreAppRoleName;
 use crate::hha_types::{
     HappAndHost, HappInput, HappPreferences, HoloportDetails, PresentedHappBundle,
-    ServiceloggerHappPreferences,
+    ServiceloggerHappPreferences, SetHappPreferencesInput, SetHostJurisdictionInput,
+    UpdateHappInput,
 };
-use crate::holo_config::{default_password, get_lair_url, Config, HappsFile, ADMIN_PORT};
+use crate::holo_config::{get_lair_url, Config, HappsFile, ADMIN_PORT};
 use crate::holofuel_types::PendingTransaction;
-use crate::{AdminWebsocket, AppConnection};
+use crate::{AdminWebsocket, AppConnection, HposEnvironment};
 use anyhow::{anyhow, Context, Result};
 use holochain_keystore::AgentPubKeyExt;
-use holochain_types::dna::{ActionHashB64, AgentPubKey};
+use holochain_types::dna::{ActionHash, ActionHashB64, AgentPubKey};
 use holochain_types::prelude::{FunctionName, Signature, ZomeName};
 
 // NOTE: This should really be renamed CORE_APP_AGENT, as it related to the core app and therfore connects to BOTH hha and hf
 /// Struct giving access to local instance of HHA on HPOS
 /// `config` of type `holo_config::Config` represents CLI params and can be passed
-/// to describe local running environment
+/// to describe local running environment (admin port defaults to `ADMIN_PORT` without it), `env` supplies passwords, lair and happs file location
 pub struct CoreAppAgent {
     pub app: AppConnection,
 }
 
 impl CoreAppAgent {
-    pub async fn spawn(config: Option<&Config>) -> Result<Self> {
-        let mut admin_ws = AdminWebsocket::connect(ADMIN_PORT)
+    pub async fn spawn(config: Option<&Config>, env: &HposEnvironment) -> Result<Self> {
+        let admin_port = config.map_or(ADMIN_PORT, |c| c.admin_port);
+        let mut admin_ws = AdminWebsocket::connect(admin_port)
             .await
             .context("failed to connect to holochain's app interface")?;
 
-        let app_file = HappsFile::load_happ_file_from_env(config)?;
+        let app_file = HappsFile::load_happ_file_from_env(config, env)?;
         let core_app = app_file
             .core_app()
             .ok_or(anyhow!("There's no core-app defined in a happs file"))?;
 
         // connect to lair
-        let passphrase = sodoken::BufRead::from(default_password()?.as_bytes().to_vec());
+        let passphrase =
+            sodoken::BufRead::from(env.holochain_default_password()?.as_bytes().to_vec());
 
         let keystore = holochain_keystore::lair_keystore::spawn_lair_keystore(
-            url2::url2!("{}", get_lair_url(config)?),
+            url2::url2!("{}", get_lair_url(config, env)?),
             passphrase,
         )
         .await?;
 
-        let app = AppConnection::connect(&mut admin_ws, keystore, core_app.id())
+        let app = AppConnection::connect(&mut admin_ws, keystore, core_app.id(env))
             .await
             .context("Failed to connect to holochain's app interface")?;
 
@@ -103,6 +106,51 @@ impl CoreAppAgent {
             .await
     }
 
+    pub async fn get_happ(&mut self, happ_id: ActionHashB64) -> Result<PresentedHappBundle> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("get_happ"),
+                happ_id,
+            )
+            .await
+    }
+
+    pub async fn update_happ(&mut self, input: UpdateHappInput) -> Result<PresentedHappBundle> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("update_happ"),
+                input,
+            )
+            .await
+    }
+
+    /// Stops hosts from serving a hApp of this publisher until it is unpaused
+    pub async fn pause_happ(&mut self, happ_id: ActionHashB64) -> Result<PresentedHappBundle> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("pause_happ"),
+                happ_id,
+            )
+            .await
+    }
+
+    pub async fn unpause_happ(&mut self, happ_id: ActionHashB64) -> Result<PresentedHappBundle> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("unpause_happ"),
+                happ_id,
+            )
+            .await
+    }
+
     pub async fn get_host_preferences(&mut self) -> Result<HappPreferences> {
         self.app
             .zome_call_typed(
@@ -128,6 +176,72 @@ impl CoreAppAgent {
             .await
     }
 
+    pub async fn set_happ_preferences(
+        &mut self,
+        preferences: SetHappPreferencesInput,
+    ) -> Result<HappPreferences> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("set_happ_preferences"),
+                preferences,
+            )
+            .await
+    }
+
+    /// Preferences stored under `preferences_hash`, also after they were replaced by newer ones
+    pub async fn get_specific_happ_preferences(
+        &mut self,
+        preferences_hash: ActionHash,
+    ) -> Result<HappPreferences> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("get_specific_happ_preferences"),
+                preferences_hash,
+            )
+            .await
+    }
+
+    /// All preferences this host has set for a hApp, oldest first
+    pub async fn get_happ_preference_history(
+        &mut self,
+        happ_id: ActionHashB64,
+    ) -> Result<Vec<HappPreferences>> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("get_happ_preference_history"),
+                happ_id,
+            )
+            .await
+    }
+
+    pub async fn get_host_jurisdiction(&mut self, pubkey: AgentPubKey) -> Result<Option<String>> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("get_host_jurisdiction"),
+                pubkey,
+            )
+            .await
+    }
+
+    pub async fn set_host_jurisdiction(&mut self, input: SetHostJurisdictionInput) -> Result<()> {
+        self.app
+            .zome_call_typed(
+                CoreAppRoleName::HHA.into(),
+                ZomeName::from("hha"),
+                FunctionName::from("set_host_jurisdiction"),
+                input,
+            )
+            .await
+    }
+
     pub async fn get_publisher_jurisdiction(
         &mut self,
         pubkey: AgentPubKey,
@@ -69,6 +69,18 @@ pub struct SetHappPreferencesInput {
     pub max_time_before_invoice: Duration, // how much time to allow to pass before sending invoice even if fuel trigger not reached.
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetHostJurisdictionInput {
+    pub pubkey: AgentPubKey,
+    pub jurisdiction: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateHappInput {
+    pub happ_id: ActionHashB64,
+    pub updated_happ: HappInput,
+}
+
 #[derive(Debug, Serialize, Deserialize, SerializedBytes, Clone, Default)]
 pub struct HostSettings {
     pub is_enabled: bool,
@@ -76,10 +88,10 @@ pub struct HostSettings {
     pub is_auto_disabled: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HoloportId(pub String);
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HoloportDetails {
     pub host_pub_key: AgentPubKeyB64,
     pub holoport_id: HoloportId,
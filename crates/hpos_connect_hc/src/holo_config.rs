@@ -1,13 +1,19 @@
-use super::hpos_agent::{read_hpos_config, Admin};
+use super::environment::HposEnvironment;
+use super::hpos_agent::Admin;
+use super::hpos_config::HposConfig;
+use super::utils::{download_file, verify_sha256};
 use anyhow::{anyhow, Context, Result};
-use holochain_types::prelude::{AgentPubKey, AppBundleSource};
+use holochain_types::prelude::{
+    AppBundleSource, CellProvisioning, CoordinatorManifest, DnaBundle, DnaHash, DnaManifest,
+};
 use holochain_types::{app::AppManifest, prelude::YamlProperties};
 use lair_keystore_api::{
     dependencies::{serde_yaml, url::Url},
     prelude::LairServerConfigInner,
 };
+use mr_bundle::Bundle;
 use serde::Deserialize;
-use std::env;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tracing::{debug, instrument};
@@ -15,18 +21,13 @@ use tracing::{debug, instrument};
 pub const APP_PORT: u16 = 42233;
 pub const ADMIN_PORT: u16 = 4444;
 
-pub fn default_password() -> Result<String> {
-    env::var("HOLOCHAIN_DEFAULT_PASSWORD")
-        .context("Failed to read HOLOCHAIN_DEFAULT_PASSWORD. Is it set in env?")
-}
-
 /// Read url for connection to Lair
 /// Depending on a system this url can be obtained from the following sources (in order of trying):
 /// 1. `Config` representing CLI parameters of a binary
-/// 2. From LAIR_CONNECTION_URL env var
-/// 3. From lair config present in default working directory
+/// 2. From `lair_connection_url` of the environment
+/// 3. From lair config present in `lair_working_dir`
 /// 4. Throws an error otherwise
-pub fn get_lair_url(maybe_config: Option<&Config>) -> Result<String> {
+pub fn get_lair_url(maybe_config: Option<&Config>, env: &HposEnvironment) -> Result<String> {
     if let Some(config) = maybe_config {
         return config
             .lair_url
@@ -34,27 +35,25 @@ pub fn get_lair_url(maybe_config: Option<&Config>) -> Result<String> {
             .ok_or_else(|| anyhow!("Does not have lair url, please provide --lair-url"));
     }
 
-    if let Ok(url) = env::var("LAIR_CONNECTION_URL") {
-        return Ok(url);
+    if let Some(url) = &env.lair_connection_url {
+        return Ok(url.clone());
     }
 
-    if let Ok(config) = read_lair_config() {
+    if let Ok(config) = read_lair_config(env) {
         return Ok(config.connection_url.to_string());
     }
 
     Err(anyhow!("Couldn't obtain lair connection url from CLI, LAIR_CONNECTION_URL or lair working directory"))
 }
 
-fn read_lair_config() -> Result<LairServerConfigInner> {
-    let file = std::fs::File::open(default_lair_dir()?)?;
+fn read_lair_config(env: &HposEnvironment) -> Result<LairServerConfigInner> {
+    let file = std::fs::File::open(default_lair_dir(env)?)?;
     let config: LairServerConfigInner = serde_yaml::from_reader(file)?;
     Ok(config)
 }
 
-fn default_lair_dir() -> Result<String> {
-    let working_dir = env::var("LAIR_WORKING_DIR")
-        .context("Failed to read LAIR_WORKING_DIR. Is it set in env?")?;
-    Ok(format!("{}/lair-keystore-config.yaml", working_dir))
+fn default_lair_dir(env: &HposEnvironment) -> Result<PathBuf> {
+    Ok(env.lair_working_dir()?.join("lair-keystore-config.yaml"))
 }
 
 #[derive(Debug, Clone, StructOpt)]
@@ -105,8 +104,12 @@ pub struct MembraneProofFile {
 pub struct Happ {
     pub ui_url: Option<Url>,
     pub ui_path: Option<PathBuf>,
+    /// Hex encoded sha256 the UI archive has to match
+    pub ui_sha256: Option<String>,
     pub bundle_url: Option<Url>,
     pub bundle_path: Option<PathBuf>,
+    /// Hex encoded sha256 the .happ bundle has to match
+    pub bundle_sha256: Option<String>,
     pub dnas: Option<Vec<Dna>>,
     pub agent_bundle_override: Option<String>,
 }
@@ -115,21 +118,40 @@ pub struct Happ {
 pub struct Dna {
     pub role_name: String,
     pub properties: Option<String>,
+    /// Expected hash of the role's DNA as installed, with `properties` applied
+    pub dna_hash: Option<String>,
+}
+
+/// DNA of one role of a happ bundle
+#[derive(Debug, Clone)]
+pub struct RoleDna {
+    /// Hash of the DNA with modifiers applied, as the installed cell will have it
+    pub hash: DnaHash,
+    /// Coordinator zomes of the DNA, to update cells that keep their integrity zomes
+    pub coordinators: Bundle<CoordinatorManifest>,
 }
 
 impl Happ {
     /// returns the name that will be used to access the ui
     pub fn ui_name(&self) -> String {
-        let mut name = self.id();
+        let mut name = self.base_id();
         if let Some(idx) = name.find(':') {
             name.truncate(idx);
         }
         name
     }
     /// generates the installed app id that should be used
-    /// based on the path or url of the bundle.
+    /// based on the path or url of the bundle and `dev_uid_override` of `env`.
     /// Assumes file name ends in .happ, and converts periods -> colons
-    pub fn id(&self) -> String {
+    pub fn id(&self, env: &HposEnvironment) -> String {
+        if let Some(uid) = &env.dev_uid_override {
+            format!("{}::{}", self.base_id(), uid)
+        } else {
+            self.base_id()
+        }
+    }
+    /// installed app id without the network seed suffix
+    fn base_id(&self) -> String {
         let name = if let Some(ref bundle) = self.bundle_path {
             bundle
                 .file_name()
@@ -143,118 +165,246 @@ impl Happ {
             //TODO fix
             "unreabable".to_string()
         };
-        if let Ok(uid) = env::var("DEV_UID_OVERRIDE") {
-            format!("{}::{}", name.replace(".happ", "").replace('.', ":"), uid)
-        } else {
-            name.replace(".happ", "").replace('.', ":")
+        name.replace(".happ", "").replace('.', ":")
+    }
+    /// Downloads the happ bundle into the download cache of `env` and returns its path,
+    /// checked against `bundle_sha256` if set
+    pub async fn download(&self, env: &HposEnvironment) -> Result<PathBuf> {
+        local_or_download(
+            self.bundle_path.as_deref(),
+            self.bundle_url.as_ref(),
+            self.bundle_sha256.as_deref(),
+            env,
+        )
+        .await
+        .context(format!("bundle of {} rejected", self.base_id()))?
+        .context("bundle_url in happ is None")
+    }
+    /// Downloads the UI archive, if the happ has one, into the download cache of `env`
+    /// and returns its path, checked against `ui_sha256` if set
+    pub async fn download_ui(&self, env: &HposEnvironment) -> Result<Option<PathBuf>> {
+        local_or_download(
+            self.ui_path.as_deref(),
+            self.ui_url.as_ref(),
+            self.ui_sha256.as_deref(),
+            env,
+        )
+        .await
+        .context(format!("UI of {} rejected", self.base_id()))
+    }
+    /// Reads the happ bundle, downloading it into the download cache of `env` if needed,
+    /// with configured properties applied. Load it once and pass it on to `role_dnas`,
+    /// `verify_dna_hashes` and `source`.
+    pub async fn bundle(&self, env: &HposEnvironment) -> Result<Bundle<AppManifest>> {
+        let path = self.download(env).await?;
+        let bundle: Bundle<AppManifest> = Bundle::read_from_file(&path)
+            .await
+            .context(format!("failed to read bundle {}", path.display()))?;
+        let Some(dnas) = &self.dnas else {
+            return Ok(bundle);
+        };
+        let AppManifest::V1(mut manifest) = bundle.manifest().clone();
+        for dna in dnas {
+            for role_manifest in &mut manifest.roles {
+                if role_manifest.name == dna.role_name {
+                    // check for provided properties in the config file and apply if it exists
+                    let mut properties: Option<YamlProperties> = None;
+                    if let Some(p) = &dna.properties {
+                        debug!("Core app Properties: {}", p);
+                        properties = Some(YamlProperties::new(serde_yaml::from_str(p)?));
+                    }
+                    role_manifest.dna.modifiers.properties = properties
+                }
+            }
         }
+        Ok(bundle.update_manifest(AppManifest::V1(manifest))?)
     }
-    /// Downloads the happ bundle and returns its path
-    pub async fn download(&self) -> Result<PathBuf> {
-        match self.bundle_path.clone() {
-            Some(path) => Ok(path),
-            None => {
-                let path = super::utils::download_file(
-                    self.bundle_url
-                        .as_ref()
-                        .context("bundle_url in happ is None")?,
-                )
-                .await?;
-                Ok(path)
+
+    /// Source to install `bundle` from, with the roles in `existing_roles` provisioned
+    /// from cells that already exist on the conductor. Those cells have to be passed
+    /// as `existing_cells` on install.
+    pub fn source(
+        bundle: Bundle<AppManifest>,
+        existing_roles: &[String],
+    ) -> Result<AppBundleSource> {
+        if existing_roles.is_empty() {
+            return Ok(AppBundleSource::Bundle(bundle.into()));
+        }
+        let AppManifest::V1(mut manifest) = bundle.manifest().clone();
+        for role_manifest in &mut manifest.roles {
+            if existing_roles.contains(&role_manifest.name) {
+                // not protected, so that the app owning the cells can still be uninstalled
+                // once this one is enabled, the cells stay as long as this app uses them
+                role_manifest.provisioning =
+                    Some(CellProvisioning::UseExisting { protected: false });
             }
         }
+        Ok(AppBundleSource::Bundle(
+            bundle.update_manifest(AppManifest::V1(manifest))?.into(),
+        ))
     }
-    // get the source of the happ by retrieving the happ and updating the properties if any
-    pub async fn source(&self) -> Result<AppBundleSource> {
-        let path = self.download().await?;
-        let mut source = AppBundleSource::Path(path);
-        if self.dnas.is_some() {
-            for dna in self.dnas.clone().unwrap().iter() {
-                use mr_bundle::Bundle;
-                let bundle = match source {
-                    AppBundleSource::Bundle(bundle) => bundle.into_inner(),
-                    AppBundleSource::Path(path) => Bundle::read_from_file(&path).await.unwrap(),
-                };
-                let AppManifest::V1(mut manifest) = bundle.manifest().clone();
-                for role_manifest in &mut manifest.roles {
-                    if role_manifest.name == dna.role_name {
-                        // check for provided properties in the config file and apply if it exists
-                        let mut properties: Option<YamlProperties> = None;
-                        if let Some(p) = dna.properties.clone() {
-                            let prop = p.to_string();
-                            debug!("Core app Properties: {}", prop);
-                            properties =
-                                Some(YamlProperties::new(serde_yaml::from_str(&prop).unwrap()));
-                        }
-                        role_manifest.dna.modifiers.properties = properties
-                    }
-                }
-                source = AppBundleSource::Bundle(
-                    bundle
-                        .update_manifest(AppManifest::V1(manifest))
-                        .unwrap()
-                        .into(),
-                )
+
+    /// DNA hashes and coordinator zomes by role name of `bundle`, see `bundle`,
+    /// with `dev_uid_override` of `env` applied
+    pub async fn role_dnas(
+        bundle: &Bundle<AppManifest>,
+        env: &HposEnvironment,
+    ) -> Result<HashMap<String, RoleDna>> {
+        let AppManifest::V1(manifest) = bundle.manifest().clone();
+        let mut dnas = HashMap::new();
+        for role_manifest in manifest.roles {
+            let location = role_manifest
+                .dna
+                .location
+                .clone()
+                .context(format!("role {} has no DNA location", role_manifest.name))?;
+            let bytes = bundle.resolve(&location).await?;
+
+            let dna: Bundle<DnaManifest> = Bundle::decode(&bytes)?;
+            let DnaManifest::V1(dna_manifest) = dna.manifest().clone();
+            let coordinators = Bundle::new(
+                dna_manifest.coordinator,
+                dna.bundled_resources().clone(),
+                PathBuf::new(),
+            )?;
+
+            let mut modifiers = role_manifest.dna.modifiers.clone().serialized()?;
+            if let Some(uid) = &env.dev_uid_override {
+                modifiers.network_seed = Some(uid.clone());
+            }
+            let (_, hash) = DnaBundle::decode(&bytes)?.into_dna_file(modifiers).await?;
+
+            dnas.insert(role_manifest.name, RoleDna { hash, coordinators });
+        }
+        Ok(dnas)
+    }
+
+    /// Whether any role has an expected `dna_hash` to check with `verify_dna_hashes`
+    pub fn expects_dna_hashes(&self) -> bool {
+        self.dnas.iter().flatten().any(|dna| dna.dna_hash.is_some())
+    }
+
+    /// Fails if a role with an expected `dna_hash` would be installed with another DNA hash,
+    /// `role_dnas` being the DNAs of the bundle to install
+    pub fn verify_dna_hashes(&self, role_dnas: &HashMap<String, RoleDna>) -> Result<()> {
+        for dna in self.dnas.iter().flatten() {
+            let Some(expected) = &dna.dna_hash else {
+                continue;
+            };
+            let role_dna = role_dnas.get(&dna.role_name).context(format!(
+                "role {} is not in the manifest of {}",
+                dna.role_name,
+                self.base_id()
+            ))?;
+            if &role_dna.hash.to_string() != expected {
+                return Err(anyhow!(
+                    "DNA hash mismatch for role {} of {}: expected {}, bundle has {}",
+                    dna.role_name,
+                    self.base_id(),
+                    expected,
+                    role_dna.hash
+                ));
             }
         }
-        Ok(source)
+        Ok(())
     }
+
     // returns pub key is agent override exists
-    pub async fn agent_override_details(&self) -> Result<Option<Admin>> {
+    pub async fn agent_override_details(&self, env: &HposEnvironment) -> Result<Option<Admin>> {
         if let Some(agent_bundle_override) = &self.agent_bundle_override {
-            let config = read_hpos_config(agent_bundle_override)?;
-            let pub_key = hpos_config_seed_bundle_explorer::holoport_public_key(
-                &config,
-                Some(default_password()?),
-            )
-            .await?;
-            let key = AgentPubKey::from_raw_32(pub_key.to_bytes().to_vec());
-            match config {
-                hpos_config_core::Config::V3 {
-                    registration_code,
-                    settings,
-                    ..
-                } => Ok(Some(Admin {
-                    key,
-                    registration_code,
-                    email: settings.admin.email,
-                })),
-                hpos_config_core::Config::V2 {
-                    registration_code,
-                    settings,
-                    ..
-                } => Ok(Some(Admin {
-                    key,
-                    registration_code,
-                    email: settings.admin.email,
-                })),
-                hpos_config_core::Config::V1 { .. } => Ok(None),
+            let config = HposConfig::load(agent_bundle_override)?;
+            if !config.is_supported() {
+                return Ok(None);
             }
+            let key = config
+                .holoport_public_key(env.holochain_default_password()?)
+                .await?;
+            Ok(Some(Admin {
+                key,
+                registration_code: config.registration_code()?.to_string(),
+                email: config.admin_email()?.to_string(),
+            }))
         } else {
             Ok(None)
         }
     }
 }
 
+/// Local `path` if set, otherwise `url` downloaded into the download cache of `env`,
+/// either one checked against `sha256` if set
+async fn local_or_download(
+    path: Option<&Path>,
+    url: Option<&Url>,
+    sha256: Option<&str>,
+    env: &HposEnvironment,
+) -> Result<Option<PathBuf>> {
+    match (path, url) {
+        (Some(path), _) => {
+            if let Some(sha256) = sha256 {
+                verify_sha256(path, sha256)?;
+            }
+            Ok(Some(path.to_path_buf()))
+        }
+        (None, Some(url)) => Ok(Some(
+            download_file(url, &env.download_cache()?, sha256).await?,
+        )),
+        (None, None) => Ok(None),
+    }
+}
+
+/// What configure-holochain does with enabled apps that are not in the happs file.
+/// App ids are matched against globs, where `*` matches any run of characters and `?` one character.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CleanupPolicy {
+    /// Keep hosted happs, whose ids contain `uhCkk`
+    pub keep_hosted: bool,
+    /// Globs of app ids to keep enabled
+    pub keep: Vec<String>,
+    /// Regular expressions of app ids to keep enabled
+    pub keep_regex: Vec<String>,
+    /// Globs of app ids that are disabled instead of uninstalled, so their data survives
+    pub protected: Vec<String>,
+    /// Globs of app ids configure-holochain never disables, uninstalls or upgrades from
+    pub never_touch: Vec<String>,
+    /// Disable all apps that are not kept instead of uninstalling them
+    pub disable_instead_of_uninstall: bool,
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        CleanupPolicy {
+            keep_hosted: true,
+            keep: vec![],
+            keep_regex: vec![],
+            protected: vec![],
+            never_touch: vec![],
+            disable_instead_of_uninstall: false,
+        }
+    }
+}
+
 /// hApps
 #[derive(Debug, Deserialize)]
 pub struct HappsFile {
     pub self_hosted_happs: Vec<Happ>,
     pub core_happs: Vec<Happ>,
+    #[serde(default)]
+    pub cleanup: CleanupPolicy,
 }
 impl HappsFile {
     pub fn holofuel(self) -> Option<Happ> {
         let core_app = &self
             .core_happs
             .into_iter()
-            .find(|x| x.id().contains("holofuel"));
+            .find(|x| x.base_id().contains("holofuel"));
         core_app.clone()
     }
     pub fn core_app(self) -> Option<Happ> {
         let core_app = &self
             .core_happs
             .into_iter()
-            .find(|x| x.id().contains("core-app"));
+            .find(|x| x.base_id().contains("core-app"));
         core_app.clone()
     }
 
@@ -269,13 +419,16 @@ impl HappsFile {
 
     /// Loads happ file form the path constructed in such a way (in order of precedence):
     /// 1. If `config` is passed loads from `config.happs_file_path`
-    /// 2. From CORE_HAPP_FILE env var
+    /// 2. From `core_happ_file` of the environment
     /// 3. Throws an error otherwise
-    pub fn load_happ_file_from_env(maybe_config: Option<&Config>) -> Result<Self> {
+    pub fn load_happ_file_from_env(
+        maybe_config: Option<&Config>,
+        env: &HposEnvironment,
+    ) -> Result<Self> {
         let path = if let Some(config) = maybe_config {
             config.happs_file_path.clone()
-        } else if let Ok(path) = env::var("CORE_HAPP_FILE") {
-            path.into()
+        } else if let Some(path) = &env.core_happ_file {
+            path.clone()
         } else {
             return Err(anyhow!(
                 "Couldn't obtain happs file path from CLI nor CORE_HAPP_FILE env var"
@@ -299,19 +452,34 @@ mod tests {
             bundle_url: None,
             ui_url: None,
             ui_path: None,
+            ui_sha256: None,
+            bundle_sha256: None,
             dnas: None,
             agent_bundle_override: None,
         };
-        assert_eq!(cfg.id(), String::from("elemental_chat:1:0001"));
+        assert_eq!(
+            cfg.id(&HposEnvironment::default()),
+            String::from("elemental_chat:1:0001")
+        );
+        let env = HposEnvironment {
+            dev_uid_override: Some("0002".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(cfg.id(&env), String::from("elemental_chat:1:0001::0002"));
         let cfg = Happ {
             bundle_path: None,
             bundle_url: Some(Url::parse("https://github.com/holochain/elemental-chat/releases/download/v0.1.0-alpha1/elemental_chat.1.0001.happ").unwrap()),
             ui_url: None,
             ui_path: None,
+            ui_sha256: None,
+            bundle_sha256: None,
             dnas: None,
             agent_bundle_override: None,
         };
-        assert_eq!(cfg.id(), String::from("elemental_chat:1:0001"));
+        assert_eq!(
+            cfg.id(&HposEnvironment::default()),
+            String::from("elemental_chat:1:0001")
+        );
     }
 
     #[test]
@@ -321,6 +489,8 @@ mod tests {
             bundle_url: None,
             ui_url: None,
             ui_path: None,
+            ui_sha256: None,
+            bundle_sha256: None,
             dnas: None,
             agent_bundle_override: None,
         };
@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::environment::HposEnvironment;
+use anyhow::{anyhow, Context, Result};
 use holochain_types::prelude::ActionHash;
 use holochain_types::prelude::AgentPubKey;
 use holochain_types::prelude::AnyLinkableHash;
@@ -11,6 +12,7 @@ use holochain_types::prelude::{
 use holofuel_types::fuel::Fuel;
 use serde::Deserialize;
 use serde::Serialize;
+use std::str::FromStr;
 use std::time::Duration;
 use tracing::debug;
 
@@ -45,6 +47,41 @@ pub struct Actionable {
     pub promise_actionable: Vec<Transaction>,
 }
 
+/// Input of `transactor/accept_transaction`
+#[derive(Serialize, Deserialize, Debug, SerializedBytes)]
+pub struct AcceptTx {
+    pub address: EntryHashB64,
+    pub expiration_date: Option<Timestamp>,
+}
+
+/// Input of `transactor/create_invoice`, `receiver` is the agent asked to pay
+#[derive(Serialize, Deserialize, Debug, Clone, SerializedBytes)]
+pub struct InvoiceInput {
+    pub receiver: AgentPubKeyB64,
+    pub amount: String,
+    pub payload: PayloadInput,
+    pub expiration_date: Option<Timestamp>,
+}
+
+/// Input of `transactor/create_promise`, `receiver` is the agent being paid.
+/// `invoice_hash` is set when the promise pays an existing invoice
+#[derive(Serialize, Deserialize, Debug, Clone, SerializedBytes)]
+pub struct PromiseInput {
+    pub receiver: AgentPubKeyB64,
+    pub amount: String,
+    pub timestamp: Timestamp,
+    pub expiration_date: Timestamp,
+    pub payload: PayloadInput,
+    pub invoice_hash: Option<EntryHashB64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SerializedBytes)]
+pub struct PayloadInput {
+    pub note: Option<String>,
+    pub proof_of_service: Option<POS>,
+    pub url: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, SerializedBytes, Debug, Clone, PartialEq)]
 pub enum CounterSigningResponse {
     Successful(EntryHashB64),
@@ -76,6 +113,54 @@ pub struct Transaction {
     pub url: Option<String>,
     pub expiration_date: Option<Timestamp>,
 }
+/// Parses a fuel amount as returned by holofuel (eg. "-10.5")
+pub fn parse_fuel(amount: &str) -> Result<Fuel> {
+    Fuel::from_str(amount).map_err(|e| anyhow!("invalid fuel amount {}: {:?}", amount, e))
+}
+
+/// Selects transactions for batch operations, unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    pub counterparty: Option<AgentPubKeyB64>,
+    /// Upper bound, inclusive, of the transaction amount
+    pub max_amount: Option<Fuel>,
+    /// Only transactions with an expiration date before this one
+    pub expiring_before: Option<Timestamp>,
+}
+
+impl TransactionFilter {
+    pub fn matches(&self, tx: &Transaction) -> Result<bool> {
+        if let Some(counterparty) = &self.counterparty {
+            if &tx.counterparty != counterparty {
+                return Ok(false);
+            }
+        }
+        if let Some(max_amount) = &self.max_amount {
+            if &parse_fuel(&tx.amount)? > max_amount {
+                return Ok(false);
+            }
+        }
+        if let Some(before) = &self.expiring_before {
+            match &tx.expiration_date {
+                Some(expiration) if expiration < before => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns transactions matching the filter, in original order
+    pub fn apply<'a>(&self, txs: &'a [Transaction]) -> Result<Vec<&'a Transaction>> {
+        let mut matching = vec![];
+        for tx in txs {
+            if self.matches(tx)? {
+                matching.push(tx);
+            }
+        }
+        Ok(matching)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, SerializedBytes)]
 pub enum TransactionType {
     Request, //Invoice
@@ -112,6 +197,13 @@ pub struct Profile {
     pub uniqueness: AnyDhtHashB64,
 }
 
+/// Input of `profile/update_my_profile`
+#[derive(Serialize, Deserialize, Debug, SerializedBytes)]
+pub struct ProfileInput {
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, SerializedBytes)]
 pub struct Reserve {
     pub reserve_id: ActionHashB64,
@@ -140,11 +232,10 @@ pub struct ReserveSettingFile {
     note: Option<String>,
 }
 impl ReserveSettingFile {
-    pub fn load_happ_file() -> Result<Self> {
+    pub fn load_happ_file(env: &HposEnvironment) -> Result<Self> {
         debug!("loading happ file");
-        let path = std::env::var("REGISTER_RESERVE")
-            .context("Failed to read REGISTER_RESERVE. Is it set in env?")?;
-        debug!("got path {}", path);
+        let path = env.register_reserve()?;
+        debug!("got path {}", path.display());
         // let file = File::open(path).context("failed to open file")?;
         let file = std::fs::read(path)?;
         debug!("got file: {:?}", file);
@@ -309,12 +400,22 @@ pub struct MultiSigPayload {
 }
 #[cfg(test)]
 pub mod tests {
-    use crate::holofuel_types::ReserveSettingFile;
+    use crate::holofuel_types::{parse_fuel, ReserveSettingFile};
 
     #[test]
     fn read_file() {
-        use std::env::set_var;
-        set_var("REGISTER_RESERVE", "./test/reserve_details.json");
-        ReserveSettingFile::load_happ_file().unwrap();
+        let env = crate::HposEnvironment {
+            register_reserve: Some("./test/reserve_details.json".into()),
+            ..Default::default()
+        };
+        ReserveSettingFile::load_happ_file(&env).unwrap();
+    }
+
+    #[test]
+    fn fuel_amounts() {
+        assert!(parse_fuel("10.50").unwrap() > parse_fuel("10.499").unwrap());
+        assert!(parse_fuel("-0.5").unwrap() < parse_fuel("0").unwrap());
+        assert!(parse_fuel("ten").is_err());
+        assert!(parse_fuel("").is_err());
     }
 }
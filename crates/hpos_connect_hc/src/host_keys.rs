@@ -1,41 +1,189 @@
+use super::environment::HposEnvironment;
+use super::holo_config::{get_lair_url, Config};
 use super::hpos_agent::get_signing_admin;
-use anyhow::{Context, Result};
+use super::hpos_config::HposConfig;
+use anyhow::{anyhow, Context, Result};
 use base64::encode_config;
 use ed25519_dalek::*;
+use futures::future::BoxFuture;
+use holochain_keystore::{AgentPubKeyExt, MetaLairClient};
+use holochain_types::dna::AgentPubKey;
 use holochain_types::prelude::ExternIO;
 use hpos_config_core::public_key;
+use lair_keystore_api::prelude::LairEntryInfo;
+use std::fmt;
+use std::sync::Arc;
+
+/// Tag under which the device seed of hpos-config is imported into lair
+pub const HOST_SEED_TAG: &str = "host";
+
+/// Signs payloads with the host's key
+pub trait HostSigner: Send + Sync + fmt::Debug {
+    fn verifying_key(&self) -> VerifyingKey;
+
+    fn sign<'a>(&'a self, data: &'a [u8]) -> BoxFuture<'a, Result<[u8; SIGNATURE_LENGTH]>>;
+}
+
+/// Signer holding the unlocked device key in process memory, meant for tests and tools
+#[derive(Debug)]
+pub struct InMemorySigner(SigningKey);
+
+impl InMemorySigner {
+    pub fn new(signing_key: SigningKey) -> Self {
+        Self(signing_key)
+    }
+}
+
+impl HostSigner for InMemorySigner {
+    fn verifying_key(&self) -> VerifyingKey {
+        self.0.verifying_key()
+    }
+
+    fn sign<'a>(&'a self, data: &'a [u8]) -> BoxFuture<'a, Result<[u8; SIGNATURE_LENGTH]>> {
+        Box::pin(async move {
+            let signature = self.0.try_sign(data).context("Failed to sign payload")?;
+            Ok(signature.to_bytes())
+        })
+    }
+}
+
+/// Signer asking lair to sign with the key of `pubkey`, the private key never leaves lair
+pub struct LairSigner {
+    keystore: MetaLairClient,
+    pubkey: AgentPubKey,
+}
+
+impl LairSigner {
+    pub fn new(keystore: MetaLairClient, pubkey: AgentPubKey) -> Self {
+        Self { keystore, pubkey }
+    }
+
+    /// Looks up the host's seed imported into lair under `HOST_SEED_TAG`
+    pub async fn host(keystore: MetaLairClient) -> Result<Self> {
+        let entry = keystore
+            .lair_client()
+            .get_entry(HOST_SEED_TAG.into())
+            .await
+            .context(format!("Failed to find seed {} in lair", HOST_SEED_TAG))?;
+        let seed_info = match entry {
+            LairEntryInfo::Seed { seed_info, .. } => seed_info,
+            LairEntryInfo::DeepLockedSeed { seed_info, .. } => seed_info,
+            _ => return Err(anyhow!("Lair entry {} is not a seed", HOST_SEED_TAG)),
+        };
+        let pubkey = AgentPubKey::from_raw_32(seed_info.ed25519_pub_key.to_vec());
+        Ok(Self::new(keystore, pubkey))
+    }
+}
+
+impl fmt::Debug for LairSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LairSigner")
+            .field("pubkey", &self.pubkey)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HostSigner for LairSigner {
+    fn verifying_key(&self) -> VerifyingKey {
+        let bytes: [u8; PUBLIC_KEY_LENGTH] = self
+            .pubkey
+            .get_raw_32()
+            .try_into()
+            .expect("agent key has 32 bytes");
+        VerifyingKey::from_bytes(&bytes).expect("lair returned a valid ed25519 key")
+    }
+
+    fn sign<'a>(&'a self, data: &'a [u8]) -> BoxFuture<'a, Result<[u8; SIGNATURE_LENGTH]>> {
+        Box::pin(async move {
+            let signature = self
+                .pubkey
+                .sign_raw(&self.keystore, data.into())
+                .await
+                .context("Failed to sign payload in lair")?;
+            Ok(signature.0)
+        })
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct HostKeys {
     pub email: String,
-    keypair: SigningKey,
+    signer: Arc<dyn HostSigner>,
     pub pubkey_base36: String,
     pub holoport_id: String,
 }
 
 impl HostKeys {
-    pub async fn new() -> Result<Self> {
-        let (keypair, email) = get_signing_admin().await?;
-        let pubkey_base36 = public_key::to_holochain_encoded_agent_key(&keypair.verifying_key());
-        let holoport_id = public_key::to_base36_id(&keypair.verifying_key());
+    /// Unlocks the device bundle of hpos-config at `hpos_config_path` and keeps its key in memory
+    pub async fn new(env: &HposEnvironment) -> Result<Self> {
+        let (keypair, email) = get_signing_admin(env).await?;
+        Ok(Self::from_signer(
+            email,
+            Arc::new(InMemorySigner::new(keypair)),
+        ))
+    }
+
+    /// Signs with the host's seed in lair, the device bundle is not unlocked
+    pub async fn from_lair(config: Option<&Config>, env: &HposEnvironment) -> Result<Self> {
+        let email = HposConfig::from_env(env)?.admin_email()?.to_string();
+        let passphrase =
+            sodoken::BufRead::from(env.holochain_default_password()?.as_bytes().to_vec());
+        let keystore = holochain_keystore::lair_keystore::spawn_lair_keystore(
+            url2::url2!("{}", get_lair_url(config, env)?),
+            passphrase,
+        )
+        .await?;
+        let signer = LairSigner::host(keystore).await?;
+        Ok(Self::from_signer(email, Arc::new(signer)))
+    }
 
-        Ok(Self {
+    pub fn from_signer(email: String, signer: Arc<dyn HostSigner>) -> Self {
+        let verifying_key = signer.verifying_key();
+        Self {
             email,
-            keypair,
-            pubkey_base36,
-            holoport_id,
-        })
+            pubkey_base36: public_key::to_holochain_encoded_agent_key(&verifying_key),
+            holoport_id: public_key::to_base36_id(&verifying_key),
+            signer,
+        }
     }
 
     pub async fn sign(&self, payload: ExternIO) -> Result<String> {
-        let signature = self
-            .keypair
-            .try_sign(payload.as_bytes())
-            .context("Failed to sign payload")?;
-
-        Ok(encode_config(
-            &signature.to_bytes()[..],
-            base64::STANDARD_NO_PAD,
-        ))
+        let signature = self.signer.sign(payload.as_bytes()).await?;
+
+        Ok(encode_config(&signature[..], base64::STANDARD_NO_PAD))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn assert_signs(keys: HostKeys, verifying_key: VerifyingKey) {
+        let payload = ExternIO::encode("payload").unwrap();
+        let encoded = keys.sign(payload.clone()).await.unwrap();
+        let bytes = base64::decode_config(encoded, base64::STANDARD_NO_PAD).unwrap();
+        let signature = ed25519_dalek::Signature::from_slice(&bytes).unwrap();
+        verifying_key
+            .verify_strict(payload.as_bytes(), &signature)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn in_memory_and_lair_signers_produce_valid_signatures() {
+        let signing_key = SigningKey::from_bytes(&[7; SECRET_KEY_LENGTH]);
+        let verifying_key = signing_key.verifying_key();
+        let keys = HostKeys::from_signer(
+            "host@holo.host".into(),
+            Arc::new(InMemorySigner::new(signing_key)),
+        );
+        assert_eq!(keys.holoport_id, public_key::to_base36_id(&verifying_key));
+        assert_signs(keys, verifying_key).await;
+
+        let keystore = holochain_keystore::test_keystore();
+        let pubkey = keystore.new_sign_keypair_random().await.unwrap();
+        let signer = LairSigner::new(keystore, pubkey);
+        let verifying_key = signer.verifying_key();
+        let keys = HostKeys::from_signer("host@holo.host".into(), Arc::new(signer));
+        assert_signs(keys, verifying_key).await;
     }
 }
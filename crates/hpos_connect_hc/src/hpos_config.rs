@@ -0,0 +1,207 @@
+//! Version independent access to hpos-config.
+//!
+//! Everything that depends on the layout of a `hpos_config_core::Config` version lives in
+//! `HposConfig::fields`, so supporting a new version only means adding its match arm there.
+
+use crate::environment::HposEnvironment;
+use crate::hpos_agent::AuthError;
+use anyhow::{Context, Result};
+use ed25519_dalek::SigningKey;
+use holochain_types::dna::AgentPubKey;
+use hpos_config_core::Config;
+use hpos_config_seed_bundle_explorer::unlock;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Parts of the config shared by all versions with a device bundle
+struct Fields<'a> {
+    device_bundle: &'a str,
+    registration_code: &'a str,
+    admin_email: &'a str,
+}
+
+/// hpos-config with the path it was read from
+pub struct HposConfig {
+    config: Config,
+    path: PathBuf,
+}
+
+impl HposConfig {
+    /// Reads hpos-config json at `path`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config_json =
+            fs::read(path).context(format!("failed to read hpos-config {}", path.display()))?;
+        let config: Config = serde_json::from_slice(&config_json)
+            .context(format!("failed to parse hpos-config {}", path.display()))?;
+        Ok(HposConfig {
+            config,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Reads hpos-config at `hpos_config_path`
+    pub fn from_env(env: &HposEnvironment) -> Result<Self> {
+        Self::load(env.hpos_config_path()?)
+    }
+
+    fn fields(&self) -> Result<Fields<'_>> {
+        match &self.config {
+            Config::V3 {
+                device_bundle,
+                registration_code,
+                settings,
+                ..
+            }
+            | Config::V2 {
+                device_bundle,
+                registration_code,
+                settings,
+                ..
+            } => Ok(Fields {
+                device_bundle,
+                registration_code,
+                admin_email: &settings.admin.email,
+            }),
+            Config::V1 { .. } => Err(AuthError::ConfigVersionError.into()),
+        }
+    }
+
+    /// False for configs without a device bundle, which can not be used on HPOS anymore
+    pub fn is_supported(&self) -> bool {
+        self.fields().is_ok()
+    }
+
+    pub fn admin_email(&self) -> Result<&str> {
+        Ok(self.fields()?.admin_email)
+    }
+
+    pub fn registration_code(&self) -> Result<&str> {
+        Ok(self.fields()?.registration_code)
+    }
+
+    pub fn device_bundle(&self) -> Result<&str> {
+        Ok(self.fields()?.device_bundle)
+    }
+
+    /// Unlocks the device bundle with `password`
+    pub async fn signing_key(&self, password: &str) -> Result<SigningKey> {
+        unlock(self.device_bundle()?, Some(password.to_string()))
+            .await
+            .context(format!(
+                "unable to unlock the device bundle from {}",
+                self.path.display()
+            ))
+    }
+
+    /// Agent key of the holoport derived from the device bundle
+    pub async fn holoport_public_key(&self, password: &str) -> Result<AgentPubKey> {
+        self.fields()?;
+        let pub_key = hpos_config_seed_bundle_explorer::holoport_public_key(
+            &self.config,
+            Some(password.to_string()),
+        )
+        .await
+        .context(format!(
+            "unable to derive holoport key from {}",
+            self.path.display()
+        ))?;
+        Ok(AgentPubKey::from_raw_32(pub_key.to_bytes().to_vec()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Raw config, for code that needs version specific fields
+    pub fn inner(&self) -> &Config {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CONFIG: &str = "../holochain_env_setup/config/hp-primary-bzywj.json";
+    const TEST_CONFIG_V3: &str = "../holochain_env_setup/config/hp-primary-bzywj-v3.json";
+
+    /// Same device bundle and settings in both versions, so all accessors agree
+    async fn assert_test_config(config: &HposConfig) {
+        assert!(config.is_supported());
+        assert_eq!(
+            config.admin_email().unwrap(),
+            "configure.holochain@holo.host"
+        );
+        assert_eq!(
+            config.registration_code().unwrap(),
+            "configure-holochain-test-registration-code"
+        );
+        assert!(config.device_bundle().unwrap().starts_with("k6VoY3Ni"));
+
+        config.signing_key("pass").await.unwrap();
+        assert!(config.signing_key("wrong").await.is_err());
+        config.holoport_public_key("pass").await.unwrap();
+        assert!(config.holoport_public_key("wrong").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_v2_config() {
+        let config = HposConfig::load(TEST_CONFIG).unwrap();
+        assert!(matches!(config.inner(), Config::V2 { .. }));
+        assert_test_config(&config).await;
+    }
+
+    #[tokio::test]
+    async fn reads_v3_config() {
+        let config = HposConfig::load(TEST_CONFIG_V3).unwrap();
+        assert!(matches!(config.inner(), Config::V3 { .. }));
+        assert_test_config(&config).await;
+
+        let v2 = HposConfig::load(TEST_CONFIG).unwrap();
+        assert_eq!(
+            config.signing_key("pass").await.unwrap().verifying_key(),
+            v2.signing_key("pass").await.unwrap().verifying_key()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_v1_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hpos-config.json");
+        fs::write(
+            &path,
+            r#"{
+  "v1": {
+    "seed": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+    "settings": {
+      "admin": {
+        "email": "configure.holochain@holo.host",
+        "public_key": "i9/AhCiEjG07glN1Me63A1enbe+LkoHJp8pqTdz/rv4"
+      }
+    }
+  }
+}"#,
+        )
+        .unwrap();
+        let config = HposConfig::load(&path).unwrap();
+
+        assert!(!config.is_supported());
+        for err in [
+            config.admin_email().unwrap_err(),
+            config.registration_code().unwrap_err(),
+            config.device_bundle().unwrap_err(),
+            config.signing_key("pass").await.unwrap_err(),
+            config.holoport_public_key("pass").await.unwrap_err(),
+        ] {
+            assert!(
+                matches!(
+                    err.downcast_ref::<AuthError>(),
+                    Some(AuthError::ConfigVersionError)
+                ),
+                "{:?}",
+                err
+            );
+        }
+    }
+}
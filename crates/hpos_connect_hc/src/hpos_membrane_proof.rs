@@ -1,3 +1,4 @@
+use super::environment::HposEnvironment;
 use super::holo_config::Happ;
 use super::hpos_agent::{Admin, AuthError};
 use anyhow::{Context, Result};
@@ -9,15 +10,26 @@ use reqwest::Client;
 use serde::*;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::{env, fmt, fs, io::Write, path::Path};
+use std::{fmt, fs, io::Write, path::Path};
 use tracing::{debug, error, instrument};
 
+/// Error response of the membrane proof server
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize)]
-struct RegistrationError {
-    error: String,
-    isDisplayedToUser: bool,
-    info: String,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationError {
+    pub error: String,
+    pub isDisplayedToUser: bool,
+    pub info: String,
+}
+
+impl RegistrationError {
+    pub fn new(error: impl Into<String>, info: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            isDisplayedToUser: true,
+            info: info.into(),
+        }
+    }
 }
 
 impl fmt::Display for RegistrationError {
@@ -26,83 +38,77 @@ impl fmt::Display for RegistrationError {
     }
 }
 
-#[derive(Debug, Serialize)]
-struct Registration {
-    registration_code: String,
-    #[serde(serialize_with = "serialize_holochain_agent_pub_key")]
-    agent_pub_key: VerifyingKey,
-    email: String,
-    payload: RegistrationPayload,
+/// Body of `POST /membrane-proof` to the membrane proof server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration {
+    pub registration_code: String,
+    /// Holochain encoded agent key (`uhCAk...`)
+    pub agent_pub_key: String,
+    pub email: String,
+    pub payload: RegistrationPayload,
+}
+
+impl Registration {
+    fn new(admin: Admin, role: String) -> Result<Self> {
+        let key = VerifyingKey::from_bytes(admin.key.get_raw_32()[0..32].try_into()?)?;
+        Ok(Self {
+            registration_code: admin.registration_code,
+            agent_pub_key: public_key::to_holochain_encoded_agent_key(&key),
+            email: admin.email,
+            payload: RegistrationPayload { role },
+        })
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct RegistrationPayload {
-    role: String,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationPayload {
+    pub role: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct RegistrationRequest {
-    mem_proof: String,
+/// Successful response of the membrane proof server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationRequest {
+    pub mem_proof: String,
 }
 
 lazy_static! {
     static ref CLIENT: Client = Client::new();
 }
 
-fn serialize_holochain_agent_pub_key<S>(
-    public_key: &VerifyingKey,
-    serializer: S,
-) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    serializer.serialize_str(&public_key::to_holochain_encoded_agent_key(public_key))
-}
-
 /// Some Holo servers (like mem-proof-server and match-server) set READ_ONLY_MEM_PROOF=true because
 /// they need read only access to core app. In that case function returns "empty" memproof.
-/// In other cases returns memproof from a file at MEM_PROOF_PATH
+/// In other cases returns memproof from a file at `mem_proof_path`
 /// If a file does not exist then function downloads existing mem-proof for given agent
 /// from HBS server and saves it to the file
 /// Returns error if no memproof obtained, because memproof is mandatory
 /// for core-app installation
-#[instrument(skip(admin), err)]
-pub async fn get_mem_proof(admin: Admin) -> Result<MembraneProof> {
-    if &env::var("READ_ONLY_MEM_PROOF")
-        .context("Failed to read READ_ONLY_MEM_PROOF. Is it set in env?")?
-        == "true"
-    {
+#[instrument(skip(admin, env), err)]
+pub async fn get_mem_proof(admin: Admin, env: &HposEnvironment) -> Result<MembraneProof> {
+    if env.read_only_mem_proof {
         debug!("Using read-only memproof");
         return Ok(Arc::new(SerializedBytes::from(UnsafeBytes::from(vec![0]))));
     }
 
-    let memproof_path =
-        env::var("MEM_PROOF_PATH").context("Failed to read MEM_PROOF_PATH. Is it set in env?")?;
+    let memproof_path = env.mem_proof_path()?;
 
     debug!(
         "Looking for memproof in provided file at {:?}",
         memproof_path
     );
-    if let Ok(m) = load_mem_proof_from_file(&memproof_path) {
+    if let Ok(m) = load_mem_proof_from_file(memproof_path) {
         debug!("Using memproof from file");
         return Ok(m);
     }
     debug!("No Membrane Proof found locally.");
 
-    let role = env::var("HOLOFUEL_INSTANCE_ROLE")
-        .context("Failed to read HOLOFUEL_INSTANCE_ROLE. Is it set in env?")?;
-    let payload = Registration {
-        registration_code: admin.registration_code,
-        agent_pub_key: VerifyingKey::from_bytes(admin.key.get_raw_32()[0..32].try_into()?)?,
-        email: admin.email,
-        payload: RegistrationPayload { role },
-    };
+    let role = env.holofuel_instance_role()?.to_string();
+    let payload = Registration::new(admin, role)?;
 
     debug!("Getting memproof from Membrane Proof server...");
-    let (mem_proof_str, mem_proof_serialized) = download_memproof(payload).await?;
+    let (mem_proof_str, mem_proof_serialized) = download_memproof(payload, env).await?;
 
     debug!("Saving memproof to local file...");
-    save_mem_proof_to_file(&mem_proof_str, &memproof_path)?;
+    save_mem_proof_to_file(&mem_proof_str, memproof_path)?;
 
     debug!("Using memproof downloaded from Membrane Proof server");
     Ok(mem_proof_serialized)
@@ -116,24 +122,16 @@ pub async fn get_mem_proof(admin: Admin) -> Result<MembraneProof> {
 pub async fn create_vec_for_happ(
     happ: &Happ,
     mem_proof: MembraneProof,
+    env: &HposEnvironment,
 ) -> Result<HashMap<String, Arc<SerializedBytes>>> {
-    let happ_id = happ.id();
+    let happ_id = happ.id(env);
     let mut mem_proofs_vec = HashMap::new();
     if happ_id.contains("core-app") {
         mem_proofs_vec = add_core_app(mem_proof)?;
     } else if happ_id.contains("holofuel") {
-        if let Some(agent_details) = happ.agent_override_details().await? {
-            let registration_payload = Registration {
-                registration_code: agent_details.registration_code,
-                agent_pub_key: VerifyingKey::from_bytes(
-                    agent_details.key.get_raw_32()[0..32].try_into()?,
-                )?,
-                email: agent_details.email,
-                payload: RegistrationPayload {
-                    role: "holofuel".to_string(),
-                },
-            };
-            let (_, proof) = download_memproof(registration_payload).await?;
+        if let Some(agent_details) = happ.agent_override_details(env).await? {
+            let registration_payload = Registration::new(agent_details, "holofuel".to_string())?;
+            let (_, proof) = download_memproof(registration_payload, env).await?;
             mem_proofs_vec = add_holofuel(proof)?;
         } else {
             mem_proofs_vec = add_holofuel(mem_proof)?;
@@ -157,8 +155,8 @@ fn add_holofuel(mem_proof: MembraneProof) -> Result<HashMap<String, Arc<Serializ
     Ok(vec)
 }
 
-/// Reads mem-proof from a file under MEM_PROOF_PATH
-fn load_mem_proof_from_file(path: &str) -> Result<MembraneProof> {
+/// Reads mem-proof from a file under `mem_proof_path`
+fn load_mem_proof_from_file(path: &Path) -> Result<MembraneProof> {
     use std::str;
     let file = fs::read(path).context("failed to open file")?;
     let mem_proof_str = str::from_utf8(&file)?;
@@ -168,20 +166,22 @@ fn load_mem_proof_from_file(path: &str) -> Result<MembraneProof> {
     Ok(mem_proof_serialized)
 }
 
-/// Saves mem-proof to a file under MEM_PROOF_PATH
-fn save_mem_proof_to_file(mem_proof: &str, path: &str) -> Result<()> {
+/// Saves mem-proof to a file under `mem_proof_path`
+fn save_mem_proof_to_file(mem_proof: &str, path: &Path) -> Result<()> {
     let mut file = fs::File::create(path)?;
-    file.write_all(mem_proof.as_bytes())
-        .context(format!("Failed writing memproof to file {}", path))?;
+    file.write_all(mem_proof.as_bytes()).context(format!(
+        "Failed writing memproof to file {}",
+        path.display()
+    ))?;
     Ok(())
 }
 
-/// Deletes mem-proof file located at MEM_PROOF_PATH
+/// Deletes mem-proof file located at `mem_proof_path`
 /// if it does exist
-pub fn delete_mem_proof_file() -> Result<()> {
-    if let Ok(path) = env::var("MEM_PROOF_PATH") {
-        if Path::new(&path).exists() {
-            fs::remove_file(&path)?;
+pub fn delete_mem_proof_file(env: &HposEnvironment) -> Result<()> {
+    if let Some(path) = &env.mem_proof_path {
+        if path.exists() {
+            fs::remove_file(path)?;
         }
     }
     Ok(())
@@ -192,12 +192,9 @@ pub fn delete_mem_proof_file() -> Result<()> {
 /// from HBS server and returns as a string
 async fn download_memproof(
     registration_payload: Registration,
+    env: &HposEnvironment,
 ) -> Result<(String, Arc<SerializedBytes>)> {
-    let url = format!(
-        "{}/membrane-proof",
-        env::var("MEM_PROOF_SERVER_URL")
-            .context("Failed to read MEM_PROOF_SERVER_URL. Is it set in env?")?
-    );
+    let url = format!("{}/membrane-proof", env.mem_proof_server_url()?);
     let resp = CLIENT.post(url).json(&registration_payload).send().await?;
     match resp.error_for_status_ref() {
         Ok(_) => {
@@ -212,7 +209,10 @@ async fn download_memproof(
         }
         Err(e) => {
             error!("Error: {:?}", e);
-            Err(AuthError::RegistrationError(e.to_string()).into())
+            let body = resp.text().await.unwrap_or_default();
+            let rejection = serde_json::from_str(&body)
+                .unwrap_or_else(|_| RegistrationError::new(e.to_string(), body));
+            Err(AuthError::RegistrationError(rejection).into())
         }
     }
 }
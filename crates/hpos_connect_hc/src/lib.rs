@@ -4,7 +4,8 @@
 //! It provides HolofuelAgent that connects to the holofuel instance
 //! and provide wrapper to signed zome calls.
 //!
-//! ### Expected Environment vars
+//! ### Environment
+//! Agents are configured with an `HposEnvironment`, usually read from these env vars:
 //! ```
 //! // HOLOCHAIN_DEFAULT_PASSWORD=<password to unlock holochain conductor>
 //! // CORE_HAPP_FILE=<path to a config.json file used for the configure-holochain service>
@@ -12,29 +13,24 @@
 //! // LAIR_CONNECTION_URL=<string uri to lcoation of lair keystore> *OPTIONAL*
 //! // LAIR_WORKING_DIR=<path to lair-keystore working dir> *OPTIONAL is LAIR_CONNECTION_URL is not provided*
 //! ```
+//! See `environment` for all of them.
 //! ### Example:
 //!
 //! ```rust
-//! use hpos_hc_connect::{app_connection::CoreAppRoleName, hf_agent::HfAgent, holofuel_types::Ledger};
-//! use holochain_types::prelude::{FunctionName, ZomeName};
+//! use hpos_hc_connect::{hf_agent::HfAgent, holofuel_types::Ledger, HposEnvironment};
 //! pub async fn test() {
-//!     let mut agent = HfAgent::spawn(None).await.unwrap();
+//!     let mut agent = HfAgent::spawn(None, &HposEnvironment::from_env()).await.unwrap();
 //!
-//!    let ledger: Ledger = agent
-//!    .app
-//!    .zome_call_typed(
-//!        CoreAppRoleName::Holofuel.into(),
-//!        ZomeName::from("transactor"),
-//!        FunctionName::from("get_ledger"),
-//!        (),
-//!    )
-//!    .await
-//!    .unwrap();
+//!     let ledger: Ledger = agent.ledger().await.unwrap();
 //! }
 //! ```
 
 pub mod admin_ws;
 pub mod app_connection;
+pub mod clock;
+pub mod download_cache;
+pub mod environment;
+pub mod hbs;
 pub mod hf_agent;
 pub mod hha_agent;
 pub mod hha_types;
@@ -42,8 +38,15 @@ pub mod holo_config;
 pub mod holofuel_types;
 pub mod host_keys;
 pub mod hpos_agent;
+pub mod hpos_config;
 pub mod hpos_membrane_proof;
+pub mod output;
+pub mod pay;
+pub mod signals;
+pub mod sl_clone_manager;
 pub mod sl_utils;
 pub mod utils;
 pub use admin_ws::AdminWebsocket;
 pub use app_connection::AppConnection;
+pub use environment::HposEnvironment;
+pub use signals::{SignalFilter, SignalStream};
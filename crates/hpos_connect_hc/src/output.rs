@@ -0,0 +1,53 @@
+//! Output format of the `hf` and `core_app_cli` commands
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Format of what an action prints to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human readable text between `===================` banners
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            _ => Err(anyhow!(
+                "unknown output format {}, expected one of: table, json, yaml",
+                s
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Prints serialized `value` when a structured format was requested and returns true.
+    /// For `Table` prints nothing and returns false, so the caller prints its own text.
+    pub fn print<T: Serialize>(self, value: &T) -> Result<bool> {
+        match self {
+            Self::Table => Ok(false),
+            Self::Json => {
+                println!("{}", serde_json::to_string_pretty(value)?);
+                Ok(true)
+            }
+            Self::Yaml => {
+                print!("{}", serde_yaml::to_string(value)?);
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn is_structured(self) -> bool {
+        self != Self::Table
+    }
+}
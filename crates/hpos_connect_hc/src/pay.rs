@@ -0,0 +1,281 @@
+//! Paying pending invoices and acting on single transactions, shared by the `pay`, `accept`,
+//! `decline`, `cancel` and `complete` commands of `hf` and `core_app_cli`.
+//!
+//! A transaction that was accepted but could not be completed right away is completed later
+//! by the scheduler, so failed completions are reported but do not fail a command.
+
+use crate::hf_agent::HfAgent;
+use crate::holofuel_types::{parse_fuel, CounterSigningResponse, Pending, TransactionFilter};
+use crate::output::OutputFormat;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate};
+use holochain_types::prelude::{AgentPubKeyB64, EntryHashB64, Timestamp};
+use serde::Serialize;
+use std::io::{self, BufRead, Write};
+use structopt::StructOpt;
+
+/// Selects which pending invoices get paid
+#[derive(Debug, StructOpt)]
+pub struct PayOpt {
+    /// Only invoices from this agent (uhCAk...)
+    #[structopt(long)]
+    pub counterparty: Option<String>,
+    /// Only invoices of at most this amount
+    #[structopt(long)]
+    pub max_amount: Option<String>,
+    /// Only invoices expiring before this date (YYYY-MM-DD or RFC 3339)
+    #[structopt(long)]
+    pub expiring_before: Option<String>,
+    /// Pay without asking for confirmation
+    #[structopt(long, short)]
+    pub yes: bool,
+}
+
+impl PayOpt {
+    pub fn filter(&self) -> Result<TransactionFilter> {
+        let counterparty = self
+            .counterparty
+            .as_ref()
+            .map(|key| {
+                AgentPubKeyB64::from_b64_str(key)
+                    .map_err(|e| anyhow!("invalid counterparty {}: {:?}", key, e))
+            })
+            .transpose()?;
+        let max_amount = self.max_amount.as_deref().map(parse_fuel).transpose()?;
+        let expiring_before = self
+            .expiring_before
+            .as_deref()
+            .map(parse_date)
+            .transpose()?;
+
+        Ok(TransactionFilter {
+            counterparty,
+            max_amount,
+            expiring_before,
+        })
+    }
+}
+
+/// Structured output of `pay`, one per selected invoice
+#[derive(Serialize, Default)]
+struct Payment {
+    invoice: Option<EntryHashB64>,
+    accepted: Option<EntryHashB64>,
+    completion: Option<CounterSigningResponse>,
+    error: Option<String>,
+}
+
+/// Structured output of `accept`. The accepted hash is printed even when completing the
+/// transaction fails, with the failure in `completion_error`.
+#[derive(Serialize)]
+struct Accepted {
+    accepted: EntryHashB64,
+    completion: Option<CounterSigningResponse>,
+    completion_error: Option<String>,
+}
+
+pub fn parse_id(id: &str) -> Result<EntryHashB64> {
+    EntryHashB64::from_b64_str(id).map_err(|e| anyhow!("invalid transaction id {}: {:?}", id, e))
+}
+
+/// Parses a date given as YYYY-MM-DD (midnight UTC) or RFC 3339
+pub fn parse_date(date: &str) -> Result<Timestamp> {
+    let micros = match DateTime::parse_from_rfc3339(date) {
+        Ok(date_time) => date_time.timestamp_micros(),
+        Err(_) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .context(format!("invalid date {}", date))?
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc()
+            .timestamp_micros(),
+    };
+    Ok(Timestamp::from_micros(micros))
+}
+
+/// Prompt goes to stderr so it does not end up in structured output
+fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Human readable outcome of `complete_transactions`
+pub fn describe_countersigning(response: &CounterSigningResponse) -> String {
+    match response {
+        CounterSigningResponse::Successful(hash) => {
+            format!("Transaction completed: {:?}", hash)
+        }
+        CounterSigningResponse::UnableToReachCounterparty(e) => format!(
+            "Counterparty is offline, transaction will be completed by the scheduler once it is reachable ({})",
+            e
+        ),
+        CounterSigningResponse::FeeDropOff(e) => {
+            format!("Fees are being dropped off first, try again later ({})", e)
+        }
+        CounterSigningResponse::TimeDelayWait(e) => format!(
+            "Countersigning has to wait before it is retried, transaction will be completed by the scheduler ({})",
+            e
+        ),
+    }
+}
+
+/// Accepts and completes all pending invoices of `agent` matching given filters
+pub async fn pay_invoices(agent: &mut HfAgent, opt: PayOpt, output: OutputFormat) -> Result<()> {
+    let filter = opt.filter()?;
+
+    let txs: Pending = agent.pending().await?;
+    let invoices = filter.apply(&txs.invoice_pending)?;
+
+    if output.is_structured() {
+        for tx in &invoices {
+            eprintln!("Invoice {:?} amount: {}", tx.id, tx.amount);
+        }
+    } else {
+        println!("===================");
+        if invoices.is_empty() {
+            println!("No pending invoices matching given filters");
+            println!("===================");
+            return Ok(());
+        }
+        println!("Invoices to pay: ");
+        for tx in &invoices {
+            println!(
+                "{:?} amount: {} counterparty: {:?} expires: {:?}",
+                tx.id, tx.amount, tx.counterparty, tx.expiration_date
+            );
+        }
+        println!("===================");
+    }
+
+    let mut payments = vec![];
+    if !invoices.is_empty() && (opt.yes || confirm(&format!("Pay {} invoice(s)?", invoices.len()))?)
+    {
+        for tx in invoices {
+            let mut payment = Payment {
+                invoice: Some(tx.id.clone()),
+                ..Default::default()
+            };
+            match agent.accept(tx.id.clone(), None).await {
+                Ok(hash) => {
+                    payment.accepted = Some(hash.clone());
+                    match agent.complete_transactions(hash).await {
+                        Ok(response) => payment.completion = Some(response),
+                        Err(e) => payment.error = Some(format!("{:?}", e)),
+                    }
+                }
+                Err(e) => payment.error = Some(format!("{:?}", e)),
+            }
+            payments.push(payment);
+        }
+    } else if !output.is_structured() {
+        println!("Nothing was paid");
+    }
+
+    // completion failures are retried by the scheduler, only failed acceptances fail the command
+    let failed = payments.iter().filter(|p| p.accepted.is_none()).count();
+    let total = payments.len();
+
+    if !output.print(&payments)? {
+        for payment in payments {
+            match (payment.accepted, payment.completion, payment.error) {
+                (Some(hash), Some(response), _) => {
+                    println!("Accepted tx: {:?}", hash);
+                    println!("{}", describe_countersigning(&response));
+                }
+                (Some(hash), None, Some(e)) => {
+                    println!("Accepted tx: {:?}", hash);
+                    println!("Completion will be retried by your scheduler: {}", e);
+                }
+                (_, _, e) => println!("Failed to accept tx {:?}: {:?}", payment.invoice, e),
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} invoice(s) could not be accepted",
+            failed,
+            total
+        ));
+    }
+    Ok(())
+}
+
+/// Accepts a pending transaction and tries to complete it right away
+pub async fn accept(agent: &mut HfAgent, id: &str, output: OutputFormat) -> Result<()> {
+    let hash = agent.accept(parse_id(id)?, None).await?;
+
+    let mut accepted = Accepted {
+        accepted: hash.clone(),
+        completion: None,
+        completion_error: None,
+    };
+    match agent.complete_transactions(hash).await {
+        Ok(response) => accepted.completion = Some(response),
+        Err(e) => accepted.completion_error = Some(format!("{:#}", e)),
+    }
+
+    if output.print(&accepted)? {
+        return Ok(());
+    }
+
+    println!("===================");
+    println!("Accepted tx: {:?}", accepted.accepted);
+    match (accepted.completion, accepted.completion_error) {
+        (Some(response), _) => println!("{}", describe_countersigning(&response)),
+        (None, e) => println!(
+            "Completion will be retried by your scheduler: {}",
+            e.unwrap_or_default()
+        ),
+    }
+    println!("===================");
+
+    Ok(())
+}
+
+/// Declines a pending transaction
+pub async fn decline(agent: &mut HfAgent, id: &str, output: OutputFormat) -> Result<()> {
+    let hash = agent.decline(parse_id(id)?).await?;
+
+    if output.print(&hash)? {
+        return Ok(());
+    }
+
+    println!("===================");
+    println!("Declined tx: {:?}", hash);
+    println!("===================");
+
+    Ok(())
+}
+
+/// Cancels a transaction created by this agent
+pub async fn cancel(agent: &mut HfAgent, id: &str, output: OutputFormat) -> Result<()> {
+    let hash = agent.cancel(parse_id(id)?).await?;
+
+    if output.print(&hash)? {
+        return Ok(());
+    }
+
+    println!("===================");
+    println!("Canceled tx: {:?}", hash);
+    println!("===================");
+
+    Ok(())
+}
+
+/// Retries countersigning of an already accepted transaction
+pub async fn complete(agent: &mut HfAgent, id: &str, output: OutputFormat) -> Result<()> {
+    let response = agent.complete_transactions(parse_id(id)?).await?;
+
+    if output.print(&response)? {
+        return Ok(());
+    }
+
+    println!("===================");
+    println!("{}", describe_countersigning(&response));
+    println!("===================");
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use futures::Stream;
+use holochain_types::prelude::{CellId, RoleName, ZomeName};
+use holochain_types::signal::Signal;
+use lair_keystore_api::dependencies::tokio::sync::broadcast::{self, error::RecvError};
+use serde::de::DeserializeOwned;
+use std::pin::Pin;
+use tracing::warn;
+
+/// Number of signals buffered for each subscriber before the slowest one starts lagging
+pub const SIGNAL_CHANNEL_CAPACITY: usize = 1024;
+
+/// Stream of decoded signals returned by `AppConnection::subscribe_signals`
+pub type SignalStream = Pin<Box<dyn Stream<Item = Signal> + Send>>;
+
+/// Describes which signals a subscriber is interested in.
+/// Empty filter lets through every signal, including system signals.
+/// As soon as any field is set only app signals matching all the set fields are passed.
+#[derive(Debug, Clone, Default)]
+pub struct SignalFilter {
+    pub role_name: Option<RoleName>,
+    pub cell_id: Option<CellId>,
+    pub zome_name: Option<ZomeName>,
+}
+
+impl SignalFilter {
+    pub fn role(role_name: impl Into<RoleName>) -> Self {
+        Self {
+            role_name: Some(role_name.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn cell(cell_id: CellId) -> Self {
+        Self {
+            cell_id: Some(cell_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn zome(mut self, zome_name: impl Into<ZomeName>) -> Self {
+        self.zome_name = Some(zome_name.into());
+        self
+    }
+}
+
+/// SignalFilter with role name already resolved into the list of cells
+/// (provisioned and cloned) that belong to that role
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResolvedSignalFilter {
+    pub cells: Option<Vec<CellId>>,
+    pub zome_name: Option<ZomeName>,
+}
+
+impl ResolvedSignalFilter {
+    pub fn matches(&self, signal: &Signal) -> bool {
+        if self.cells.is_none() && self.zome_name.is_none() {
+            return true;
+        }
+        match signal {
+            Signal::App {
+                cell_id, zome_name, ..
+            } => {
+                self.cells.as_ref().map_or(true, |c| c.contains(cell_id))
+                    && self.zome_name.as_ref().map_or(true, |z| z == zome_name)
+            }
+            _ => false,
+        }
+    }
+}
+
+pub(crate) fn signal_stream(
+    rx: broadcast::Receiver<Signal>,
+    filter: ResolvedSignalFilter,
+) -> SignalStream {
+    Box::pin(futures::stream::unfold(
+        (rx, filter),
+        |(mut rx, filter)| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(signal) => {
+                        if filter.matches(&signal) {
+                            return Some((signal, (rx, filter)));
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("signal subscriber is lagging, skipped {} signals", skipped)
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    ))
+}
+
+/// Decodes payload of an app signal into a type emitted by the zome.
+/// Returns None for system signals
+pub fn app_signal_payload<T: DeserializeOwned + std::fmt::Debug>(
+    signal: &Signal,
+) -> Option<Result<T>> {
+    match signal {
+        Signal::App { signal, .. } => Some(
+            signal
+                .clone()
+                .into_inner()
+                .decode()
+                .map_err(|e| anyhow::anyhow!("failed to decode app signal payload: {:?}", e)),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_types::prelude::{AgentPubKey, AppSignal, DnaHash, ExternIO};
+
+    fn app_signal(cell_id: CellId, zome: &str) -> Signal {
+        Signal::App {
+            cell_id,
+            zome_name: zome.into(),
+            signal: AppSignal::new(ExternIO::encode("new invoice").unwrap()),
+        }
+    }
+
+    #[test]
+    fn filter_by_cell_and_zome() {
+        let hf_cell = CellId::new(
+            DnaHash::from_raw_36(vec![1; 36]),
+            AgentPubKey::from_raw_36(vec![2; 36]),
+        );
+        let hha_cell = CellId::new(
+            DnaHash::from_raw_36(vec![3; 36]),
+            AgentPubKey::from_raw_36(vec![2; 36]),
+        );
+
+        let filter = ResolvedSignalFilter {
+            cells: Some(vec![hf_cell.clone()]),
+            zome_name: Some("transactor".into()),
+        };
+
+        assert!(filter.matches(&app_signal(hf_cell.clone(), "transactor")));
+        assert!(!filter.matches(&app_signal(hf_cell, "profile")));
+        assert!(!filter.matches(&app_signal(hha_cell.clone(), "transactor")));
+        assert!(ResolvedSignalFilter::default().matches(&app_signal(hha_cell, "hha")));
+    }
+
+    #[test]
+    fn decode_app_signal_payload() {
+        let cell_id = CellId::new(
+            DnaHash::from_raw_36(vec![1; 36]),
+            AgentPubKey::from_raw_36(vec![2; 36]),
+        );
+        let payload: String = app_signal_payload(&app_signal(cell_id, "transactor"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload, "new invoice");
+    }
+}
@@ -0,0 +1,349 @@
+//! Lifecycle of service-logger clone cells.
+//!
+//! Every time-bucket of service-logger is logged into its own clone cell named by `sl_clone_name`.
+//! `SlCloneManager::run` creates the clone of the next bucket `minutes_before_bucket_to_clone`
+//! ahead of time, and disables and deletes clones of buckets older than `buckets_to_keep`.
+//! Decisions are made by `plan` from the time of its `Clock` and existing clones only, so running
+//! it again at the same time does nothing new, and missed runs are caught up on the next one.
+
+use crate::clock::{Clock, SystemClock};
+use crate::sl_utils::{
+    sl_bucket_start, sl_clone_name, sl_clone_name_spec, sl_is_outside_retention,
+    sl_serialized_props, time_bucket_from_date, SlBucketMigration, SlCloneSpec, SlDnaProperties,
+    SL_BUCKETS_TO_KEEP, SL_BUCKET_SIZE_DAYS, SL_MINUTES_BEFORE_BUCKET_TO_CLONE,
+};
+use crate::{AdminWebsocket, AppConnection};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use holochain_types::app::{
+    CreateCloneCellPayload, DeleteCloneCellPayload, DisableCloneCellPayload,
+};
+use holochain_types::prelude::{CloneCellId, CloneId, ClonedCell, DnaModifiersOpt, RoleName};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{info, instrument, warn};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlClonePolicy {
+    pub days_in_bucket: u32,
+    pub minutes_before_bucket_to_clone: i64,
+    pub buckets_to_keep: u32,
+    /// Previous bucket size change, so clones of the old size get expired as well.
+    /// Its `to_days` should be `days_in_bucket`.
+    pub migration: Option<SlBucketMigration>,
+}
+
+impl Default for SlClonePolicy {
+    fn default() -> Self {
+        Self {
+            days_in_bucket: SL_BUCKET_SIZE_DAYS,
+            minutes_before_bucket_to_clone: SL_MINUTES_BEFORE_BUCKET_TO_CLONE,
+            buckets_to_keep: SL_BUCKETS_TO_KEEP,
+            migration: None,
+        }
+    }
+}
+
+/// Existing service-logger clone, as far as planning is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlClone {
+    pub name: String,
+    pub clone_id: CloneId,
+    pub enabled: bool,
+}
+
+impl From<&ClonedCell> for SlClone {
+    fn from(cell: &ClonedCell) -> Self {
+        Self {
+            name: cell.name.clone(),
+            clone_id: cell.clone_id.clone(),
+            enabled: cell.enabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SlCloneAction {
+    Create { name: String, time_bucket: u32 },
+    Disable { name: String, clone_id: CloneId },
+    Delete { name: String, clone_id: CloneId },
+}
+
+impl SlCloneAction {
+    pub fn name(&self) -> &str {
+        match self {
+            SlCloneAction::Create { name, .. }
+            | SlCloneAction::Disable { name, .. }
+            | SlCloneAction::Delete { name, .. } => name,
+        }
+    }
+}
+
+/// Returns actions bringing `clones` in line with `policy` at time `now`.
+/// Clones of other bucket sizes than the current and migrated from one, or with names not made by
+/// `sl_clone_name` are left alone.
+pub fn plan(policy: &SlClonePolicy, now: DateTime<Utc>, clones: &[SlClone]) -> Vec<SlCloneAction> {
+    let days_in_bucket = policy.days_in_bucket;
+    let current_bucket = time_bucket_from_date(now, days_in_bucket);
+    let upcoming_bucket = time_bucket_from_date(
+        now + Duration::minutes(policy.minutes_before_bucket_to_clone),
+        days_in_bucket,
+    );
+
+    let mut actions = vec![];
+    let mut needed = vec![current_bucket];
+    if upcoming_bucket != current_bucket {
+        needed.push(upcoming_bucket);
+    }
+    for time_bucket in needed {
+        let name = sl_clone_name(SlCloneSpec {
+            days_in_bucket,
+            time_bucket,
+        });
+        if !clones.iter().any(|c| c.name == name) {
+            actions.push(SlCloneAction::Create { name, time_bucket });
+        }
+    }
+
+    let mut expired: Vec<(DateTime<Utc>, &SlClone)> = clones
+        .iter()
+        .filter_map(|clone| {
+            let spec = sl_clone_name_spec(&clone.name).ok()?;
+            let is_expired = match &policy.migration {
+                Some(migration) => {
+                    migration.is_outside_retention(&spec, now, policy.buckets_to_keep)
+                }
+                None => sl_is_outside_retention(&spec, now, days_in_bucket, policy.buckets_to_keep),
+            };
+            is_expired.then_some((sl_bucket_start(&spec), clone))
+        })
+        .collect();
+    expired.sort_by(|(a, a_clone), (b, b_clone)| (a, &a_clone.name).cmp(&(b, &b_clone.name)));
+    for (_, clone) in expired {
+        if clone.enabled {
+            actions.push(SlCloneAction::Disable {
+                name: clone.name.clone(),
+                clone_id: clone.clone_id.clone(),
+            });
+        }
+        actions.push(SlCloneAction::Delete {
+            name: clone.name.clone(),
+            clone_id: clone.clone_id.clone(),
+        });
+    }
+
+    actions
+}
+
+/// What a single `SlCloneManager::run` did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SlCloneReport {
+    pub time_bucket: u32,
+    pub applied: Vec<SlCloneAction>,
+    /// Actions that failed with their error, later actions on the same clone are skipped
+    pub failed: Vec<(SlCloneAction, String)>,
+}
+
+impl SlCloneReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+pub struct SlCloneManager {
+    app: AppConnection,
+    admin_ws: AdminWebsocket,
+    role_name: RoleName,
+    properties: SlDnaProperties,
+    policy: SlClonePolicy,
+    clock: Arc<dyn Clock>,
+}
+
+impl SlCloneManager {
+    /// Manages clones of `role_name` in `app`. New clones get `properties`
+    /// with `bucket_size` and `time_bucket` set to their bucket.
+    pub fn new(
+        app: AppConnection,
+        admin_ws: AdminWebsocket,
+        role_name: RoleName,
+        properties: SlDnaProperties,
+    ) -> Self {
+        Self {
+            app,
+            admin_ws,
+            role_name,
+            properties,
+            policy: SlClonePolicy::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Manages clones of `role_name` in `app` with the properties and bucket size
+    /// read from the dna modifiers of its provisioned cell
+    pub async fn for_installed_app(
+        mut app: AppConnection,
+        admin_ws: AdminWebsocket,
+        role_name: RoleName,
+    ) -> Result<Self> {
+        let modifiers = app.dna_modifiers(role_name.clone()).await?;
+        let properties = SlDnaProperties::from_dna_modifiers(&modifiers)?;
+        let policy = SlClonePolicy {
+            days_in_bucket: properties.days_in_bucket(),
+            ..Default::default()
+        };
+        Ok(Self::new(app, admin_ws, role_name, properties).with_policy(policy))
+    }
+
+    pub fn with_policy(mut self, policy: SlClonePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Switches to buckets of `migration.to_days`, expiring clones of `migration.from_days`
+    /// once all of their logs are out of retention
+    pub fn with_migration(mut self, migration: SlBucketMigration) -> Self {
+        self.policy.days_in_bucket = migration.to_days;
+        self.policy.migration = Some(migration);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Current clones of the managed role
+    pub async fn clones(&mut self) -> Result<Vec<SlClone>> {
+        self.app.invalidate_cell_info();
+        let cells = self.app.cloned_cells(self.role_name.clone()).await?;
+        Ok(cells.iter().map(SlClone::from).collect())
+    }
+
+    /// Plans clone changes for the current time of the clock and applies them
+    #[instrument(skip(self), fields(app_id = %self.app.id(), role = %self.role_name), err)]
+    pub async fn run(&mut self) -> Result<SlCloneReport> {
+        let now = self.clock.now();
+        let clones = self.clones().await?;
+        let mut report = SlCloneReport {
+            time_bucket: time_bucket_from_date(now, self.policy.days_in_bucket),
+            ..Default::default()
+        };
+
+        for action in plan(&self.policy, now, &clones) {
+            if report.failed.iter().any(|(f, _)| f.name() == action.name()) {
+                continue;
+            }
+            match self.apply(&action).await {
+                Ok(()) => {
+                    info!("service-logger clone action done: {:?}", action);
+                    report.applied.push(action);
+                }
+                Err(e) => {
+                    warn!("service-logger clone action {:?} failed: {:?}", action, e);
+                    report.failed.push((action, format!("{:#}", e)));
+                }
+            }
+        }
+
+        self.app.invalidate_cell_info();
+        Ok(report)
+    }
+
+    async fn apply(&mut self, action: &SlCloneAction) -> Result<()> {
+        match action {
+            SlCloneAction::Create { name, time_bucket } => {
+                let properties = SlDnaProperties {
+                    bucket_size: Some(self.policy.days_in_bucket),
+                    time_bucket: Some(*time_bucket),
+                    ..self.properties.clone()
+                };
+                self.app
+                    .create_clone(CreateCloneCellPayload {
+                        role_name: self.role_name.clone(),
+                        modifiers: DnaModifiersOpt::none()
+                            .with_properties(sl_serialized_props(&properties)),
+                        membrane_proof: None,
+                        name: Some(name.clone()),
+                    })
+                    .await?;
+            }
+            SlCloneAction::Disable { clone_id, .. } => {
+                self.app
+                    .disable_clone(DisableCloneCellPayload {
+                        clone_cell_id: CloneCellId::CloneId(clone_id.clone()),
+                    })
+                    .await?;
+            }
+            SlCloneAction::Delete { clone_id, .. } => {
+                self.admin_ws
+                    .delete_clone(DeleteCloneCellPayload {
+                        app_id: self.app.id(),
+                        clone_cell_id: CloneCellId::CloneId(clone_id.clone()),
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn clone(time_bucket: u32, index: u32, enabled: bool) -> SlClone {
+        SlClone {
+            name: format!("14.{}", time_bucket),
+            clone_id: CloneId::new(&"servicelogger".into(), index),
+            enabled,
+        }
+    }
+
+    #[test]
+    fn plans_next_bucket_and_expiry() {
+        let policy = SlClonePolicy {
+            days_in_bucket: 14,
+            minutes_before_bucket_to_clone: 9,
+            buckets_to_keep: 2,
+            migration: None,
+        };
+        // 2024-03-11 is day 70 since Holo epoch, the first day of bucket 5
+        let midday = Utc.with_ymd_and_hms(2024, 3, 11, 12, 0, 0).unwrap();
+        let before_next = Utc.with_ymd_and_hms(2024, 3, 24, 23, 55, 0).unwrap();
+
+        let clones = vec![clone(2, 0, true), clone(3, 1, false), clone(5, 2, true)];
+        let other_size = SlClone {
+            name: "7.1".to_string(),
+            clone_id: CloneId::new(&"servicelogger".into(), 3),
+            enabled: true,
+        };
+        let mut with_other = clones.clone();
+        with_other.push(other_size);
+
+        assert_eq!(
+            plan(&policy, midday, &with_other),
+            vec![
+                SlCloneAction::Disable {
+                    name: "14.2".into(),
+                    clone_id: clones[0].clone_id.clone()
+                },
+                SlCloneAction::Delete {
+                    name: "14.2".into(),
+                    clone_id: clones[0].clone_id.clone()
+                },
+            ]
+        );
+
+        let kept = vec![clone(3, 1, false), clone(5, 2, true)];
+        assert!(plan(&policy, midday, &kept).is_empty());
+        assert_eq!(
+            plan(&policy, before_next, &kept),
+            vec![SlCloneAction::Create {
+                name: "14.6".into(),
+                time_bucket: 6
+            }]
+        );
+    }
+}
@@ -1,15 +1,15 @@
-use anyhow::{anyhow, Result};
+use crate::clock::Clock;
+use anyhow::{anyhow, Context, Result};
 use chrono::DateTime;
 use chrono::Datelike;
 use chrono::Duration;
-use chrono::Local;
 use chrono::NaiveDate;
 use chrono::Timelike;
 use chrono::Utc;
 use const_env::from_env;
 use holochain_types::dna::{ActionHashB64, AgentPubKeyB64, DnaHashB64};
-use holochain_types::prelude::YamlProperties;
 use holochain_types::prelude::{holochain_serial, SerializedBytes};
+use holochain_types::prelude::{DnaModifiers, YamlProperties};
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -27,7 +27,8 @@ use serde::Serialize;
 // and thus stores the value in its properties.  This is so that different apps could have different
 // log rotation schedules.  But that is not currently implemented in the `sl-check` endpoint of
 // `hpos-api-rust`.  But this is why all of these functions take the `days_in_bucket`` parameter
-// rather than using the global directly.
+// rather than using the global directly. `SlDnaProperties::from_dna_modifiers` reads an app's own
+// bucket size back from its cell, and `SlBucketMigration` covers apps whose bucket size changed.
 #[from_env]
 pub const SL_BUCKET_SIZE_DAYS: u32 = 14;
 #[from_env]
@@ -36,10 +37,13 @@ pub const SL_MINUTES_BEFORE_BUCKET_TO_CLONE: i64 = 9;
 pub const SL_DELETING_LOG_WINDOW_SIZE_MINUTES: u32 = 10;
 #[from_env]
 pub const HOLO_EPOCH_YEAR: u16 = 2024;
+// Number of past time-buckets whose service-logger clones are kept besides the current one
+#[from_env]
+pub const SL_BUCKETS_TO_KEEP: u32 = 2;
 
 /// This is the structure of the DNA properties for service logger instances
 /// This struct must be kept up to date with the service-logger implementation
-#[derive(Serialize, Deserialize, Debug, SerializedBytes)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, SerializedBytes)]
 pub struct SlDnaProperties {
     pub cron_schedule: Option<String>,
     pub bound_happ_id: Option<ActionHashB64>,
@@ -50,14 +54,41 @@ pub struct SlDnaProperties {
     pub time_bucket: Option<u32>,
 }
 
+impl SlDnaProperties {
+    /// Reads the properties back from the dna modifiers of a service-logger cell,
+    /// cells installed without properties get the defaults
+    pub fn from_dna_modifiers(modifiers: &DnaModifiers) -> Result<Self> {
+        let bytes = modifiers.properties.bytes();
+        if bytes.is_empty() {
+            return Ok(Self::default());
+        }
+        let properties: Option<Self> = rmp_serde::from_slice(bytes)
+            .context("failed to decode service-logger dna properties")?;
+        Ok(properties.unwrap_or_default())
+    }
+
+    /// Bucket size of the app, `SL_BUCKET_SIZE_DAYS` if it has none set
+    pub fn days_in_bucket(&self) -> u32 {
+        self.bucket_size.unwrap_or(SL_BUCKET_SIZE_DAYS)
+    }
+
+    /// Spec of the app's clone logging at `date`
+    pub fn clone_spec_at(&self, date: DateTime<Utc>) -> SlCloneSpec {
+        let days_in_bucket = self.days_in_bucket();
+        SlCloneSpec {
+            days_in_bucket,
+            time_bucket: time_bucket_from_date(date, days_in_bucket),
+        }
+    }
+}
+
 pub fn sl_serialized_props(props: &SlDnaProperties) -> YamlProperties {
     YamlProperties::new(serde_yaml::to_value(props).expect("should serialize"))
 }
 
 /// given the date in UTC timezone, return the current bucket
 pub fn time_bucket_from_date(date: DateTime<Utc>, days_in_bucket: u32) -> u32 {
-    let epoch_start = NaiveDate::from_ymd_opt(HOLO_EPOCH_YEAR.into(), 1, 1).unwrap();
-    let days_since_epoch: u32 = (date.num_days_from_ce() - epoch_start.num_days_from_ce())
+    let days_since_epoch: u32 = (date.num_days_from_ce() - holo_epoch().num_days_from_ce())
         .try_into()
         .expect("now should always be after Holo epoch");
     days_since_epoch / days_in_bucket
@@ -66,19 +97,8 @@ pub fn time_bucket_from_date(date: DateTime<Utc>, days_in_bucket: u32) -> u32 {
 /// returns the current time bucket in a deterministic way so that all code elements
 /// that rely on logging can know the service logger instance with which they should be
 /// interacting
-pub fn sl_get_current_time_bucket(days_in_bucket: u32) -> u32 {
-    let now_utc = Utc::now();
-    if std::env::var("IS_TEST_ENV").is_ok() {
-        if let Ok(test_time_bucket_str) = std::env::var("SL_TEST_TIME_BUCKET") {
-            test_time_bucket_str
-                .parse::<u32>()
-                .expect("wanted an int for SL_TEST_TIME_BUCKET")
-        } else {
-            10
-        }
-    } else {
-        time_bucket_from_date(now_utc, days_in_bucket)
-    }
+pub fn sl_get_current_time_bucket(clock: &dyn Clock, days_in_bucket: u32) -> u32 {
+    time_bucket_from_date(clock.now(), days_in_bucket)
 }
 
 /// returns whether we are within `minutes_before` minutes of the next time bucket
@@ -86,34 +106,194 @@ pub fn sl_get_current_time_bucket(days_in_bucket: u32) -> u32 {
 /// note that `days_in_bucket` and `minutes_before` are passed in as parameters by
 /// the caller as part of future-proofing, but currently they all just use the constants
 /// provided above.
-pub fn sl_within_min_of_next_time_bucket(days_in_bucket: u32, minutes_before: i64) -> bool {
-    if let Ok(val) = std::env::var("SL_TEST_IS_BEFORE_NEXT_BUCKET") {
-        if val == "true" {
-            return true;
-        }
-    }
-    let now_utc = Utc::now();
+pub fn sl_within_min_of_next_time_bucket(
+    clock: &dyn Clock,
+    days_in_bucket: u32,
+    minutes_before: i64,
+) -> bool {
+    let now_utc = clock.now();
     let current_time_bucket = time_bucket_from_date(now_utc, days_in_bucket);
     let time_bucket_soon =
         time_bucket_from_date(now_utc + Duration::minutes(minutes_before), days_in_bucket);
     current_time_bucket != time_bucket_soon
 }
 
-/// returns all the buckets that are inside the range of the `days` param
-pub fn sl_get_bucket_range(bucket_size: u32, days: u32) -> (u32, u32) {
-    let time_bucket: u32 = sl_get_current_time_bucket(bucket_size);
-    let buckets_for_days_in_request = days / bucket_size;
-    (time_bucket, buckets_for_days_in_request)
+/// returns the current bucket and how many buckets before it hold logs of the last `days` days,
+/// counting buckets only partially inside the range
+pub fn sl_get_bucket_range(clock: &dyn Clock, bucket_size: u32, days: u32) -> (u32, u32) {
+    let now = clock.now();
+    let time_bucket: u32 = time_bucket_from_date(now, bucket_size);
+    let first_bucket = time_bucket_from_date(
+        (now - Duration::days(days.into())).max(holo_epoch()),
+        bucket_size,
+    );
+    (time_bucket, time_bucket - first_bucket)
 }
 
-/// returns whether the local time is within the deleting window which is`windows_size`` min after midnight.
-pub fn sl_within_deleting_check_window(window_size: u32) -> bool {
-    if let Ok(val) = std::env::var("SL_TEST_IS_IN_DELETING_WINDOW") {
-        if val == "true" {
-            return true;
+/// start of the Holo epoch, when time bucket 0 begins
+fn holo_epoch() -> DateTime<Utc> {
+    NaiveDate::from_ymd_opt(HOLO_EPOCH_YEAR.into(), 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+}
+
+/// returns the UTC time at which the bucket of `spec` begins, the latest representable time
+/// for buckets beginning after it (e.g. of a clone named "14.4294967295")
+pub fn sl_bucket_start(spec: &SlCloneSpec) -> DateTime<Utc> {
+    i64::from(spec.days_in_bucket)
+        .checked_mul(i64::from(spec.time_bucket))
+        .and_then(Duration::try_days)
+        .and_then(|days| holo_epoch().checked_add_signed(days))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// returns the UTC time at which the bucket of `spec` ends, which is the start of the next bucket
+pub fn sl_bucket_end(spec: &SlCloneSpec) -> DateTime<Utc> {
+    match spec.time_bucket.checked_add(1) {
+        Some(time_bucket) => sl_bucket_start(&SlCloneSpec {
+            time_bucket,
+            ..*spec
+        }),
+        None => DateTime::<Utc>::MAX_UTC,
+    }
+}
+
+/// returns the specs of all buckets holding logs from `start` up to (not including) `end`,
+/// in order, with the first and last buckets possibly only partially inside the range
+pub fn sl_clone_specs_for_range(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    days_in_bucket: u32,
+) -> Vec<SlCloneSpec> {
+    let start = start.max(holo_epoch());
+    if end <= start {
+        return vec![];
+    }
+    let first = time_bucket_from_date(start, days_in_bucket);
+    let last = time_bucket_from_date(end - Duration::nanoseconds(1), days_in_bucket);
+    (first..=last)
+        .map(|time_bucket| SlCloneSpec {
+            days_in_bucket,
+            time_bucket,
+        })
+        .collect()
+}
+
+/// returns whether the clone of `spec` is older than the current bucket and the
+/// `buckets_to_keep` buckets before it. Clones of other bucket sizes are never expired.
+pub fn sl_is_outside_retention(
+    spec: &SlCloneSpec,
+    now: DateTime<Utc>,
+    days_in_bucket: u32,
+    buckets_to_keep: u32,
+) -> bool {
+    spec.days_in_bucket == days_in_bucket
+        && spec.time_bucket.saturating_add(buckets_to_keep)
+            < time_bucket_from_date(now, days_in_bucket)
+}
+
+/// returns the clone names that are outside of retention, oldest bucket first.
+/// Names not made by `sl_clone_name` are skipped.
+pub fn sl_clone_names_outside_retention<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    now: DateTime<Utc>,
+    days_in_bucket: u32,
+    buckets_to_keep: u32,
+) -> Vec<String> {
+    let mut expired: Vec<(u32, &str)> = names
+        .into_iter()
+        .filter_map(|name| {
+            let spec = sl_clone_name_spec(name).ok()?;
+            sl_is_outside_retention(&spec, now, days_in_bucket, buckets_to_keep)
+                .then_some((spec.time_bucket, name))
+        })
+        .collect();
+    expired.sort();
+    expired
+        .into_iter()
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// Change of an app's bucket size at `at`: logs before it are in buckets of `from_days`
+/// and logs from then on in buckets of `to_days`. The last old bucket and the first new one
+/// straddle the change, so each of them only holds logs of its side of `at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlBucketMigration {
+    pub from_days: u32,
+    pub to_days: u32,
+    pub at: DateTime<Utc>,
+}
+
+impl SlBucketMigration {
+    pub fn new(from_days: u32, to_days: u32, at: DateTime<Utc>) -> Self {
+        Self {
+            from_days,
+            to_days,
+            at,
+        }
+    }
+
+    /// last bucket of the old size, which is logged into until `at`
+    pub fn last_old_bucket(&self) -> SlCloneSpec {
+        SlCloneSpec {
+            days_in_bucket: self.from_days,
+            time_bucket: time_bucket_from_date(self.at - Duration::nanoseconds(1), self.from_days),
+        }
+    }
+
+    /// first bucket of the new size, which is logged into from `at`
+    pub fn first_new_bucket(&self) -> SlCloneSpec {
+        SlCloneSpec {
+            days_in_bucket: self.to_days,
+            time_bucket: time_bucket_from_date(self.at, self.to_days),
+        }
+    }
+
+    /// returns the specs of all buckets holding logs from `start` up to (not including) `end`,
+    /// old buckets first, so a range over the change gets both straddling buckets
+    pub fn specs_for_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<SlCloneSpec> {
+        let mut specs = sl_clone_specs_for_range(start, end.min(self.at), self.from_days);
+        specs.extend(sl_clone_specs_for_range(
+            start.max(self.at),
+            end,
+            self.to_days,
+        ));
+        specs
+    }
+
+    /// returns whether the clone of `spec` only holds logs from before the current bucket of
+    /// the new size and the `buckets_to_keep` buckets before it. Clones of other sizes are never expired.
+    pub fn is_outside_retention(
+        &self,
+        spec: &SlCloneSpec,
+        now: DateTime<Utc>,
+        buckets_to_keep: u32,
+    ) -> bool {
+        if spec.days_in_bucket == self.to_days {
+            return sl_is_outside_retention(spec, now, self.to_days, buckets_to_keep);
         }
+        if spec.days_in_bucket != self.from_days {
+            return false;
+        }
+        let Some(oldest_kept) =
+            time_bucket_from_date(now, self.to_days).checked_sub(buckets_to_keep)
+        else {
+            return false;
+        };
+        let retention_start = sl_bucket_start(&SlCloneSpec {
+            days_in_bucket: self.to_days,
+            time_bucket: oldest_kept,
+        });
+        sl_bucket_end(spec).min(self.at) <= retention_start
     }
-    let now = Local::now();
+}
+
+/// returns whether the local time is within the deleting window which is`windows_size`` min after midnight.
+pub fn sl_within_deleting_check_window(clock: &dyn Clock, window_size: u32) -> bool {
+    let now = clock.now_local();
     let min = now.minute();
     now.hour() == 0 && min >= 1 && min <= window_size
 }
@@ -123,6 +303,7 @@ pub fn sl_clone_name(spec: SlCloneSpec) -> String {
     format!("{}.{}", spec.days_in_bucket, spec.time_bucket)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SlCloneSpec {
     pub days_in_bucket: u32,
     pub time_bucket: u32,
@@ -144,3 +325,111 @@ pub fn sl_clone_name_spec(name: &str) -> Result<SlCloneSpec> {
         time_bucket,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use chrono::TimeZone;
+
+    fn spec(time_bucket: u32) -> SlCloneSpec {
+        SlCloneSpec {
+            days_in_bucket: 14,
+            time_bucket,
+        }
+    }
+
+    #[test]
+    fn bucket_dates_and_ranges() {
+        let bucket_5 = spec(5);
+        assert_eq!(
+            sl_bucket_start(&bucket_5),
+            Utc.with_ymd_and_hms(2024, 3, 11, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            sl_bucket_end(&bucket_5),
+            Utc.with_ymd_and_hms(2024, 3, 25, 0, 0, 0).unwrap()
+        );
+        assert_eq!(time_bucket_from_date(sl_bucket_end(&bucket_5), 14), 6);
+
+        // March invoice touches the end of bucket 4, all of bucket 5 and the start of bucket 6
+        let march = sl_clone_specs_for_range(
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap(),
+            14,
+        );
+        assert_eq!(march, vec![spec(4), spec(5), spec(6)]);
+        // end is excluded
+        let whole_bucket =
+            sl_clone_specs_for_range(sl_bucket_start(&bucket_5), sl_bucket_end(&bucket_5), 14);
+        assert_eq!(whole_bucket, vec![bucket_5]);
+        assert!(
+            sl_clone_specs_for_range(sl_bucket_end(&bucket_5), sl_bucket_start(&bucket_5), 14)
+                .is_empty()
+        );
+
+        // 10 days back from the middle of bucket 5 reaches into bucket 4
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2024, 3, 18, 12, 0, 0).unwrap());
+        assert_eq!(sl_get_bucket_range(&clock, 14, 10), (5, 1));
+        assert_eq!(sl_get_bucket_range(&clock, 14, 30), (5, 2));
+        assert_eq!(sl_get_bucket_range(&clock, 14, 1), (5, 0));
+        assert_eq!(sl_get_bucket_range(&clock, 14, 1000), (5, 5));
+    }
+
+    #[test]
+    fn bucket_size_migration() {
+        // 14 day buckets until 2024-03-18 (in 14.5), 7 day buckets after it (in 7.11)
+        let migration =
+            SlBucketMigration::new(14, 7, Utc.with_ymd_and_hms(2024, 3, 18, 0, 0, 0).unwrap());
+        let seven = |time_bucket| SlCloneSpec {
+            days_in_bucket: 7,
+            time_bucket,
+        };
+        assert_eq!(migration.last_old_bucket(), spec(5));
+        assert_eq!(migration.first_new_bucket(), seven(11));
+
+        let march = migration.specs_for_range(
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(march, vec![spec(4), spec(5), seven(11), seven(12)]);
+        let after = migration.specs_for_range(
+            migration.at,
+            Utc.with_ymd_and_hms(2024, 3, 20, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(after, vec![seven(11)]);
+
+        // 14.5 holds logs until the change only, so it expires with 7.10
+        let now = Utc.with_ymd_and_hms(2024, 4, 2, 0, 0, 0).unwrap();
+        assert_eq!(time_bucket_from_date(now, 7), 13);
+        assert!(migration.is_outside_retention(&spec(5), now, 2));
+        assert!(!migration.is_outside_retention(&seven(11), now, 2));
+        assert!(migration.is_outside_retention(&seven(10), now, 2));
+        assert!(!migration.is_outside_retention(&spec(5), now, 3));
+    }
+
+    #[test]
+    fn retention() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 18, 12, 0, 0).unwrap();
+        let names = ["14.3", "not-a-bucket", "14.1", "7.0", "14.5", "14.2"];
+        assert_eq!(
+            sl_clone_names_outside_retention(names, now, 14, 2),
+            vec!["14.1".to_string(), "14.2".to_string()]
+        );
+        assert!(sl_is_outside_retention(&spec(4), now, 14, 0));
+        assert!(!sl_is_outside_retention(&spec(5), now, 14, 0));
+    }
+
+    #[test]
+    fn last_time_bucket_does_not_overflow() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 18, 12, 0, 0).unwrap();
+        let last = sl_clone_name_spec("14.4294967295").unwrap();
+        assert_eq!(last.time_bucket, u32::MAX);
+
+        assert!(!sl_is_outside_retention(&last, now, 14, SL_BUCKETS_TO_KEEP));
+        assert!(!sl_is_outside_retention(&spec(0), now, 14, u32::MAX));
+        assert!(sl_clone_names_outside_retention(["14.4294967295"], now, 14, 2).is_empty());
+        assert_eq!(sl_bucket_start(&last), DateTime::<Utc>::MAX_UTC);
+        assert_eq!(sl_bucket_end(&last), DateTime::<Utc>::MAX_UTC);
+    }
+}
@@ -1,14 +1,15 @@
+use crate::download_cache::DownloadCache;
 use anyhow::{anyhow, Context, Result};
 use holochain_types::prelude::{Nonce256Bits, Timestamp};
 use holochain_types::prelude::{SerializedBytes, SerializedBytesError};
-use holochain_websocket::WebsocketReceiver;
-use lair_keystore_api::dependencies::tokio;
+use holochain_types::signal::Signal;
+use holochain_websocket::{ReceiveMessage, WebsocketReceiver};
+use lair_keystore_api::dependencies::tokio::{self, sync::broadcast};
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process;
-use std::time::Duration;
-use tempfile::TempDir;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, instrument};
 use url::Url;
 
@@ -22,6 +23,48 @@ pub fn fresh_nonce() -> Result<(Nonce256Bits, Timestamp)> {
     Ok((nonce, expires))
 }
 
+/// Backoff used when re-establishing a websocket connection to a restarted conductor
+pub fn reconnect_policy() -> again::RetryPolicy {
+    again::RetryPolicy::exponential(Duration::from_millis(250))
+        .with_max_delay(Duration::from_secs(10))
+        .with_max_retries(12)
+        .with_jitter(true)
+}
+
+/// Returns true if an error returned by websocket request means the connection is gone
+/// and it makes sense to reconnect. holochain_websocket reports a connection it already knows
+/// to be closed as `WebsocketClosed` and wraps errors of the underlying stream, so those are
+/// unwrapped before looking at the error kind.
+/// Timeouts do not qualify, because a slow conductor still holds the connection open.
+pub fn is_connection_error(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    match e.kind() {
+        ConnectionReset | ConnectionAborted | BrokenPipe | NotConnected | UnexpectedEof => true,
+        Other if e.to_string() == "WebsocketClosed" => true,
+        _ => e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<std::io::Error>())
+            .is_some_and(is_connection_error),
+    }
+}
+
+/// Upper limit of the uncompressed size of a UI archive
+pub const MAX_UNZIPPED_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Extracts a UI archive to `unpack_path`, see `extract_zip_with_limit`
+pub fn extract_zip<P: AsRef<Path>>(source_path: P, unpack_path: P) -> Result<()> {
+    extract_zip_with_limit(source_path, unpack_path, MAX_UNZIPPED_BYTES)
+}
+
+/// Extracts a zip archive into a new version directory `<unpack_path>.v<n>` next to
+/// `unpack_path` and then atomically points the symlink at `unpack_path` to it, by renaming
+/// a new symlink over the old one. Readers of `unpack_path` see either the complete previous
+/// or the complete new version. The replaced version is kept behind `<unpack_path>.previous`
+/// and can be restored with `rollback_zip`, older versions are removed.
+/// A directory at `unpack_path`, extracted before UIs were symlinked, becomes a version itself,
+/// which leaves `unpack_path` missing for a moment once.
+/// Entries escaping the target directory, symlinks and archives unpacking to more than
+/// `max_bytes` are rejected.
 #[instrument(
     err,
     fields(
@@ -29,68 +72,219 @@ pub fn fresh_nonce() -> Result<(Nonce256Bits, Timestamp)> {
         unpack_path = %unpack_path.as_ref().display(),
     ),
 )]
-pub fn extract_zip<P: AsRef<Path>>(source_path: P, unpack_path: P) -> Result<()> {
-    let _ = fs::remove_dir_all(unpack_path.as_ref());
-    fs::create_dir(unpack_path.as_ref()).context("failed to create empty unpack_path")?;
+pub fn extract_zip_with_limit<P: AsRef<Path>>(
+    source_path: P,
+    unpack_path: P,
+    max_bytes: u64,
+) -> Result<()> {
+    let unpack_path = unpack_path.as_ref();
+    let version = create_version_dir(unpack_path)?;
 
     debug!("unziping file");
+    if let Err(e) = unzip_into(source_path.as_ref(), &version, max_bytes) {
+        let _ = fs::remove_dir_all(&version);
+        return Err(e);
+    }
 
-    let output = process::Command::new("unzip")
-        .arg(source_path.as_ref().as_os_str())
-        .arg("-d")
-        .arg(unpack_path.as_ref().as_os_str())
-        .stdout(process::Stdio::piped())
-        .output()
-        .context("failed to spawn unzip command")?;
+    let mut current = fs::read_link(unpack_path).ok();
+    if current.is_none() && unpack_path.is_dir() {
+        let migrated = version_dir(unpack_path)?;
+        fs::rename(unpack_path, &migrated).context("failed to move aside current version")?;
+        current = Some(migrated);
+    }
+    if let Err(e) = flip_symlink(unpack_path, &version) {
+        if let Some(current) = &current {
+            let _ = flip_symlink(unpack_path, current);
+        }
+        let _ = fs::remove_dir_all(&version);
+        return Err(e).context("failed to switch to extracted archive");
+    }
+    if let Some(current) = current {
+        flip_symlink(&sibling(unpack_path, "previous")?, &current)?;
+    }
+    remove_stale_versions(unpack_path)
+}
+
+/// Restores the version `extract_zip` replaced at `unpack_path`
+pub fn rollback_zip(unpack_path: &Path) -> Result<()> {
+    let previous_link = sibling(unpack_path, "previous")?;
+    let previous = fs::read_link(&previous_link)
+        .map_err(|_| anyhow!("no previous version of {}", unpack_path.display()))?;
+    flip_symlink(unpack_path, &previous).context("failed to restore previous version")?;
+    fs::remove_file(&previous_link)?;
+    remove_stale_versions(unpack_path)
+}
 
-    debug!("{}", String::from_utf8_lossy(&output.stdout));
+/// `<dir>/<name>.<suffix>` for `<dir>/<name>`
+fn sibling(path: &Path, suffix: &str) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .context(format!("{} has no file name", path.display()))?;
+    Ok(path.with_file_name(format!("{}.{}", name.to_string_lossy(), suffix)))
+}
+
+/// Unused `<dir>/<name>.v<n>` for `<dir>/<name>`
+fn version_dir(path: &Path) -> Result<PathBuf> {
+    let mut n = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    loop {
+        let dir = sibling(path, &format!("v{}", n))?;
+        if dir.symlink_metadata().is_err() {
+            return Ok(dir);
+        }
+        n += 1;
+    }
+}
 
+fn create_version_dir(path: &Path) -> Result<PathBuf> {
+    let dir = version_dir(path)?;
+    fs::create_dir_all(dir.parent().unwrap_or(Path::new(".")))?;
+    fs::create_dir(&dir).context(format!("failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Atomically points the symlink `link` to `target`, a sibling of `link`
+fn flip_symlink(link: &Path, target: &Path) -> Result<()> {
+    let target = target
+        .file_name()
+        .context(format!("{} has no file name", target.display()))?;
+    let new_link = sibling(link, "link")?;
+    let _ = fs::remove_file(&new_link);
+    std::os::unix::fs::symlink(target, &new_link)?;
+    fs::rename(&new_link, link).map_err(|e| {
+        let _ = fs::remove_file(&new_link);
+        anyhow!(e)
+    })
+}
+
+/// Removes version directories of `unpack_path` that neither it nor `<unpack_path>.previous`
+/// point to
+fn remove_stale_versions(unpack_path: &Path) -> Result<()> {
+    let name = unpack_path
+        .file_name()
+        .context(format!("{} has no file name", unpack_path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let in_use: Vec<PathBuf> = [unpack_path.to_path_buf(), sibling(unpack_path, "previous")?]
+        .iter()
+        .filter_map(|link| fs::read_link(link).ok())
+        .collect();
+    let parent = unpack_path.parent().unwrap_or(Path::new("."));
+
+    for dir in fs::read_dir(parent)?.flatten() {
+        let file_name = dir.file_name().to_string_lossy().to_string();
+        let is_version = file_name
+            .strip_prefix(&name)
+            .and_then(|suffix| suffix.strip_prefix(".v"))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+        let is_dir = dir.file_type().is_ok_and(|t| t.is_dir());
+        if is_version
+            && is_dir
+            && !in_use
+                .iter()
+                .any(|used| used.as_os_str() == file_name.as_str())
+        {
+            debug!("removing stale version {}", file_name);
+            fs::remove_dir_all(dir.path())?;
+        }
+    }
     Ok(())
 }
 
-#[instrument(err, skip(url))]
-pub async fn download_file(url: &Url) -> Result<PathBuf> {
-    use isahc::config::Configurable;
-    use isahc::config::RedirectPolicy;
-    use isahc::prelude::*;
-    use isahc::HttpClient;
-
-    let path = if url.scheme() == "file" {
-        let p = PathBuf::from(url.path());
-        debug!("Using: {:?}", p);
-        p
-    } else {
-        debug!("downloading");
-        let mut url = Url::clone(url);
-        url.set_scheme("https")
-            .map_err(|_| anyhow!("failed to set scheme to https"))?;
-        let client = HttpClient::builder()
-            .redirect_policy(RedirectPolicy::Follow)
-            .build()
-            .context("failed to initiate download request")?;
-        let mut response = client
-            .get(url.as_str())
-            .context("failed to send GET request")?;
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "response status code {} indicated failure",
-                response.status().as_str()
-            ));
+fn unzip_into(source_path: &Path, target: &Path, max_bytes: u64) -> Result<()> {
+    let file = fs::File::open(source_path).context("failed to open archive")?;
+    let mut archive = zip::ZipArchive::new(file).context("failed to read archive")?;
+    let mut unpacked: u64 = 0;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let relative = entry
+            .enclosed_name()
+            .ok_or_else(|| anyhow!("archive entry {} escapes target directory", name))?;
+        // S_IFLNK, symlinks could point anywhere once extracted
+        if entry
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170000 == 0o120000)
+        {
+            return Err(anyhow!("archive entry {} is a symlink", name));
+        }
+        let path = target.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
-        let dir = TempDir::new().context("failed to create tempdir")?;
-        let url_path = PathBuf::from(url.path());
-        let basename = url_path
-            .file_name()
-            .context("failed to get basename from url")?;
-        let path = dir.into_path().join(basename);
-        let mut file = fs::File::create(&path).context("failed to create target file")?;
-        response
-            .copy_to(&mut file)
-            .context("failed to write response to file")?;
-        debug!("download successful");
-        path
-    };
-    Ok(path)
+        let mut out = fs::File::create(&path)?;
+        // declared sizes can lie, so count what is actually written
+        let remaining = max_bytes - unpacked;
+        let written = std::io::copy(&mut (&mut entry).take(remaining + 1), &mut out)?;
+        unpacked += written;
+        if unpacked > max_bytes {
+            return Err(anyhow!("archive unpacks to more than {} bytes", max_bytes));
+        }
+    }
+    Ok(())
+}
+
+/// Hex encoded sha256 of the file at `path`
+pub fn sha256_file(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = fs::File::open(path).context(format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).context("failed to hash file")?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Fails unless the file at `path` hashes to `expected`, a hex encoded sha256
+pub fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(anyhow!(
+            "sha256 mismatch for {}: expected {}, got {}",
+            path.display(),
+            expected,
+            actual
+        ));
+    }
+    debug!("sha256 of {} verified", path.display());
+    Ok(())
+}
+
+/// Returns a local path of `url`, which is the file itself for `file://` urls and
+/// otherwise a copy in `cache`. Only https urls are downloaded. `sha256` is the expected
+/// content, if known, and is checked here for files and by `DownloadCache::fetch` for downloads
+#[instrument(err, skip(url, cache))]
+pub async fn download_file(
+    url: &Url,
+    cache: &DownloadCache,
+    sha256: Option<&str>,
+) -> Result<PathBuf> {
+    match url.scheme() {
+        "file" => {
+            let p = PathBuf::from(url.path());
+            debug!("Using: {:?}", p);
+            if let Some(sha256) = sha256 {
+                verify_sha256(&p, sha256)?;
+            }
+            Ok(p)
+        }
+        "https" => {
+            debug!("downloading");
+            cache.fetch(url, sha256)
+        }
+        scheme => Err(anyhow!(
+            "refusing to download {} over {}, only https and file urls are supported",
+            url,
+            scheme
+        )),
+    }
 }
 
 /// You do not need to do anything with this type. While it is held it will keep polling a websocket
@@ -105,6 +299,12 @@ impl Drop for WsPollRecv {
 }
 
 impl WsPollRecv {
+    /// Returns true once the websocket stopped delivering messages, which means that
+    /// the conductor closed the connection (eg. it was restarted)
+    pub fn is_closed(&self) -> bool {
+        self.0.is_finished()
+    }
+
     /// Create a new [WsPollRecv] that will poll the given [WebsocketReceiver] for messages.
     /// The type of the messages being received must be specified. For example
     pub fn new<D>(mut rx: WebsocketReceiver) -> Self
@@ -116,4 +316,178 @@ impl WsPollRecv {
             while rx.recv::<D>().await.is_ok() {}
         }))
     }
+
+    /// Create a new [WsPollRecv] that keeps polling the given [WebsocketReceiver] like [WsPollRecv::new]
+    /// and additionally decodes every received signal and publishes it to `signal_tx`.
+    pub fn new_with_signals<D>(
+        mut rx: WebsocketReceiver,
+        signal_tx: broadcast::Sender<Signal>,
+    ) -> Self
+    where
+        D: std::fmt::Debug,
+        SerializedBytes: TryInto<D, Error = SerializedBytesError>,
+    {
+        Self(tokio::task::spawn(async move {
+            while let Ok(msg) = rx.recv::<D>().await {
+                if let ReceiveMessage::Signal(bytes) = msg {
+                    match rmp_serde::from_slice::<Signal>(&bytes) {
+                        // send only fails when there are no subscribers, which is fine
+                        Ok(signal) => {
+                            let _ = signal_tx.send(signal);
+                        }
+                        Err(e) => tracing::warn!("failed to decode signal: {:?}", e),
+                    }
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Error, ErrorKind};
+    use tempfile::TempDir;
+
+    #[test]
+    fn connection_errors() {
+        assert!(is_connection_error(&Error::from(
+            ErrorKind::ConnectionReset
+        )));
+        assert!(is_connection_error(&Error::from(ErrorKind::BrokenPipe)));
+        assert!(is_connection_error(&Error::other("WebsocketClosed")));
+        assert!(is_connection_error(&Error::other(Error::from(
+            ErrorKind::NotConnected
+        ))));
+
+        assert!(!is_connection_error(&Error::from(ErrorKind::TimedOut)));
+        assert!(!is_connection_error(&Error::other("Deserialize")));
+        assert!(!is_connection_error(&Error::from(ErrorKind::InvalidData)));
+    }
+
+    #[test]
+    fn downloads_only_https() {
+        let dir = TempDir::new().unwrap();
+        let cache = DownloadCache::new(dir.path(), u64::MAX).unwrap();
+        let url = Url::parse("http://example.com/chat.happ").unwrap();
+
+        let err = futures::executor::block_on(download_file(&url, &cache, None)).unwrap_err();
+        assert!(err.to_string().contains("only https"), "{:#}", err);
+    }
+
+    #[test]
+    fn verify_sha256_of_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bundle.happ");
+        fs::write(&path, b"hello").unwrap();
+
+        verify_sha256(
+            &path,
+            "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824",
+        )
+        .unwrap();
+        let err = verify_sha256(&path, "00").unwrap_err();
+        assert!(err.to_string().contains("sha256 mismatch"));
+    }
+
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, bytes) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Names of the entries of `dir`, sorted
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn extract_zip_swaps_in_new_version() {
+        let dir = TempDir::new().unwrap();
+        let ui = dir.path().join("ui");
+        let v1 = dir.path().join("v1.zip");
+        let v2 = dir.path().join("v2.zip");
+        let v3 = dir.path().join("v3.zip");
+        write_zip(&v1, &[("index.html", b"v1")]);
+        write_zip(&v2, &[("index.html", b"v2"), ("js/app.js", b"app")]);
+        write_zip(&v3, &[("index.html", b"v3")]);
+
+        extract_zip(&v1, &ui).unwrap();
+        assert!(fs::symlink_metadata(&ui).unwrap().file_type().is_symlink());
+        let first = fs::read_link(&ui).unwrap();
+        extract_zip(&v2, &ui).unwrap();
+        assert_eq!(fs::read(ui.join("index.html")).unwrap(), b"v2");
+        assert_eq!(fs::read(ui.join("js/app.js")).unwrap(), b"app");
+        assert_eq!(
+            fs::read_link(dir.path().join("ui.previous")).unwrap(),
+            first
+        );
+
+        // only the current and the previous version are kept
+        extract_zip(&v3, &ui).unwrap();
+        let versions: Vec<String> = entries(dir.path())
+            .into_iter()
+            .filter(|name| name.starts_with("ui.v"))
+            .collect();
+        assert_eq!(versions.len(), 2);
+        assert!(!versions.contains(&first.to_string_lossy().to_string()));
+        assert!(!dir.path().join("ui.link").exists());
+
+        rollback_zip(&ui).unwrap();
+        assert_eq!(fs::read(ui.join("index.html")).unwrap(), b"v2");
+        assert!(rollback_zip(&ui).is_err());
+    }
+
+    #[test]
+    fn extract_zip_replaces_unpacked_directory() {
+        let dir = TempDir::new().unwrap();
+        let ui = dir.path().join("ui");
+        fs::create_dir(&ui).unwrap();
+        fs::write(ui.join("index.html"), b"unpacked").unwrap();
+        let zip = dir.path().join("ui.zip");
+        write_zip(&zip, &[("index.html", b"extracted")]);
+
+        extract_zip(&zip, &ui).unwrap();
+        assert!(fs::symlink_metadata(&ui).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(ui.join("index.html")).unwrap(), b"extracted");
+
+        rollback_zip(&ui).unwrap();
+        assert_eq!(fs::read(ui.join("index.html")).unwrap(), b"unpacked");
+    }
+
+    #[test]
+    fn extract_zip_rejects_unsafe_archives() {
+        let dir = TempDir::new().unwrap();
+        let ui = dir.path().join("ui");
+        let good = dir.path().join("good.zip");
+        write_zip(&good, &[("index.html", b"good")]);
+        extract_zip(&good, &ui).unwrap();
+        let before = entries(dir.path());
+
+        let traversal = dir.path().join("traversal.zip");
+        write_zip(&traversal, &[("../evil.html", b"evil")]);
+        let err = extract_zip(&traversal, &ui).unwrap_err();
+        assert!(err.to_string().contains("escapes target directory"));
+
+        let big = dir.path().join("big.zip");
+        write_zip(&big, &[("a", &[0; 60]), ("b", &[0; 60])]);
+        let err = extract_zip_with_limit(&big, &ui, 100).unwrap_err();
+        assert!(err.to_string().contains("more than 100 bytes"));
+
+        // failed extractions leave the current version in place
+        assert_eq!(fs::read(ui.join("index.html")).unwrap(), b"good");
+        let mut after = entries(dir.path());
+        after.retain(|name| !name.ends_with(".zip") || before.contains(name));
+        assert_eq!(after, before);
+    }
 }
@@ -0,0 +1,150 @@
+//! Tests of hpos_hc_connect agents against an in-process mock conductor,
+//! so they run without holochain and lair-keystore installed.
+
+use anyhow::Result;
+use futures::StreamExt;
+use holochain_types::prelude::AgentPubKey;
+use hpos_hc_connect::{
+    hf_agent::HfAgent,
+    hha_agent::CoreAppAgent,
+    hha_types::{PresentedHappBundle, SetHostJurisdictionInput},
+    signals::app_signal_payload,
+    AdminWebsocket, AppConnection, SignalFilter,
+};
+use mock_conductor::{MockApp, MockConductor};
+
+const CORE_APP_ID: &str = "core-app:0_6_2";
+
+async fn core_app_connection() -> Result<(MockConductor, AppConnection, AgentPubKey)> {
+    let keystore = holochain_keystore::test_keystore();
+    let agent_key = keystore.new_sign_keypair_random().await?;
+
+    let conductor = MockConductor::builder()
+        .app(
+            MockApp::new(CORE_APP_ID, agent_key.clone())
+                .role("core-app")
+                .role("holofuel"),
+        )
+        .app(MockApp::new("uhCkkStale", agent_key.clone()).disabled())
+        .zome_fn("hha", "get_happs", |_: ()| {
+            Ok(Vec::<PresentedHappBundle>::new())
+        })
+        .zome_fn("hha", "get_host_jurisdiction", |_: AgentPubKey| {
+            Ok(Some("Gibraltar".to_string()))
+        })
+        .zome_fn(
+            "hha",
+            "set_host_jurisdiction",
+            |input: SetHostJurisdictionInput| {
+                anyhow::ensure!(input.jurisdiction == "Germany");
+                Ok(())
+            },
+        )
+        .spawn()
+        .await?;
+
+    let mut admin_ws = AdminWebsocket::connect(conductor.admin_port()).await?;
+    let app = AppConnection::connect(&mut admin_ws, keystore, CORE_APP_ID.to_string()).await?;
+
+    Ok((conductor, app, agent_key))
+}
+
+#[tokio::test]
+async fn admin_lists_enabled_apps() -> Result<()> {
+    let (conductor, _, _) = core_app_connection().await?;
+    let mut admin_ws = AdminWebsocket::connect(conductor.admin_port()).await?;
+
+    assert_eq!(admin_ws.list_enabled_apps().await?, vec![CORE_APP_ID]);
+
+    admin_ws.disable_app(CORE_APP_ID).await?;
+    assert!(conductor.enabled_apps().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn core_app_agent_zome_calls() -> Result<()> {
+    let (conductor, app, agent_key) = core_app_connection().await?;
+    let mut agent = CoreAppAgent { app };
+
+    assert_eq!(agent.pubkey().await?, agent_key);
+    assert!(agent.get_happs().await?.is_empty());
+
+    // Call without a scripted handler surfaces as an error of the zome call
+    assert!(agent.get_pending_transactions().await.is_err());
+
+    let calls = conductor.zome_calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].fn_name, "get_happs".into());
+    assert_eq!(calls[1].zome_name, "transactor".into());
+    Ok(())
+}
+
+#[tokio::test]
+async fn core_app_agent_host_jurisdiction() -> Result<()> {
+    let (conductor, app, agent_key) = core_app_connection().await?;
+    let mut agent = CoreAppAgent { app };
+
+    let jurisdiction = agent.get_host_jurisdiction(agent_key.clone()).await?;
+    assert_eq!(jurisdiction.as_deref(), Some("Gibraltar"));
+    agent
+        .set_host_jurisdiction(SetHostJurisdictionInput {
+            pubkey: agent_key,
+            jurisdiction: "Germany".to_string(),
+        })
+        .await?;
+
+    let calls = conductor.zome_calls();
+    assert_eq!(calls[1].fn_name, "set_host_jurisdiction".into());
+    Ok(())
+}
+
+#[tokio::test]
+async fn agents_reconnect_after_connections_are_dropped() -> Result<()> {
+    let (conductor, app, _) = core_app_connection().await?;
+    let mut admin_ws = AdminWebsocket::connect(conductor.admin_port()).await?;
+    let mut agent = CoreAppAgent { app };
+    assert!(agent.get_happs().await?.is_empty());
+
+    conductor.drop_connections();
+    // Same as after a conductor restart, the closed connections are noticed before next request
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    assert_eq!(admin_ws.list_enabled_apps().await?, vec![CORE_APP_ID]);
+    assert!(agent.get_happs().await?.is_empty());
+    assert_eq!(conductor.zome_calls().len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn hf_agent_uses_holofuel_cell() -> Result<()> {
+    let (_conductor, mut app, agent_key) = core_app_connection().await?;
+    let hf_cell = app.cell("holofuel".into()).await?;
+    let agent = HfAgent { app };
+
+    assert_eq!(agent.pubkey().await?, agent_key);
+    assert_eq!(hf_cell.agent_pubkey(), &agent_key);
+    Ok(())
+}
+
+#[tokio::test]
+async fn signals_are_filtered_by_role() -> Result<()> {
+    let (conductor, mut app, _) = core_app_connection().await?;
+    let hf_cell = app.cell("holofuel".into()).await?;
+    let hha_cell = app.cell("core-app".into()).await?;
+
+    let mut signals = app
+        .subscribe_signals(SignalFilter::role("holofuel"))
+        .await?;
+
+    conductor
+        .emit_signal(CORE_APP_ID, hha_cell, "hha", "ignored")
+        .await?;
+    conductor
+        .emit_signal(CORE_APP_ID, hf_cell, "transactor", "new invoice")
+        .await?;
+
+    let signal = signals.next().await.expect("signal stream ended");
+    let payload: String = app_signal_payload(&signal).unwrap()?;
+    assert_eq!(payload, "new invoice");
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! `HbsClient` against the local mock of Holo backend services.
+
+use anyhow::Result;
+use ed25519_dalek::{SigningKey, VerifyingKey, SIGNATURE_LENGTH};
+use futures::future::BoxFuture;
+use hpos_hc_connect::hbs::{HbsClient, HbsError};
+use hpos_hc_connect::host_keys::{HostKeys, HostSigner, InMemorySigner};
+use mock_services::hbs::{HbsServer, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn host_keys() -> HostKeys {
+    let signer = InMemorySigner::new(SigningKey::from_bytes(&[1; 32]));
+    HostKeys::from_signer("host@holo.host".to_string(), Arc::new(signer))
+}
+
+fn client(server: &HbsServer, keys: HostKeys) -> HbsClient {
+    HbsClient::new(server.url(), keys)
+        .with_retry_policy(again::RetryPolicy::fixed(Duration::from_millis(10)).with_max_retries(3))
+}
+
+#[tokio::test]
+async fn registration_is_fetched_after_retries() -> Result<()> {
+    let server = HbsServer::builder()
+        .default_jurisdiction("Gibraltar")
+        .fail_next(StatusCode::SERVICE_UNAVAILABLE, 2)
+        .spawn("127.0.0.1:0".parse()?)
+        .await?;
+    let keys = host_keys();
+    let hbs = client(&server, keys.clone());
+
+    let record = hbs.get_host_registration().await?;
+    assert_eq!(record.jurisdiction, "Gibraltar");
+    assert_eq!(record.email, "host@holo.host");
+    assert_eq!(record.public_key, keys.pubkey_base36);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    // every attempt is built and signed anew, with a fresh timestamp
+    assert!(requests.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+
+    server.set_jurisdiction(&keys.pubkey_base36, "Germany");
+    assert_eq!(hbs.get_host_registration().await?.jurisdiction, "Germany");
+    Ok(())
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() -> Result<()> {
+    let server = HbsServer::builder().spawn("127.0.0.1:0".parse()?).await?;
+    let hbs = client(&server, host_keys());
+
+    let err = hbs.get_host_registration().await.unwrap_err();
+    assert!(matches!(err, HbsError::Status { status, .. } if status == StatusCode::NOT_FOUND));
+    assert!(!err.is_retryable());
+    assert_eq!(server.requests().len(), 1);
+    Ok(())
+}
+
+#[derive(Debug)]
+struct WrongSigner;
+
+impl HostSigner for WrongSigner {
+    fn verifying_key(&self) -> VerifyingKey {
+        SigningKey::from_bytes(&[2; 32]).verifying_key()
+    }
+
+    fn sign<'a>(&'a self, _: &'a [u8]) -> BoxFuture<'a, Result<[u8; SIGNATURE_LENGTH]>> {
+        Box::pin(async { Ok([0; SIGNATURE_LENGTH]) })
+    }
+}
+
+#[tokio::test]
+async fn invalid_signature_is_rejected() -> Result<()> {
+    let server = HbsServer::builder()
+        .default_jurisdiction("Gibraltar")
+        .spawn("127.0.0.1:0".parse()?)
+        .await?;
+    let keys = HostKeys::from_signer("host@holo.host".to_string(), Arc::new(WrongSigner));
+
+    let err = client(&server, keys)
+        .get_host_registration()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, HbsError::Status { status, .. } if status == StatusCode::UNAUTHORIZED));
+    Ok(())
+}
+
+#[tokio::test]
+async fn slow_responses_time_out() -> Result<()> {
+    let server = HbsServer::builder()
+        .default_jurisdiction("Gibraltar")
+        .delay(Duration::from_secs(5))
+        .spawn("127.0.0.1:0".parse()?)
+        .await?;
+    let hbs = client(&server, host_keys())
+        .with_timeout(Duration::from_millis(100))
+        .with_retry_policy(
+            again::RetryPolicy::fixed(Duration::from_millis(10)).with_max_retries(1),
+        );
+
+    let err = hbs.get_host_registration().await.unwrap_err();
+    assert!(matches!(err, HbsError::Timeout));
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! `get_mem_proof` flow against the local mock of membrane proof server.
+
+use anyhow::Result;
+use ed25519_dalek::SigningKey;
+use holochain_types::prelude::AgentPubKey;
+use hpos_hc_connect::hpos_agent::{Admin, AuthError};
+use hpos_hc_connect::hpos_membrane_proof::get_mem_proof;
+use hpos_hc_connect::HposEnvironment;
+use mock_services::membrane_proof::{
+    MemProofServer, RegistrationError, ScriptedError, SignedMemProof, StatusCode,
+};
+
+fn admin(registration_code: &str) -> Admin {
+    let key = SigningKey::from_bytes(&[1; 32]).verifying_key();
+    Admin {
+        key: AgentPubKey::from_raw_32(key.to_bytes().to_vec()),
+        email: "host@holo.host".to_string(),
+        registration_code: registration_code.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn mem_proof_is_downloaded_or_rejected() -> Result<()> {
+    let server = MemProofServer::builder()
+        .error_for(
+            "rejected-code",
+            ScriptedError {
+                status: StatusCode::BAD_REQUEST,
+                body: RegistrationError::new("Registration code not found", ""),
+            },
+        )
+        .spawn("127.0.0.1:0".parse()?)
+        .await?;
+    let tmp = tempfile::tempdir()?;
+    let mem_proof_path = tmp.path().join("mem-proof");
+
+    let env = HposEnvironment {
+        holofuel_instance_role: Some("host".to_string()),
+        mem_proof_server_url: Some(server.url()),
+        mem_proof_path: Some(mem_proof_path.clone()),
+        ..Default::default()
+    };
+
+    let err = get_mem_proof(admin("rejected-code"), &env)
+        .await
+        .unwrap_err();
+    match err.downcast_ref::<AuthError>() {
+        Some(AuthError::RegistrationError(rejection)) => {
+            assert_eq!(rejection.error, "Registration code not found")
+        }
+        _ => panic!("expected a registration error, got {:#}", err),
+    }
+    assert!(!mem_proof_path.exists());
+
+    let mem_proof = get_mem_proof(admin("valid-code"), &env).await?;
+    let saved = std::fs::read_to_string(&mem_proof_path)?;
+    assert_eq!(base64::decode(&saved)?, mem_proof.bytes().to_vec());
+
+    let signed = SignedMemProof::decode(&saved)?;
+    signed.verify(&server.verifying_key())?;
+    assert_eq!(signed.content.role, "host");
+    assert_eq!(signed.content.registration_code, "valid-code");
+    assert_eq!(server.registrations().len(), 2);
+
+    // a read-only agent in the same process never asks the server
+    let read_only = HposEnvironment {
+        read_only_mem_proof: true,
+        ..env
+    };
+    let mem_proof = get_mem_proof(admin("other-code"), &read_only).await?;
+    assert_eq!(mem_proof.bytes().to_vec(), vec![0]);
+    assert_eq!(server.registrations().len(), 2);
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! `SlCloneManager` against an in-process mock conductor.
+
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use holochain_types::app::CreateCloneCellPayload;
+use holochain_types::prelude::{DnaModifiersOpt, SerializedBytes};
+use hpos_hc_connect::clock::SimulatedClock;
+use hpos_hc_connect::sl_clone_manager::{SlCloneAction, SlCloneManager, SlClonePolicy};
+use hpos_hc_connect::sl_utils::{sl_serialized_props, SlBucketMigration, SlDnaProperties};
+use hpos_hc_connect::{AdminWebsocket, AppConnection};
+use mock_conductor::{MockApp, MockConductor};
+use std::sync::Arc;
+
+const SL_APP_ID: &str = "uhCkkServiceLogger::servicelogger";
+
+#[tokio::test]
+async fn clones_are_created_and_expired_once() -> Result<()> {
+    let keystore = holochain_keystore::test_keystore();
+    let agent_key = keystore.new_sign_keypair_random().await?;
+    let conductor = MockConductor::builder()
+        .app(MockApp::new(SL_APP_ID, agent_key).role("servicelogger"))
+        .spawn()
+        .await?;
+    let mut admin_ws = AdminWebsocket::connect(conductor.admin_port()).await?;
+    let app = AppConnection::connect(&mut admin_ws, keystore, SL_APP_ID.to_string()).await?;
+
+    // last minutes of bucket 5, so bucket 6 is cloned ahead of time
+    let clock = SimulatedClock::new(Utc.with_ymd_and_hms(2024, 3, 24, 23, 55, 0).unwrap());
+    let mut manager = SlCloneManager::new(
+        app,
+        admin_ws,
+        "servicelogger".into(),
+        SlDnaProperties::default(),
+    )
+    .with_policy(SlClonePolicy {
+        days_in_bucket: 14,
+        minutes_before_bucket_to_clone: 9,
+        buckets_to_keep: 0,
+        migration: None,
+    })
+    .with_clock(Arc::new(clock.clone()));
+
+    let report = manager.run().await?;
+    assert!(report.is_success());
+    assert_eq!(report.time_bucket, 5);
+    let created: Vec<&str> = report.applied.iter().map(|a| a.name()).collect();
+    assert_eq!(created, vec!["14.5", "14.6"]);
+
+    // same decisions when run again
+    assert!(manager.run().await?.applied.is_empty());
+
+    clock.set(Utc.with_ymd_and_hms(2024, 3, 26, 0, 0, 0).unwrap());
+    let report = manager.run().await?;
+    assert!(matches!(
+        report.applied.as_slice(),
+        [SlCloneAction::Disable { name, .. }, SlCloneAction::Delete { .. }] if name == "14.5"
+    ));
+    let names: Vec<String> = manager
+        .clones()
+        .await?
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    assert_eq!(names, vec!["14.6"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn bucket_size_is_read_from_the_app_and_migrated() -> Result<()> {
+    let keystore = holochain_keystore::test_keystore();
+    let agent_key = keystore.new_sign_keypair_random().await?;
+    let properties = SlDnaProperties {
+        bucket_size: Some(7),
+        ..Default::default()
+    };
+    let conductor = MockConductor::builder()
+        .app(MockApp::new(SL_APP_ID, agent_key).role_with_properties(
+            "servicelogger",
+            SerializedBytes::try_from(sl_serialized_props(&properties))?,
+        ))
+        .spawn()
+        .await?;
+    let mut admin_ws = AdminWebsocket::connect(conductor.admin_port()).await?;
+    let mut app = AppConnection::connect(&mut admin_ws, keystore, SL_APP_ID.to_string()).await?;
+
+    // clone of the 14 day buckets used before the app switched to 7 days on 2024-03-18
+    app.create_clone(CreateCloneCellPayload {
+        role_name: "servicelogger".into(),
+        modifiers: DnaModifiersOpt::none().with_properties(sl_serialized_props(&SlDnaProperties {
+            bucket_size: Some(14),
+            time_bucket: Some(5),
+            ..Default::default()
+        })),
+        membrane_proof: None,
+        name: Some("14.5".to_string()),
+    })
+    .await?;
+
+    let clock = SimulatedClock::new(Utc.with_ymd_and_hms(2024, 4, 2, 0, 0, 0).unwrap());
+    let mut manager = SlCloneManager::for_installed_app(app, admin_ws, "servicelogger".into())
+        .await?
+        .with_clock(Arc::new(clock.clone()));
+    let report = manager.run().await?;
+    assert_eq!(report.time_bucket, 13);
+    let names: Vec<&str> = report.applied.iter().map(|a| a.name()).collect();
+    assert_eq!(names, vec!["7.13"]);
+
+    let mut manager = manager.with_migration(SlBucketMigration::new(
+        14,
+        7,
+        Utc.with_ymd_and_hms(2024, 3, 18, 0, 0, 0).unwrap(),
+    ));
+    let report = manager.run().await?;
+    assert!(matches!(
+        report.applied.as_slice(),
+        [SlCloneAction::Disable { name, .. }, SlCloneAction::Delete { .. }] if name == "14.5"
+    ));
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use crate::state::{random_bytes, MockApp, SharedState};
+use holochain_conductor_api::{
+    AdminRequest, AdminResponse, AppAuthenticationTokenIssued, AppInterfaceInfo, AppStatusFilter,
+    ExternalApiWireError,
+};
+use holochain_types::app::{AppManifest, InstallAppPayload};
+use holochain_types::prelude::{
+    AgentPubKey, AppBundleSource, CellId, CellProvisioning, CloneCellId, DnaHash, SerializedBytes,
+};
+use mr_bundle::Bundle;
+use tracing::debug;
+
+/// Handles a single admin request, mutating conductor state the same way a real conductor would
+pub(crate) fn handle(state: &SharedState, request: AdminRequest) -> AdminResponse {
+    debug!("mock conductor admin request: {:?}", request);
+    let mut state = state.lock().expect("mock conductor state lock poisoned");
+
+    match request {
+        AdminRequest::AttachAppInterface {
+            port: _,
+            allowed_origins,
+            installed_app_id,
+        } => {
+            // All app interfaces are served by the single app websocket of the mock
+            let port = state.app_port;
+            state.app_interfaces.push(AppInterfaceInfo {
+                port,
+                allowed_origins,
+                installed_app_id,
+            });
+            AdminResponse::AppInterfaceAttached { port }
+        }
+        AdminRequest::ListAppInterfaces => {
+            AdminResponse::AppInterfacesListed(state.app_interfaces.clone())
+        }
+        AdminRequest::IssueAppAuthenticationToken(payload) => {
+            if state.app(&payload.installed_app_id).is_none() {
+                return error(format!("AppNotInstalled({})", payload.installed_app_id));
+            }
+            let token = random_bytes::<16>().to_vec();
+            state.tokens.insert(token.clone(), payload.installed_app_id);
+            AdminResponse::AppAuthenticationTokenIssued(AppAuthenticationTokenIssued {
+                token,
+                expires_at: None,
+            })
+        }
+        AdminRequest::ListApps { status_filter } => AdminResponse::AppsListed(
+            state
+                .apps
+                .iter()
+                .filter(|a| match status_filter {
+                    None => true,
+                    Some(AppStatusFilter::Enabled) | Some(AppStatusFilter::Running) => a.enabled,
+                    Some(AppStatusFilter::Disabled) | Some(AppStatusFilter::Stopped) => !a.enabled,
+                    Some(AppStatusFilter::Paused) => false,
+                })
+                .map(MockApp::app_info)
+                .collect(),
+        ),
+        AdminRequest::InstallApp(payload) => {
+            let Some(installed_app_id) = payload.installed_app_id.clone() else {
+                return error("mock conductor requires installed_app_id".to_string());
+            };
+            if state.app(&installed_app_id).is_some() {
+                return error(format!("AppAlreadyInstalled({})", installed_app_id));
+            }
+            let agent_key = payload
+                .agent_key
+                .clone()
+                .unwrap_or_else(|| AgentPubKey::from_raw_32(random_bytes::<32>().to_vec()));
+            let mut app = match provision(MockApp::new(installed_app_id, agent_key), &payload) {
+                Ok(app) => app.disabled(),
+                Err(e) => return error(e),
+            };
+            app.membrane_proofs = payload.membrane_proofs.clone().unwrap_or_default();
+            let app_info = app.app_info();
+            state.apps.push(app);
+            AdminResponse::AppInstalled(app_info)
+        }
+        AdminRequest::EnableApp { installed_app_id } => match state.app_mut(&installed_app_id) {
+            Some(app) => {
+                app.enabled = true;
+                AdminResponse::AppEnabled {
+                    app: app.app_info(),
+                    errors: vec![],
+                }
+            }
+            None => error(format!("AppNotInstalled({})", installed_app_id)),
+        },
+        AdminRequest::DisableApp { installed_app_id } => match state.app_mut(&installed_app_id) {
+            Some(app) => {
+                app.enabled = false;
+                AdminResponse::AppDisabled
+            }
+            None => error(format!("AppNotInstalled({})", installed_app_id)),
+        },
+        AdminRequest::UninstallApp {
+            installed_app_id, ..
+        } => {
+            if state.app(&installed_app_id).is_none() {
+                return error(format!("AppNotInstalled({})", installed_app_id));
+            }
+            state
+                .apps
+                .retain(|a| a.installed_app_id != installed_app_id);
+            AdminResponse::AppUninstalled
+        }
+        AdminRequest::GenerateAgentPubKey => AdminResponse::AgentPubKeyGenerated(
+            AgentPubKey::from_raw_32(random_bytes::<32>().to_vec()),
+        ),
+        AdminRequest::DeleteCloneCell(payload) => match state.app_mut(&payload.app_id) {
+            Some(app) => {
+                let before = app.clones.len();
+                app.clones.retain(|(_, c)| match &payload.clone_cell_id {
+                    CloneCellId::CloneId(clone_id) => &c.clone_id != clone_id,
+                    CloneCellId::DnaHash(dna_hash) => c.cell_id.dna_hash() != dna_hash,
+                });
+                if app.clones.len() == before {
+                    error(format!("CloneCellNotFound({:?})", payload.clone_cell_id))
+                } else {
+                    AdminResponse::CloneCellDeleted
+                }
+            }
+            None => error(format!("AppNotInstalled({})", payload.app_id)),
+        },
+        AdminRequest::UpdateCoordinators(payload) => {
+            let exists = state
+                .apps
+                .iter()
+                .any(|app| app.roles.iter().any(|r| r.cell_id == payload.cell_id));
+            if !exists {
+                return error(format!("CellMissing({:?})", payload.cell_id));
+            }
+            state.coordinator_updates.push(payload.cell_id);
+            AdminResponse::CoordinatorsUpdated
+        }
+        request => error(format!(
+            "mock conductor does not support admin request {:?}",
+            request
+        )),
+    }
+}
+
+/// Adds a cell for every role of the bundle's manifest. Roles provisioned from existing cells
+/// take them from `existing_cells`, new cells get the manifest's `installed_hash` as DNA hash
+/// if it has one, a random one otherwise.
+fn provision(mut app: MockApp, payload: &InstallAppPayload) -> Result<MockApp, String> {
+    let bundle = match &payload.source {
+        AppBundleSource::Bundle(bundle) => bundle.clone().into_inner(),
+        AppBundleSource::Path(path) => std::fs::read(path)
+            .map_err(|e| format!("failed to read bundle {}: {:?}", path.display(), e))
+            .and_then(|bytes| {
+                Bundle::decode(&bytes).map_err(|e| format!("invalid app bundle: {:?}", e))
+            })?,
+    };
+    let AppManifest::V1(manifest) = bundle.manifest().clone();
+    for role in manifest.roles {
+        if let Some(CellProvisioning::UseExisting { .. }) = role.provisioning {
+            let cell_id = payload
+                .existing_cells
+                .get(&role.name)
+                .ok_or_else(|| format!("CellMissing({})", role.name))?;
+            app = app.role_with_cell(role.name, cell_id.clone());
+            continue;
+        }
+        let properties = match role.dna.modifiers.properties {
+            Some(properties) => SerializedBytes::try_from(properties).unwrap_or_default(),
+            None => SerializedBytes::default(),
+        };
+        app = match role.dna.installed_hash {
+            Some(hash) => {
+                let cell_id = CellId::new(DnaHash::from(hash), app.agent_key.clone());
+                app.role_with_cell_and_properties(role.name, cell_id, properties)
+            }
+            None => app.role_with_properties(role.name, properties),
+        };
+    }
+    Ok(app)
+}
+
+fn error(message: String) -> AdminResponse {
+    AdminResponse::Error(ExternalApiWireError::InternalError(message))
+}
@@ -0,0 +1,97 @@
+use crate::state::{RecordedZomeCall, SharedState, State};
+use holochain_conductor_api::{AppRequest, AppResponse, ExternalApiWireError};
+use holochain_types::prelude::{CloneCellId, ClonedCell, SerializedBytes};
+use tracing::debug;
+
+/// Handles a single app request on a connection authenticated for `installed_app_id`
+pub(crate) fn handle(
+    state: &SharedState,
+    installed_app_id: &str,
+    request: AppRequest,
+) -> AppResponse {
+    debug!("mock conductor app request: {:?}", request);
+    let mut state = state.lock().expect("mock conductor state lock poisoned");
+
+    match request {
+        AppRequest::AppInfo => {
+            AppResponse::AppInfo(state.app(installed_app_id).map(|a| a.app_info()))
+        }
+        AppRequest::CallZome(call) => {
+            state.zome_calls.push(RecordedZomeCall {
+                installed_app_id: installed_app_id.to_string(),
+                cell_id: call.cell_id.clone(),
+                zome_name: call.zome_name.clone(),
+                fn_name: call.fn_name.clone(),
+                payload: call.payload.clone(),
+            });
+            match state.app(installed_app_id) {
+                Some(app) if !app.enabled => {
+                    return error(format!("AppNotRunning({})", installed_app_id))
+                }
+                None => return error(format!("AppNotInstalled({})", installed_app_id)),
+                _ => {}
+            }
+            match state
+                .handlers
+                .get(&(call.zome_name.clone(), call.fn_name.clone()))
+            {
+                Some(handler) => match handler(call.payload) {
+                    Ok(response) => AppResponse::ZomeCalled(Box::new(response)),
+                    Err(e) => error(format!("{:?}", e)),
+                },
+                None => error(format!(
+                    "mock conductor has no handler for {}/{}",
+                    call.zome_name, call.fn_name
+                )),
+            }
+        }
+        AppRequest::CreateCloneCell(payload) => {
+            let Some(app) = state.app_mut(installed_app_id) else {
+                return error(format!("AppNotInstalled({})", installed_app_id));
+            };
+            let properties = match payload.modifiers.properties {
+                Some(properties) => SerializedBytes::try_from(properties).unwrap_or_default(),
+                None => SerializedBytes::default(),
+            };
+            match app.add_clone(&payload.role_name, payload.name, properties) {
+                Some(clone) => AppResponse::CloneCellCreated(clone),
+                None => error(format!("AppRoleMissing({})", payload.role_name)),
+            }
+        }
+        AppRequest::DisableCloneCell(payload) => {
+            match set_clone_enabled(&mut state, installed_app_id, &payload.clone_cell_id, false) {
+                Some(_) => AppResponse::CloneCellDisabled,
+                None => error(format!("CloneCellNotFound({:?})", payload.clone_cell_id)),
+            }
+        }
+        AppRequest::EnableCloneCell(payload) => {
+            match set_clone_enabled(&mut state, installed_app_id, &payload.clone_cell_id, true) {
+                Some(clone) => AppResponse::CloneCellEnabled(clone),
+                None => error(format!("CloneCellNotFound({:?})", payload.clone_cell_id)),
+            }
+        }
+        request => error(format!(
+            "mock conductor does not support app request {:?}",
+            request
+        )),
+    }
+}
+
+fn set_clone_enabled(
+    state: &mut State,
+    installed_app_id: &str,
+    clone_cell_id: &CloneCellId,
+    enabled: bool,
+) -> Option<ClonedCell> {
+    let app = state.app_mut(installed_app_id)?;
+    let (_, clone) = app.clones.iter_mut().find(|(_, c)| match clone_cell_id {
+        CloneCellId::CloneId(clone_id) => &c.clone_id == clone_id,
+        CloneCellId::DnaHash(dna_hash) => c.cell_id.dna_hash() == dna_hash,
+    })?;
+    clone.enabled = enabled;
+    Some(clone.clone())
+}
+
+fn error(message: String) -> AppResponse {
+    AppResponse::Error(ExternalApiWireError::InternalError(message))
+}
@@ -0,0 +1,344 @@
+//! In-process mock of holochain conductor's admin and app websocket interfaces,
+//! so that `hpos_hc_connect` consumers can be tested without a running conductor.
+
+mod admin;
+mod app;
+mod state;
+
+pub use state::{MockApp, MockRole, RecordedZomeCall};
+
+use anyhow::{anyhow, Context, Result};
+use holochain_conductor_api::{AdminRequest, AppAuthenticationRequest, AppRequest};
+use holochain_types::prelude::{AppSignal, CellId, ExternIO, FunctionName, ZomeName};
+use holochain_types::signal::Signal;
+use holochain_websocket::{
+    ReceiveMessage, WebsocketConfig, WebsocketListener, WebsocketReceiver, WebsocketSender,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use state::{SharedState, State};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, warn};
+
+/// Handler of a zome function, receives encoded payload and returns encoded response
+pub type ZomeHandler = Arc<dyn Fn(ExternIO) -> Result<ExternIO> + Send + Sync>;
+
+type AppConnections = Arc<Mutex<Vec<(String, WebsocketSender)>>>;
+
+#[derive(Default)]
+pub struct MockConductorBuilder {
+    state: State,
+}
+
+impl MockConductorBuilder {
+    /// Installs an app before the conductor starts accepting connections
+    pub fn app(mut self, app: MockApp) -> Self {
+        self.state.apps.push(app);
+        self
+    }
+
+    /// Registers a handler for `zome_name/fn_name`, shared by all the cells of all the apps.
+    /// Payload is decoded into `I` and the returned `O` is encoded as zome call response,
+    /// an error is returned to the client as `AppResponse::Error`.
+    pub fn zome_fn<I, O, F>(
+        mut self,
+        zome_name: impl Into<ZomeName>,
+        fn_name: impl Into<FunctionName>,
+        f: F,
+    ) -> Self
+    where
+        I: DeserializeOwned + std::fmt::Debug,
+        O: Serialize + std::fmt::Debug,
+        F: Fn(I) -> Result<O> + Send + Sync + 'static,
+    {
+        let handler: ZomeHandler = Arc::new(move |payload: ExternIO| {
+            let input: I = payload
+                .decode()
+                .map_err(|e| anyhow!("failed to decode zome call payload: {:?}", e))?;
+            ExternIO::encode(f(input)?)
+                .map_err(|e| anyhow!("failed to encode zome call response: {:?}", e))
+        });
+        self.state
+            .handlers
+            .insert((zome_name.into(), fn_name.into()), handler);
+        self
+    }
+
+    /// Binds admin and app websockets on random localhost ports and starts serving them
+    pub async fn spawn(mut self) -> Result<MockConductor> {
+        let admin_listener = bind()
+            .await
+            .context("failed to bind mock admin interface")?;
+        let app_listener = bind().await.context("failed to bind mock app interface")?;
+        let admin_port = admin_listener.local_addr()?.port();
+        let app_port = app_listener.local_addr()?.port();
+        debug!(admin_port, app_port, "mock conductor listening");
+
+        self.state.app_port = app_port;
+        let state: SharedState = Arc::new(Mutex::new(self.state));
+        let app_connections = AppConnections::default();
+        let (disconnect, _) = broadcast::channel(1);
+
+        let tasks = vec![
+            tokio::spawn(serve_admin(
+                admin_listener,
+                state.clone(),
+                disconnect.clone(),
+            )),
+            tokio::spawn(serve_app(
+                app_listener,
+                state.clone(),
+                app_connections.clone(),
+                disconnect.clone(),
+            )),
+        ];
+
+        Ok(MockConductor {
+            admin_port,
+            app_port,
+            state,
+            app_connections,
+            disconnect,
+            tasks,
+        })
+    }
+}
+
+/// Running mock conductor. Stops serving both interfaces when dropped.
+pub struct MockConductor {
+    admin_port: u16,
+    app_port: u16,
+    state: SharedState,
+    app_connections: AppConnections,
+    disconnect: broadcast::Sender<()>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockConductor {
+    pub fn builder() -> MockConductorBuilder {
+        MockConductorBuilder::default()
+    }
+
+    pub fn admin_port(&self) -> u16 {
+        self.admin_port
+    }
+
+    pub fn app_port(&self) -> u16 {
+        self.app_port
+    }
+
+    /// Snapshot of all installed apps
+    pub fn installed_apps(&self) -> Vec<MockApp> {
+        self.state().apps.clone()
+    }
+
+    /// Ids of installed apps which are currently enabled
+    pub fn enabled_apps(&self) -> Vec<String> {
+        self.state()
+            .apps
+            .iter()
+            .filter(|a| a.enabled)
+            .map(|a| a.installed_app_id.clone())
+            .collect()
+    }
+
+    /// All zome calls received so far, in order of arrival
+    pub fn zome_calls(&self) -> Vec<RecordedZomeCall> {
+        self.state().zome_calls.clone()
+    }
+
+    /// Cells whose coordinator zomes were updated so far, in order of the requests
+    pub fn coordinator_updates(&self) -> Vec<CellId> {
+        self.state().coordinator_updates.clone()
+    }
+
+    /// Installs an app while the conductor is running
+    pub fn install_app(&self, app: MockApp) {
+        self.state().apps.push(app);
+    }
+
+    /// Closes all open admin and app connections, like a conductor restart would.
+    /// Installed apps and issued tokens are kept and new connections are accepted right away.
+    pub fn drop_connections(&self) {
+        self.app_connections
+            .lock()
+            .expect("mock conductor connections lock poisoned")
+            .clear();
+        // Nobody listens if there are no open connections
+        let _ = self.disconnect.send(());
+    }
+
+    /// Sends an app signal to every app connection authenticated for `installed_app_id`
+    pub async fn emit_signal<T: Serialize + std::fmt::Debug>(
+        &self,
+        installed_app_id: &str,
+        cell_id: CellId,
+        zome_name: impl Into<ZomeName>,
+        payload: T,
+    ) -> Result<()> {
+        let signal = Signal::App {
+            cell_id,
+            zome_name: zome_name.into(),
+            signal: AppSignal::new(
+                ExternIO::encode(payload)
+                    .map_err(|e| anyhow!("failed to encode signal payload: {:?}", e))?,
+            ),
+        };
+        let senders: Vec<WebsocketSender> = self
+            .app_connections
+            .lock()
+            .expect("mock conductor connections lock poisoned")
+            .iter()
+            .filter(|(app_id, _)| app_id == installed_app_id)
+            .map(|(_, tx)| tx.clone())
+            .collect();
+        for tx in senders {
+            // Connections closed by the client are not pruned, so failures are not fatal
+            if let Err(e) = tx.signal(&signal).await {
+                debug!(
+                    "failed to deliver signal to a closed app connection: {:?}",
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("mock conductor state lock poisoned")
+    }
+}
+
+impl Drop for MockConductor {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Binds to the first address `localhost` resolves to, same as AdminWebsocket and
+// AppConnection do when connecting
+async fn bind() -> std::io::Result<WebsocketListener> {
+    WebsocketListener::bind(Arc::new(WebsocketConfig::LISTENER_DEFAULT), "localhost:0").await
+}
+
+// Connection tasks live in a JoinSet owned by the accept loop, so aborting the loop
+// also closes all the connections accepted by it
+async fn serve_admin(
+    listener: WebsocketListener,
+    state: SharedState,
+    disconnect: broadcast::Sender<()>,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        let (tx, mut rx) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("mock admin interface failed to accept connection: {:?}", e);
+                continue;
+            }
+        };
+        let state = state.clone();
+        let mut disconnect = disconnect.subscribe();
+        connections.spawn(async move {
+            // Sender has to outlive the receiver, otherwise connection is closed
+            let _tx = tx;
+            loop {
+                let message = tokio::select! {
+                    message = rx.recv::<AdminRequest>() => message,
+                    _ = disconnect.recv() => break,
+                };
+                let Ok(message) = message else {
+                    break;
+                };
+                if let ReceiveMessage::Request(request, respond) = message {
+                    if respond
+                        .respond(admin::handle(&state, request))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn serve_app(
+    listener: WebsocketListener,
+    state: SharedState,
+    app_connections: AppConnections,
+    disconnect: broadcast::Sender<()>,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        let (tx, rx) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("mock app interface failed to accept connection: {:?}", e);
+                continue;
+            }
+        };
+        connections.spawn(serve_app_connection(
+            tx,
+            rx,
+            state.clone(),
+            app_connections.clone(),
+            disconnect.subscribe(),
+        ));
+    }
+}
+
+async fn serve_app_connection(
+    tx: WebsocketSender,
+    mut rx: WebsocketReceiver,
+    state: SharedState,
+    app_connections: AppConnections,
+    mut disconnect: broadcast::Receiver<()>,
+) {
+    // Real conductor drops app connections which do not authenticate with their first message
+    let installed_app_id = match rx.recv::<AppRequest>().await {
+        Ok(ReceiveMessage::Authenticate(bytes)) => {
+            let Ok(AppAuthenticationRequest { token }) = rmp_serde::from_slice(&bytes) else {
+                return;
+            };
+            let app_id = state
+                .lock()
+                .expect("mock conductor state lock poisoned")
+                .tokens
+                .get(&token)
+                .cloned();
+            match app_id {
+                Some(app_id) => app_id,
+                None => return,
+            }
+        }
+        _ => return,
+    };
+
+    app_connections
+        .lock()
+        .expect("mock conductor connections lock poisoned")
+        .push((installed_app_id.clone(), tx));
+
+    loop {
+        let message = tokio::select! {
+            message = rx.recv::<AppRequest>() => message,
+            _ = disconnect.recv() => break,
+        };
+        let Ok(message) = message else {
+            break;
+        };
+        if let ReceiveMessage::Request(request, respond) = message {
+            let response = app::handle(&state, &installed_app_id, request);
+            if respond.respond(response).await.is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use holochain_conductor_api::{
+    AppInfo, AppInfoStatus, AppInterfaceInfo, CellInfo, ProvisionedCell,
+};
+use holochain_types::{
+    app::{AppManifest, DisabledAppReason},
+    prelude::{
+        AgentPubKey, CellId, CloneId, ClonedCell, DnaHash, DnaModifiers, ExternIO, FunctionName,
+        MembraneProof, RoleName, SerializedBytes, Timestamp, ZomeName,
+    },
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::ZomeHandler;
+
+/// An app installed in the mock conductor
+#[derive(Clone, Debug)]
+pub struct MockApp {
+    pub installed_app_id: String,
+    pub agent_key: AgentPubKey,
+    pub roles: Vec<MockRole>,
+    pub enabled: bool,
+    /// Membrane proofs by role name the app was installed with
+    pub membrane_proofs: HashMap<RoleName, MembraneProof>,
+    pub(crate) clones: Vec<(RoleName, ClonedCell)>,
+}
+
+/// Provisioned cell of a `MockApp`
+#[derive(Clone, Debug)]
+pub struct MockRole {
+    pub name: RoleName,
+    pub cell_id: CellId,
+    pub properties: SerializedBytes,
+}
+
+impl MockApp {
+    /// Creates an enabled app without any cells
+    pub fn new(installed_app_id: impl Into<String>, agent_key: AgentPubKey) -> Self {
+        Self {
+            installed_app_id: installed_app_id.into(),
+            agent_key,
+            roles: vec![],
+            enabled: true,
+            membrane_proofs: HashMap::new(),
+            clones: vec![],
+        }
+    }
+
+    /// Adds a provisioned cell with a random dna hash for given role
+    pub fn role(self, name: impl Into<RoleName>) -> Self {
+        self.role_with_properties(name, SerializedBytes::default())
+    }
+
+    /// Adds a provisioned cell with a random dna hash and given dna properties for given role
+    pub fn role_with_properties(
+        self,
+        name: impl Into<RoleName>,
+        properties: SerializedBytes,
+    ) -> Self {
+        let cell_id = CellId::new(random_dna_hash(), self.agent_key.clone());
+        self.role_with_cell_and_properties(name, cell_id, properties)
+    }
+
+    /// Adds a provisioned cell with given cell id for given role, eg. a cell shared with
+    /// another app or one whose dna hash a test has to know in advance
+    pub fn role_with_cell(self, name: impl Into<RoleName>, cell_id: CellId) -> Self {
+        self.role_with_cell_and_properties(name, cell_id, SerializedBytes::default())
+    }
+
+    pub(crate) fn role_with_cell_and_properties(
+        mut self,
+        name: impl Into<RoleName>,
+        cell_id: CellId,
+        properties: SerializedBytes,
+    ) -> Self {
+        self.roles.push(MockRole {
+            name: name.into(),
+            cell_id,
+            properties,
+        });
+        self
+    }
+
+    /// Marks app as installed but disabled
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    /// Returns cell id of provisioned cell for given role
+    pub fn cell_id(&self, role_name: &str) -> Option<CellId> {
+        self.roles
+            .iter()
+            .find(|r| r.name == role_name)
+            .map(|r| r.cell_id.clone())
+    }
+
+    pub(crate) fn app_info(&self) -> AppInfo {
+        let mut cell_info: HashMap<RoleName, Vec<CellInfo>> = HashMap::new();
+        for role in &self.roles {
+            cell_info
+                .entry(role.name.clone())
+                .or_default()
+                .push(CellInfo::Provisioned(ProvisionedCell {
+                    cell_id: role.cell_id.clone(),
+                    dna_modifiers: dna_modifiers(role.properties.clone()),
+                    name: role.name.clone(),
+                }));
+        }
+        for (role_name, clone) in &self.clones {
+            cell_info
+                .entry(role_name.clone())
+                .or_default()
+                .push(CellInfo::Cloned(clone.clone()));
+        }
+
+        let status = if self.enabled {
+            AppInfoStatus::Running
+        } else {
+            AppInfoStatus::Disabled {
+                reason: DisabledAppReason::User,
+            }
+        };
+
+        AppInfo {
+            installed_app_id: self.installed_app_id.clone(),
+            cell_info,
+            status,
+            agent_pub_key: self.agent_key.clone(),
+            manifest: manifest(&self.installed_app_id),
+            installed_at: Timestamp::now(),
+        }
+    }
+
+    pub(crate) fn add_clone(
+        &mut self,
+        role_name: &RoleName,
+        name: Option<String>,
+        properties: SerializedBytes,
+    ) -> Option<ClonedCell> {
+        let original = self.roles.iter().find(|r| &r.name == role_name)?;
+        let clone_index = self.clones.iter().filter(|(r, _)| r == role_name).count() as u32;
+        let clone = ClonedCell {
+            cell_id: CellId::new(random_dna_hash(), self.agent_key.clone()),
+            clone_id: CloneId::new(role_name, clone_index),
+            original_dna_hash: original.cell_id.dna_hash().clone(),
+            dna_modifiers: dna_modifiers(properties),
+            name: name.unwrap_or_default(),
+            enabled: true,
+        };
+        self.clones.push((role_name.clone(), clone.clone()));
+        Some(clone)
+    }
+}
+
+/// Zome call received by the mock conductor
+#[derive(Clone, Debug)]
+pub struct RecordedZomeCall {
+    pub installed_app_id: String,
+    pub cell_id: CellId,
+    pub zome_name: ZomeName,
+    pub fn_name: FunctionName,
+    pub payload: ExternIO,
+}
+
+#[derive(Default)]
+pub(crate) struct State {
+    pub apps: Vec<MockApp>,
+    pub handlers: HashMap<(ZomeName, FunctionName), ZomeHandler>,
+    pub app_interfaces: Vec<AppInterfaceInfo>,
+    pub tokens: HashMap<Vec<u8>, String>,
+    pub zome_calls: Vec<RecordedZomeCall>,
+    pub coordinator_updates: Vec<CellId>,
+    pub app_port: u16,
+}
+
+pub(crate) type SharedState = Arc<Mutex<State>>;
+
+impl State {
+    pub fn app(&self, installed_app_id: &str) -> Option<&MockApp> {
+        self.apps
+            .iter()
+            .find(|a| a.installed_app_id == installed_app_id)
+    }
+
+    pub fn app_mut(&mut self, installed_app_id: &str) -> Option<&mut MockApp> {
+        self.apps
+            .iter_mut()
+            .find(|a| a.installed_app_id == installed_app_id)
+    }
+}
+
+fn dna_modifiers(properties: SerializedBytes) -> DnaModifiers {
+    DnaModifiers {
+        network_seed: String::new(),
+        properties,
+        origin_time: Timestamp::HOLOCHAIN_EPOCH,
+        quantum_time: Duration::from_secs(5 * 60),
+    }
+}
+
+// Manifest is not inspected by any of the hpos_hc_connect consumers, so mock app
+// gets the smallest valid one
+fn manifest(name: &str) -> AppManifest {
+    serde_yaml::from_str(&format!(
+        "manifest_version: \"1\"\nname: {:?}\ndescription: ~\nroles: []\n",
+        name
+    ))
+    .expect("mock app manifest should deserialize")
+}
+
+pub(crate) fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).expect("failed to get random bytes");
+    bytes
+}
+
+fn random_dna_hash() -> DnaHash {
+    DnaHash::from_raw_32(random_bytes::<32>().to_vec())
+}
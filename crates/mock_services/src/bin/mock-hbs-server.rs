@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use mock_services::hbs::{HbsServer, StatusCode};
+use std::net::SocketAddr;
+use structopt::StructOpt;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "mock-hbs-server",
+    about = "Local stand-in for Holo backend services"
+)]
+struct Opt {
+    /// Address to listen on
+    #[structopt(long, default_value = "127.0.0.1:8801")]
+    addr: SocketAddr,
+    /// Jurisdiction of every host asking for its registration
+    #[structopt(long, default_value = "Gibraltar")]
+    jurisdiction: String,
+    /// Answer this many first requests with 503 Service Unavailable
+    #[structopt(long, default_value = "0")]
+    fail_first: usize,
+    /// Accept requests without a valid signature
+    #[structopt(long)]
+    skip_signature_check: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+    let opt = Opt::from_args();
+
+    let mut builder = HbsServer::builder()
+        .default_jurisdiction(opt.jurisdiction)
+        .fail_next(StatusCode::SERVICE_UNAVAILABLE, opt.fail_first);
+    if opt.skip_signature_check {
+        builder = builder.skip_signature_check();
+    }
+
+    let server = builder.spawn(opt.addr).await?;
+    println!("HBS_URL={}", server.url());
+
+    tokio::signal::ctrl_c()
+        .await
+        .context("failed to listen for ctrl-c")?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::SigningKey;
+use mock_services::membrane_proof::{
+    MemProofServer, RegistrationError, ScriptedError, StatusCode, DEFAULT_SIGNING_KEY_SEED,
+};
+use std::net::SocketAddr;
+use structopt::StructOpt;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "mock-membrane-proof-server",
+    about = "Local stand-in for Holo membrane proof server"
+)]
+struct Opt {
+    /// Address to listen on
+    #[structopt(long, default_value = "127.0.0.1:8800")]
+    addr: SocketAddr,
+    /// Base64 encoded 32 byte seed of the key signing mem proofs
+    #[structopt(long)]
+    signing_key_seed: Option<String>,
+    /// Registration code answered with RegistrationError, can be repeated
+    #[structopt(long)]
+    reject_code: Vec<String>,
+    /// Answer every registration with RegistrationError
+    #[structopt(long)]
+    reject_all: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+    let opt = Opt::from_args();
+
+    let seed = match opt.signing_key_seed {
+        Some(seed) => base64::decode(seed)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("signing key seed has to be 32 bytes long"))?,
+        None => DEFAULT_SIGNING_KEY_SEED,
+    };
+    let rejection = ScriptedError {
+        status: StatusCode::BAD_REQUEST,
+        body: RegistrationError::new(
+            "Registration code rejected",
+            "mock membrane proof server was told to reject this registration",
+        ),
+    };
+
+    let mut builder = MemProofServer::builder().signing_key(SigningKey::from_bytes(&seed));
+    for code in opt.reject_code {
+        builder = builder.error_for(code, rejection.clone());
+    }
+    if opt.reject_all {
+        builder = builder.error_for_all(rejection);
+    }
+
+    let server = builder.spawn(opt.addr).await?;
+    println!("MEM_PROOF_SERVER_URL={}", server.url());
+    println!(
+        "signing key: {}",
+        base64::encode(server.verifying_key().as_bytes())
+    );
+
+    tokio::signal::ctrl_c()
+        .await
+        .context("failed to listen for ctrl-c")?;
+    Ok(())
+}
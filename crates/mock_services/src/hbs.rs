@@ -0,0 +1,228 @@
+//! Stand-in for Holo backend services (HBS) queried by `hpos_hc_connect::hbs::HbsClient`.
+//!
+//! Serves `POST /auth/api/v1/holo-client` taking the `HoloClientPayload` JSON of `HbsClient`.
+//! The `X-Signature` header has to be a valid signature of the msgpack encoded payload by the
+//! key in `pubKey`, otherwise the request is answered with 401. Registrations are kept
+//! per host key and can be changed while the server runs, so jurisdiction updates can be tested.
+//! Failures and slow responses can be scripted to exercise retries and timeouts.
+
+use anyhow::{Context, Result};
+use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tracing::{debug, info};
+
+pub use axum::http::StatusCode;
+
+pub use hpos_hc_connect::hbs::{HoloClientPayload, RegistrationRecord, HOLO_CLIENT_PATH};
+
+/// Record of a verified host registered in `jurisdiction`
+pub fn registration_record(
+    public_key: impl Into<String>,
+    jurisdiction: impl Into<String>,
+) -> RegistrationRecord {
+    let public_key = public_key.into();
+    RegistrationRecord {
+        id: format!("mock-{}", public_key),
+        email: String::new(),
+        access_token: "mock-access-token".to_string(),
+        permissions: vec!["host".to_string()],
+        kyc: "holo_kyc_2".to_string(),
+        jurisdiction: jurisdiction.into(),
+        public_key,
+    }
+}
+
+/// Decodes ed25519 key out of a holochain encoded agent key
+pub fn decode_agent_key(agent_key: &str) -> Option<VerifyingKey> {
+    let bytes =
+        base64::decode_config(agent_key.strip_prefix('u')?, base64::URL_SAFE_NO_PAD).ok()?;
+    let key: [u8; 32] = bytes.get(3..35)?.try_into().ok()?;
+    VerifyingKey::from_bytes(&key).ok()
+}
+
+#[derive(Default)]
+struct Script {
+    failures: VecDeque<StatusCode>,
+    delay: Option<Duration>,
+    skip_signature_check: bool,
+}
+
+#[derive(Default)]
+struct ServerState {
+    registrations: HashMap<String, RegistrationRecord>,
+    default_jurisdiction: Option<String>,
+    script: Script,
+    requests: Vec<HoloClientPayload>,
+}
+
+type SharedState = Arc<Mutex<ServerState>>;
+
+#[derive(Default)]
+pub struct HbsServerBuilder {
+    state: ServerState,
+}
+
+impl HbsServerBuilder {
+    /// Answers requests of the host with `record.public_key` with `record`
+    pub fn registration(mut self, record: RegistrationRecord) -> Self {
+        self.state
+            .registrations
+            .insert(record.public_key.clone(), record);
+        self
+    }
+
+    /// Answers requests of hosts without a registration with a record in `jurisdiction`,
+    /// otherwise they get 404
+    pub fn default_jurisdiction(mut self, jurisdiction: impl Into<String>) -> Self {
+        self.state.default_jurisdiction = Some(jurisdiction.into());
+        self
+    }
+
+    /// Answers the next `times` requests with `status`
+    pub fn fail_next(mut self, status: StatusCode, times: usize) -> Self {
+        self.state
+            .script
+            .failures
+            .extend(std::iter::repeat_n(status, times));
+        self
+    }
+
+    /// Waits `delay` before answering each request
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.state.script.delay = Some(delay);
+        self
+    }
+
+    /// Accepts requests without a valid `X-Signature`
+    pub fn skip_signature_check(mut self) -> Self {
+        self.state.script.skip_signature_check = true;
+        self
+    }
+
+    /// Starts serving on `addr`, use port 0 to let OS pick a free one
+    pub async fn spawn(self, addr: SocketAddr) -> Result<HbsServer> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .context(format!("failed to bind HBS server to {}", addr))?;
+        let addr = listener.local_addr()?;
+        let state: SharedState = Arc::new(Mutex::new(self.state));
+
+        let app = Router::new()
+            .route(HOLO_CLIENT_PATH, post(holo_client))
+            .with_state(state.clone());
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("HBS server stopped: {:?}", e);
+            }
+        });
+        info!("mock HBS server listening on {}", addr);
+
+        Ok(HbsServer { addr, state, task })
+    }
+}
+
+/// Running HBS server, stops when dropped
+pub struct HbsServer {
+    addr: SocketAddr,
+    state: SharedState,
+    task: JoinHandle<()>,
+}
+
+impl HbsServer {
+    pub fn builder() -> HbsServerBuilder {
+        HbsServerBuilder::default()
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Value for HBS_URL
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Changes jurisdiction of a host, registering it if needed
+    pub fn set_jurisdiction(&self, public_key: &str, jurisdiction: impl Into<String>) {
+        let jurisdiction = jurisdiction.into();
+        self.state()
+            .registrations
+            .entry(public_key.to_string())
+            .and_modify(|record| record.jurisdiction = jurisdiction.clone())
+            .or_insert_with(|| registration_record(public_key, jurisdiction));
+    }
+
+    /// All requests received so far, including the failed ones
+    pub fn requests(&self) -> Vec<HoloClientPayload> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ServerState> {
+        self.state.lock().expect("HBS server state lock poisoned")
+    }
+}
+
+impl Drop for HbsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn holo_client(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(payload): Json<HoloClientPayload>,
+) -> Result<Json<RegistrationRecord>, (StatusCode, String)> {
+    debug!("holo-client requested: {:?}", payload);
+    let delay = state
+        .lock()
+        .expect("HBS server state lock poisoned")
+        .script
+        .delay;
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let mut state = state.lock().expect("HBS server state lock poisoned");
+    state.requests.push(payload.clone());
+
+    if let Some(status) = state.script.failures.pop_front() {
+        return Err((status, format!("error code: {}", status.as_u16())));
+    }
+    if !state.script.skip_signature_check && !is_signed(&headers, &payload) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid signature".to_string()));
+    }
+
+    let record = match (
+        state.registrations.get(&payload.pub_key),
+        &state.default_jurisdiction,
+    ) {
+        (Some(record), _) => record.clone(),
+        (None, Some(jurisdiction)) => registration_record(&payload.pub_key, jurisdiction),
+        (None, None) => return Err((StatusCode::NOT_FOUND, "Host not found".to_string())),
+    };
+    Ok(Json(RegistrationRecord {
+        email: payload.email,
+        ..record
+    }))
+}
+
+/// Checks `X-Signature` header is a signature of msgpack encoded `payload` by its `pub_key`
+fn is_signed(headers: &HeaderMap, payload: &HoloClientPayload) -> bool {
+    let verify = || -> Option<()> {
+        let key = decode_agent_key(&payload.pub_key)?;
+        let header = headers.get("X-Signature")?.to_str().ok()?;
+        let bytes = base64::decode_config(header, base64::STANDARD_NO_PAD).ok()?;
+        let signature = Signature::from_slice(&bytes).ok()?;
+        let message = rmp_serde::to_vec_named(payload).ok()?;
+        key.verify(&message, &signature).ok()
+    };
+    verify().is_some()
+}
@@ -0,0 +1,5 @@
+//! Local stand-ins for Holo HTTP services used by hpos_hc_connect and configure-holochain,
+//! so that flows depending on them can be exercised without network access.
+
+pub mod hbs;
+pub mod membrane_proof;
@@ -0,0 +1,221 @@
+//! Stand-in for the membrane proof server queried by `hpos_membrane_proof::get_mem_proof`.
+//!
+//! Serves `POST /membrane-proof` taking the `Registration` JSON of the client in
+//! `hpos_membrane_proof` and responding with its `RegistrationRequest { mem_proof }`.
+//! Mem proof is a base64 encoded msgpack of `SignedMemProof`, signed with a configurable
+//! ed25519 test key.
+//! Responses for selected registration codes can be scripted to be `RegistrationError`s.
+
+use anyhow::{Context, Result};
+use axum::{extract::State, routing::post, Json, Router};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tracing::{debug, info};
+
+pub use axum::http::StatusCode;
+pub use hpos_hc_connect::hpos_membrane_proof::{
+    Registration, RegistrationError, RegistrationPayload, RegistrationRequest,
+};
+
+/// Seed of the signing key used when none is configured
+pub const DEFAULT_SIGNING_KEY_SEED: [u8; 32] = [7; 32];
+
+/// Error response played back instead of a mem proof
+#[derive(Debug, Clone)]
+pub struct ScriptedError {
+    pub status: StatusCode,
+    pub body: RegistrationError,
+}
+
+/// Data covered by the signature of a mock mem proof
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemProofContent {
+    pub role: String,
+    pub registration_code: String,
+    pub agent_pub_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedMemProof {
+    pub content: MemProofContent,
+    pub signature: Vec<u8>,
+}
+
+impl SignedMemProof {
+    /// Decodes base64 `mem_proof` string returned by the server
+    pub fn decode(mem_proof: &str) -> Result<Self> {
+        let bytes = base64::decode(mem_proof).context("mem proof is not valid base64")?;
+        rmp_serde::from_slice(&bytes).context("failed to deserialize mem proof")
+    }
+
+    pub fn verify(&self, key: &VerifyingKey) -> Result<()> {
+        let signature = Signature::from_slice(&self.signature)?;
+        key.verify(&rmp_serde::to_vec_named(&self.content)?, &signature)
+            .context("mem proof signature does not match")
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    errors: HashMap<String, ScriptedError>,
+    error_for_all: Option<ScriptedError>,
+}
+
+struct ServerState {
+    signing_key: SigningKey,
+    script: Script,
+    registrations: Vec<Registration>,
+}
+
+type SharedState = Arc<Mutex<ServerState>>;
+
+pub struct MemProofServerBuilder {
+    signing_key: SigningKey,
+    script: Script,
+}
+
+impl Default for MemProofServerBuilder {
+    fn default() -> Self {
+        Self {
+            signing_key: SigningKey::from_bytes(&DEFAULT_SIGNING_KEY_SEED),
+            script: Script::default(),
+        }
+    }
+}
+
+impl MemProofServerBuilder {
+    pub fn signing_key(mut self, signing_key: SigningKey) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
+    /// Responds with `error` to every registration carrying `registration_code`
+    pub fn error_for(mut self, registration_code: impl Into<String>, error: ScriptedError) -> Self {
+        self.script.errors.insert(registration_code.into(), error);
+        self
+    }
+
+    /// Responds with `error` to every registration, takes precedence over `error_for`
+    pub fn error_for_all(mut self, error: ScriptedError) -> Self {
+        self.script.error_for_all = Some(error);
+        self
+    }
+
+    /// Starts serving on `addr`, use port 0 to let OS pick a free one
+    pub async fn spawn(self, addr: SocketAddr) -> Result<MemProofServer> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .context(format!("failed to bind membrane proof server to {}", addr))?;
+        let addr = listener.local_addr()?;
+        let state: SharedState = Arc::new(Mutex::new(ServerState {
+            signing_key: self.signing_key,
+            script: self.script,
+            registrations: vec![],
+        }));
+
+        let app = Router::new()
+            .route("/membrane-proof", post(membrane_proof))
+            .with_state(state.clone());
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("membrane proof server stopped: {:?}", e);
+            }
+        });
+        info!("mock membrane proof server listening on {}", addr);
+
+        Ok(MemProofServer { addr, state, task })
+    }
+}
+
+/// Running membrane proof server, stops when dropped
+pub struct MemProofServer {
+    addr: SocketAddr,
+    state: SharedState,
+    task: JoinHandle<()>,
+}
+
+impl MemProofServer {
+    pub fn builder() -> MemProofServerBuilder {
+        MemProofServerBuilder::default()
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Value for MEM_PROOF_SERVER_URL
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Key verifying signatures of issued mem proofs
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.state().signing_key.verifying_key()
+    }
+
+    /// All registrations received so far, including the rejected ones
+    pub fn registrations(&self) -> Vec<Registration> {
+        self.state().registrations.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ServerState> {
+        self.state
+            .lock()
+            .expect("membrane proof server state lock poisoned")
+    }
+}
+
+impl Drop for MemProofServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn membrane_proof(
+    State(state): State<SharedState>,
+    Json(registration): Json<Registration>,
+) -> Result<Json<RegistrationRequest>, (StatusCode, Json<RegistrationError>)> {
+    debug!("membrane proof requested: {:?}", registration);
+    let mut state = state
+        .lock()
+        .expect("membrane proof server state lock poisoned");
+    state.registrations.push(registration.clone());
+
+    let scripted = state
+        .script
+        .error_for_all
+        .as_ref()
+        .or_else(|| state.script.errors.get(&registration.registration_code));
+    if let Some(e) = scripted {
+        return Err((e.status, Json(e.body.clone())));
+    }
+
+    let content = MemProofContent {
+        role: registration.payload.role,
+        registration_code: registration.registration_code,
+        agent_pub_key: registration.agent_pub_key,
+    };
+    let bytes = rmp_serde::to_vec_named(&content).map_err(internal_error)?;
+    let signed = SignedMemProof {
+        signature: state.signing_key.sign(&bytes).to_bytes().to_vec(),
+        content,
+    };
+    let mem_proof = rmp_serde::to_vec_named(&signed).map_err(internal_error)?;
+
+    Ok(Json(RegistrationRequest {
+        mem_proof: base64::encode(mem_proof),
+    }))
+}
+
+fn internal_error(e: impl std::fmt::Debug) -> (StatusCode, Json<RegistrationError>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(RegistrationError::new("Internal error", format!("{:?}", e))),
+    )
+}